@@ -0,0 +1,466 @@
+//! Local copies of album art, for tools such as bars and notification daemons
+//! that need a file instead of the URL a player reports.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "http")]
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::hash::stable_hash;
+use crate::player::Metadata;
+
+/// Images larger than this are not read.
+const MAX_IMAGE_SIZE: u64 = 32 * 1024 * 1024;
+
+#[cfg(feature = "http")]
+const MUSICBRAINZ_URL: &str = "https://musicbrainz.org/ws/2/release/";
+
+#[cfg(feature = "http")]
+const COVER_ART_ARCHIVE_URL: &str = "https://coverartarchive.org";
+
+/// How well, out of 100, a MusicBrainz release has to match the album to be
+/// taken for it.
+#[cfg(feature = "http")]
+const MIN_SCORE: u64 = 90;
+
+/// How long to wait for art URLs, MusicBrainz and the Cover Art Archive.
+#[cfg(feature = "http")]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Returns a local file with the cover art of the track described by
+/// `metadata`, copying or downloading it into `cache_dir/art` the first time.
+/// With a `size`, the image is scaled down to fit a `size`×`size` square.
+/// With `musicbrainz`, tracks without `mpris:artUrl` get the cover of their
+/// album from the Cover Art Archive, found through MusicBrainz.
+///
+/// Fails with [`Error::MissingMetadata`] if the track has no `mpris:artUrl`,
+/// and none could be found.
+pub fn fetch(
+    cache_dir: &Path,
+    player: &str,
+    metadata: &Metadata,
+    size: Option<u32>,
+    musicbrainz: bool,
+) -> Result<PathBuf> {
+    let missing = || Error::MissingMetadata {
+        player: player.to_string(),
+        field: String::from("cover art"),
+    };
+
+    if metadata.art_url().is_none() && !musicbrainz {
+        return Err(missing());
+    }
+
+    if size == Some(0) {
+        return Err(Error::InvalidArgument(String::from(
+            "The art size has to be at least 1.",
+        )));
+    }
+
+    let art_dir = cache_dir.join("art");
+
+    fs::create_dir_all(&art_dir)
+        .map_err(|why| Error::Other(format!("Failed to create art directory: {}", why)))?;
+
+    let (key, original) = match metadata.art_url() {
+        Some(url) => {
+            // The URL is part of the key since players reuse track ids, or
+            // change the art of a track once it has been loaded.
+            let key = format!(
+                "{:016x}",
+                stable_hash([metadata.track_id().unwrap_or_default(), url])
+            );
+
+            let original = match find_cached(&art_dir, &key)? {
+                Some(path) => path,
+                None => {
+                    let bytes = read_url(url)?;
+                    let path = art_dir.join(format!("{}.{}", key, extension(&bytes)));
+
+                    write_atomically(&path, &bytes)?;
+
+                    path
+                }
+            };
+
+            (key, original)
+        }
+        None => look_up(&art_dir, metadata)?.ok_or_else(missing)?,
+    };
+
+    match size {
+        Some(size) => resized(
+            &original,
+            &art_dir.join(format!("{}-{}.png", key, size)),
+            size,
+        ),
+        None => Ok(original),
+    }
+}
+
+/// Finds the cover of the track's album on the Cover Art Archive, keeping it
+/// under a key for the album. Albums without one are remembered as well, so
+/// that they are not looked up again.
+fn look_up(art_dir: &Path, metadata: &Metadata) -> Result<Option<(String, PathBuf)>> {
+    let artist = match metadata.album_artists().first() {
+        Some(artist) => Some(artist.to_string()),
+        None => metadata.artist(),
+    };
+
+    let (artist, album) = match (artist, metadata.album()) {
+        (Some(artist), Some(album)) => (artist, album),
+        _ => return Ok(None),
+    };
+
+    let key = format!("mb-{:016x}", stable_hash([artist.as_str(), album]));
+
+    if let Some(path) = find_cached(art_dir, &key)? {
+        return Ok(match path.extension().is_some_and(|e| e == "none") {
+            true => None,
+            false => Some((key, path)),
+        });
+    }
+
+    let bytes = cover_art_archive(&artist, album)
+        .map_err(|why| Error::Other(format!("Failed to look up cover art: {}", why)))?;
+
+    let path = match &bytes {
+        Some(bytes) => art_dir.join(format!("{}.{}", key, extension(bytes))),
+        None => art_dir.join(format!("{}.none", key)),
+    };
+
+    write_atomically(&path, bytes.as_deref().unwrap_or_default())?;
+
+    Ok(bytes.map(|_| (key, path)))
+}
+
+/// Finds the unscaled copy for `key`, whatever its extension.
+fn find_cached(art_dir: &Path, key: &str) -> Result<Option<PathBuf>> {
+    let prefix = format!("{}.", key);
+
+    for entry in fs::read_dir(art_dir)? {
+        let path = entry?.path();
+
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            if name.starts_with(&prefix) {
+                return Ok(Some(path));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn read_url(url: &str) -> Result<Vec<u8>> {
+    let read_error = |why: &dyn std::fmt::Display| {
+        Error::Other(format!("Failed to read cover art {}: {}", url, why))
+    };
+
+    // The player tells which file to read, so only images are.
+    let read_file = |path: &str| {
+        File::open(path)
+            .map_err(|why| why.into())
+            .and_then(read_image)
+            .map_err(|why| read_error(&why))
+    };
+
+    if let Some(path) = url.strip_prefix("file://") {
+        return read_file(&percent_decode(path));
+    }
+
+    if url.starts_with('/') {
+        return read_file(url);
+    }
+
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return download(url).map_err(|why| read_error(&why));
+    }
+
+    Err(Error::Other(format!("Unsupported cover art URL: {}", url)))
+}
+
+#[cfg(feature = "http")]
+fn download(url: &str) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    read_image(
+        ureq::get(url)
+            .timeout(REQUEST_TIMEOUT)
+            .call()?
+            .into_reader(),
+    )
+}
+
+/// Reads an image from `reader`, failing rather than keeping part of one
+/// larger than [`MAX_IMAGE_SIZE`], or something that is not an image at all.
+fn read_image(reader: impl Read) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = Vec::new();
+
+    reader.take(MAX_IMAGE_SIZE + 1).read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 > MAX_IMAGE_SIZE {
+        return Err(format!(
+            "the image is larger than {} MiB",
+            MAX_IMAGE_SIZE / 1024 / 1024
+        )
+        .into());
+    }
+
+    if extension(&bytes) == "img" {
+        return Err("not a PNG, JPEG, WebP or GIF image".into());
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "http"))]
+fn download(_url: &str) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Err("multiplayerctl was built without the `http` feature".into())
+}
+
+/// Searches MusicBrainz for the album and downloads the front cover of the
+/// first good match that has one.
+#[cfg(feature = "http")]
+fn cover_art_archive(
+    artist: &str,
+    album: &str,
+) -> std::result::Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    // Quotes and backslashes are all that need escaping in a quoted phrase.
+    let quoted = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+
+    let response = ureq::get(MUSICBRAINZ_URL)
+        .set("User-Agent", crate::USER_AGENT)
+        .timeout(REQUEST_TIMEOUT)
+        .query(
+            "query",
+            &format!("release:{} AND artist:{}", quoted(album), quoted(artist)),
+        )
+        .query("fmt", "json")
+        .query("limit", "5")
+        .call()?
+        .into_string()?;
+
+    let response: Value = serde_json::from_str(&response)?;
+
+    let mut groups: Vec<&str> = response["releases"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|release| release["score"].as_u64().is_some_and(|s| s >= MIN_SCORE))
+        .filter_map(|release| release["release-group"]["id"].as_str())
+        .collect();
+
+    groups.dedup();
+
+    for group in groups {
+        let url = format!(
+            "{}/release-group/{}/front-500",
+            COVER_ART_ARCHIVE_URL, group
+        );
+
+        match ureq::get(&url)
+            .set("User-Agent", crate::USER_AGENT)
+            .timeout(REQUEST_TIMEOUT)
+            .call()
+        {
+            Ok(response) => return Ok(Some(read_image(response.into_reader())?)),
+            Err(ureq::Error::Status(404, _)) => continue,
+            Err(why) => return Err(why.into()),
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(not(feature = "http"))]
+fn cover_art_archive(
+    _artist: &str,
+    _album: &str,
+) -> std::result::Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    Err("multiplayerctl was built without the `http` feature".into())
+}
+
+/// The colors of a cover, for theming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Colors {
+    /// The color covering the most of the image.
+    pub dominant: [u8; 3],
+    /// A vivid color that stands apart from the dominant one, or the
+    /// dominant one again if there is none.
+    pub accent: [u8; 3],
+}
+
+/// Formats a color like `#1e90ff`.
+pub fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// Picks the dominant and accent colors of the image at `path`, such as one
+/// returned by [`fetch`].
+#[cfg(feature = "resize")]
+pub fn colors(path: &Path) -> Result<Colors> {
+    use std::collections::HashMap;
+
+    let image = image::open(path)
+        .map_err(|why| Error::Other(format!("Failed to read cover art: {}", why)))?
+        .thumbnail(64, 64)
+        .to_rgba8();
+
+    // Pixels are grouped by the top four bits of each channel, so that
+    // shades of a color count together, and each group is averaged.
+    let mut groups: HashMap<[u8; 3], (u32, [u32; 3])> = HashMap::new();
+
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+
+        if a < 128 {
+            continue;
+        }
+
+        let (count, sum) = groups.entry([r >> 4, g >> 4, b >> 4]).or_default();
+        *count += 1;
+        sum[0] += u32::from(r);
+        sum[1] += u32::from(g);
+        sum[2] += u32::from(b);
+    }
+
+    let groups: Vec<(u32, [u8; 3])> = groups
+        .into_values()
+        .map(|(count, sum)| (count, sum.map(|c| (c / count) as u8)))
+        .collect();
+
+    let dominant = groups
+        .iter()
+        .max_by_key(|(count, _)| *count)
+        .map(|(_, color)| *color)
+        .ok_or_else(|| Error::Other(String::from("The cover art is see-through.")))?;
+
+    // Vivid and bright colors are favored over large but dull areas.
+    let vividness = |(count, color): &(u32, [u8; 3])| {
+        let max = f64::from(*color.iter().max().unwrap_or(&0));
+        let min = f64::from(*color.iter().min().unwrap_or(&0));
+        let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+
+        f64::from(*count) * saturation * saturation * max / 255.0
+    };
+
+    let accent = groups
+        .iter()
+        .filter(|(_, color)| distance(*color, dominant) >= MIN_ACCENT_DISTANCE)
+        .max_by(|a, b| vividness(a).total_cmp(&vividness(b)))
+        .map_or(dominant, |(_, color)| *color);
+
+    Ok(Colors { dominant, accent })
+}
+
+#[cfg(not(feature = "resize"))]
+pub fn colors(_path: &Path) -> Result<Colors> {
+    Err(Error::InvalidArgument(String::from(
+        "multiplayerctl was built without the `resize` feature.",
+    )))
+}
+
+/// How far apart, in RGB, the accent color has to be from the dominant one.
+#[cfg(feature = "resize")]
+const MIN_ACCENT_DISTANCE: f64 = 64.0;
+
+#[cfg(feature = "resize")]
+fn distance(a: [u8; 3], b: [u8; 3]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (f64::from(*a) - f64::from(b)).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[cfg(feature = "resize")]
+fn resized(original: &Path, path: &Path, size: u32) -> Result<PathBuf> {
+    if path.exists() {
+        return Ok(path.to_path_buf());
+    }
+
+    let image = image::open(original)
+        .map_err(|why| Error::Other(format!("Failed to read cover art: {}", why)))?;
+
+    let mut bytes = Vec::new();
+
+    image
+        .thumbnail(size, size)
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageFormat::Png,
+        )
+        .map_err(|why| Error::Other(format!("Failed to scale cover art: {}", why)))?;
+
+    write_atomically(path, &bytes)?;
+
+    Ok(path.to_path_buf())
+}
+
+#[cfg(not(feature = "resize"))]
+fn resized(_original: &Path, _path: &Path, _size: u32) -> Result<PathBuf> {
+    Err(Error::InvalidArgument(String::from(
+        "multiplayerctl was built without the `resize` feature.",
+    )))
+}
+
+/// Writes to a temporary file first, so readers never see a partial image.
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.part", name));
+
+    fs::write(&temporary, bytes)
+        .and_then(|_| fs::rename(&temporary, path))
+        .map_err(|why| Error::Other(format!("Failed to write {}: {}", path.display(), why)))
+}
+
+/// Picks a file extension from the image's magic bytes.
+fn extension(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "png",
+        [0xff, 0xd8, 0xff, ..] => "jpg",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "webp",
+        [b'G', b'I', b'F', b'8', ..] => "gif",
+        _ => "img",
+    }
+}
+
+/// The media type of an image, from its magic bytes, or `None` if it is not
+/// one of the formats cover art comes in.
+pub fn content_type(bytes: &[u8]) -> Option<&'static str> {
+    match extension(bytes) {
+        "png" => Some("image/png"),
+        "jpg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        "gif" => Some("image/gif"),
+        _ => None,
+    }
+}
+
+/// Decodes `%XX` escapes in the path of a `file://` URL.
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], escaped) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,901 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use zbus::zvariant::OwnedValue;
+
+use crate::error::{Error, Result};
+use crate::mpris::{self, Mpris};
+use crate::player::{Capability, Change, LoopStatus, Metadata, MetadataValue, PlaybackStatus};
+use crate::playerctl;
+use crate::pulse::{self, SinkInput};
+
+/// A source of players other than the session bus, such as an MPD server.
+/// The players it lists appear next to the MPRIS players, and everything
+/// done to them goes through it.
+///
+/// Only listing players, their status and metadata has to be implemented;
+/// anything else fails with [`Error::Unsupported`] unless it is.
+pub trait PlayerBackend: Send + Sync {
+    /// Whether the player named `player` is one of this source's, whether or
+    /// not it is available right now.
+    fn owns(&self, player: &str) -> bool;
+
+    /// The players available right now.
+    fn list_players(&self) -> Result<Vec<String>>;
+
+    fn playback_status(&self, player: &str) -> Result<PlaybackStatus>;
+
+    fn metadata(&self, player: &str) -> Result<Metadata>;
+
+    /// Runs `play`, `pause`, `play-pause`, `next`, `previous` or `stop`.
+    fn player_command(&self, player: &str, command: &str) -> Result<()> {
+        Err(unsupported(player, command))
+    }
+
+    fn open_uri(&self, player: &str, _uri: &str) -> Result<()> {
+        Err(unsupported(player, "open URIs"))
+    }
+
+    /// Whether the player supports `capability`, assumed to be true.
+    fn can(&self, _player: &str, _capability: Capability) -> bool {
+        true
+    }
+
+    fn volume(&self, player: &str) -> Result<f64> {
+        Err(unsupported(player, "report its volume"))
+    }
+
+    fn set_volume(&self, player: &str, _volume: f64) -> Result<()> {
+        Err(unsupported(player, "change its volume"))
+    }
+
+    /// Whether the player is muted, for sources that can mute it without
+    /// losing its volume. Others are muted by setting the volume to zero.
+    fn muted(&self, player: &str) -> Result<bool> {
+        Err(unsupported(player, "mute"))
+    }
+
+    fn set_muted(&self, player: &str, _muted: bool) -> Result<()> {
+        Err(unsupported(player, "mute"))
+    }
+
+    fn loop_status(&self, player: &str) -> Result<LoopStatus> {
+        Err(unsupported(player, "loop"))
+    }
+
+    fn set_loop_status(&self, player: &str, _status: LoopStatus) -> Result<()> {
+        Err(unsupported(player, "loop"))
+    }
+
+    fn shuffle(&self, player: &str) -> Result<bool> {
+        Err(unsupported(player, "shuffle"))
+    }
+
+    fn set_shuffle(&self, player: &str, _shuffle: bool) -> Result<()> {
+        Err(unsupported(player, "shuffle"))
+    }
+
+    /// Returns the playback position in microseconds.
+    fn position(&self, player: &str) -> Result<i64> {
+        Err(unsupported(player, "report its position"))
+    }
+
+    /// Sets the absolute playback position in microseconds.
+    fn set_position(&self, player: &str, _position: i64) -> Result<()> {
+        Err(unsupported(player, "seek"))
+    }
+
+    /// Seeks by a relative offset in microseconds.
+    fn seek(&self, player: &str, _offset: i64) -> Result<()> {
+        Err(unsupported(player, "seek"))
+    }
+
+    /// Blocks, calling `f` with the player name and what happened whenever
+    /// anything about one of its players changes. Without an implementation,
+    /// changes are only noticed when something else looks.
+    fn watch_changes(&self, f: &mut dyn FnMut(&str, Change)) -> Result<()> {
+        let _ = f;
+
+        Ok(())
+    }
+}
+
+/// How commands reach the players: natively over D-Bus, or by shelling out to
+/// playerctl, and through any other [sources](PlayerBackend) added.
+#[derive(Clone)]
+pub struct Backend {
+    /// The players on the session bus, which are all those no source owns.
+    bus: Arc<dyn PlayerBackend>,
+    /// The connection to the session bus, for what only MPRIS offers, unless
+    /// commands go through playerctl.
+    mpris: Option<Mpris>,
+    sources: Vec<Arc<dyn PlayerBackend>>,
+    /// How long a playerctl command may take, see [`with_timeout`](Self::with_timeout).
+    timeout: Option<Duration>,
+}
+
+/// The players on the session bus, reached over D-Bus.
+struct SessionBus(Mpris);
+
+/// The players on the session bus, reached through playerctl.
+struct Playerctl {
+    timeout: Option<Duration>,
+}
+
+/// What the watchers of the bus and of the sources report to the thread
+/// that is watching them all.
+enum Watched<T> {
+    Change(String, T),
+    Stopped(Result<()>),
+}
+
+impl Backend {
+    /// Creates the backend named by `kind` (`mpris` or `playerctl`). The MPRIS
+    /// backend falls back to playerctl if the session bus is unavailable.
+    pub fn new(kind: &str) -> Backend {
+        let mpris = match kind {
+            "playerctl" => None,
+            _ => Mpris::new().ok(),
+        };
+
+        let bus: Arc<dyn PlayerBackend> = match &mpris {
+            Some(m) => Arc::new(SessionBus(m.clone())),
+            None => Arc::new(Playerctl { timeout: None }),
+        };
+
+        Backend {
+            bus,
+            mpris,
+            sources: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    /// Whether commands go through playerctl, as asked for or because the
+    /// session bus was unavailable.
+    pub fn uses_playerctl(&self) -> bool {
+        self.mpris.is_none()
+    }
+
+    /// Adds the players of `source`. Where one has the same name as an MPRIS
+    /// player, it takes its place.
+    pub fn with_source(mut self, source: Arc<dyn PlayerBackend>) -> Backend {
+        self.sources.push(source);
+        self
+    }
+
+    /// Kills playerctl commands that take longer than `timeout`, failing
+    /// with [`Error::Stalled`], instead of waiting for them forever.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Backend {
+        if self.uses_playerctl() {
+            self.bus = Arc::new(Playerctl { timeout });
+        }
+
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// A copy watching only the session bus, to be moved to another thread.
+    fn bus_only(&self) -> Backend {
+        Backend {
+            bus: Arc::clone(&self.bus),
+            mpris: self.mpris.clone(),
+            sources: Vec::new(),
+            timeout: self.timeout,
+        }
+    }
+
+    /// The source `player` belongs to, or `None` for the session bus.
+    fn source(&self, player: &str) -> Option<&dyn PlayerBackend> {
+        self.sources
+            .iter()
+            .find(|source| source.owns(player))
+            .map(|source| source.as_ref())
+    }
+
+    /// Where everything done to `player` goes: its source, or else the
+    /// session bus.
+    fn reaching(&self, player: &str) -> &dyn PlayerBackend {
+        self.source(player).unwrap_or(self.bus.as_ref())
+    }
+
+    pub fn list_players(&self) -> Result<Vec<String>> {
+        let mut players = self.bus.list_players()?;
+
+        players.retain(|p| p != mpris::PROXY_PLAYER && self.source(p).is_none());
+
+        // A source that cannot be reached simply has no players.
+        for source in &self.sources {
+            players.extend(source.list_players().unwrap_or_default());
+        }
+
+        Ok(players)
+    }
+
+    /// Runs a simple playerctl-style command (`play-pause`, `next`, ...) on a player.
+    pub fn player_command(&self, player: &str, command: &str) -> Result<()> {
+        self.reaching(player).player_command(player, command)
+    }
+
+    pub fn open_uri(&self, player: &str, uri: &str) -> Result<()> {
+        self.reaching(player).open_uri(player, uri)
+    }
+
+    /// Calls `Raise` or `Quit` on the player. playerctl has no equivalent for these.
+    pub fn root_command(&self, player: &str, method: &str) -> Result<()> {
+        if self.source(player).is_some() {
+            return Err(unsupported(player, &method.to_lowercase()));
+        }
+
+        match &self.mpris {
+            Some(m) => match m.call_root(player, method) {
+                // Players may exit before getting around to answering Quit.
+                Err(zbus::Error::MethodError(name, _, _))
+                    if method == "Quit"
+                        && name.as_str() == "org.freedesktop.DBus.Error.NoReply" =>
+                {
+                    Ok(())
+                }
+                result => result
+                    .map_err(|why| mpris_error(player, &format!("Failed to call {}", method), why)),
+            },
+            None => Err(Error::player_command(
+                player,
+                format!("{} is not supported by the playerctl backend", method),
+            )),
+        }
+    }
+
+    pub fn playback_status(&self, player: &str) -> Result<PlaybackStatus> {
+        self.reaching(player).playback_status(player)
+    }
+
+    pub fn metadata(&self, player: &str) -> Result<Metadata> {
+        self.reaching(player).metadata(player)
+    }
+
+    /// Whether the player supports `capability`. Assumed to be true when it
+    /// can't be told, which is always the case with playerctl.
+    pub fn can(&self, player: &str, capability: Capability) -> bool {
+        self.reaching(player).can(player, capability)
+    }
+
+    /// The player's volume, or for players without one, such as browsers,
+    /// that of its streams in PulseAudio or PipeWire.
+    pub fn volume(&self, player: &str) -> Result<f64> {
+        match self.reaching(player).volume(player) {
+            Err(why @ Error::UnknownPlayer(_)) => Err(why),
+            Err(why) => match self.sink_inputs(player).ok().filter(|i| !i.is_empty()) {
+                Some(inputs) => Ok(inputs[0].volume),
+                None => Err(why),
+            },
+            volume => volume,
+        }
+    }
+
+    /// Sets the player's volume, falling back to its streams like
+    /// [`Backend::volume`].
+    pub fn set_volume(&self, player: &str, volume: f64) -> Result<()> {
+        match self.reaching(player).set_volume(player, volume) {
+            Err(why @ Error::UnknownPlayer(_)) => Err(why),
+            Err(why) => match self.sink_inputs(player).ok().filter(|i| !i.is_empty()) {
+                Some(inputs) => inputs
+                    .iter()
+                    .try_for_each(|input| pulse::set_sink_input_volume(input.index, volume))
+                    .map_err(|pulse_why| Error::player_command(player, pulse_why)),
+                None => Err(why),
+            },
+            result => result,
+        }
+    }
+
+    /// The name the player would like to be shown as. Only MPRIS players
+    /// report one.
+    pub fn identity(&self, player: &str) -> Result<String> {
+        match (self.source(player), &self.mpris) {
+            (None, Some(m)) => m
+                .identity(player)
+                .map_err(|why| mpris_error(player, "Failed to get identity", why)),
+            _ => Err(unsupported(player, "report its name")),
+        }
+    }
+
+    /// Whether the player is muted, if its source can mute it by itself.
+    /// MPRIS players can't.
+    pub fn muted(&self, player: &str) -> Result<bool> {
+        self.reaching(player).muted(player)
+    }
+
+    pub fn set_muted(&self, player: &str, muted: bool) -> Result<()> {
+        self.reaching(player).set_muted(player, muted)
+    }
+
+    /// The player's streams in PulseAudio or PipeWire.
+    pub fn sink_inputs(&self, player: &str) -> Result<Vec<SinkInput>> {
+        let pid = match (self.source(player), &self.mpris) {
+            (None, Some(m)) => m.process_id(player).ok(),
+            _ => None,
+        };
+
+        pulse::sink_inputs_of(player, pid)
+    }
+
+    pub fn loop_status(&self, player: &str) -> Result<LoopStatus> {
+        self.reaching(player).loop_status(player)
+    }
+
+    pub fn set_loop_status(&self, player: &str, status: LoopStatus) -> Result<()> {
+        self.reaching(player).set_loop_status(player, status)
+    }
+
+    pub fn shuffle(&self, player: &str) -> Result<bool> {
+        self.reaching(player).shuffle(player)
+    }
+
+    pub fn set_shuffle(&self, player: &str, shuffle: bool) -> Result<()> {
+        self.reaching(player).set_shuffle(player, shuffle)
+    }
+
+    /// Blocks forever, calling `f` whenever a player's playback status changes.
+    pub fn watch_status<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&str, PlaybackStatus),
+    {
+        if self.sources.is_empty() {
+            return self.watch_bus_status(f);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+
+        for source in &self.sources {
+            let source = Arc::clone(source);
+            let sender = sender.clone();
+
+            // Sources report changes of any kind, so the status is compared
+            // with the one last seen.
+            thread::spawn(move || {
+                let mut statuses = HashMap::new();
+
+                let watched = source.watch_changes(&mut |player, _| {
+                    let status = match source.playback_status(player) {
+                        Ok(s) => s,
+                        Err(_) => return,
+                    };
+
+                    if statuses.insert(player.to_string(), status) != Some(status) {
+                        let _ = sender.send(Watched::Change(player.to_string(), status));
+                    }
+                });
+
+                if let Err(why) = watched {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        let bus = self.bus_only();
+
+        thread::spawn(move || {
+            let watched = bus.watch_bus_status(|player, status| {
+                let _ = sender.send(Watched::Change(player.to_string(), status));
+            });
+
+            let _ = sender.send(Watched::Stopped(watched));
+        });
+
+        receive(receiver, |player, status| f(player, status))
+    }
+
+    fn watch_bus_status<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&str, PlaybackStatus),
+    {
+        let m = match &self.mpris {
+            Some(m) => m,
+            None => return playerctl_watch_status(f),
+        };
+
+        m.watch_playback_status(|player, status| {
+            if let Ok(status) = status.parse() {
+                f(player, status);
+            }
+        })
+        .map_err(|why| Error::Other(format!("Failed to watch players: {}", why)))
+    }
+
+    /// Blocks forever, calling `f` with the player name and what happened
+    /// whenever anything about a player changes. playerctl only reports
+    /// [`Change::Properties`].
+    pub fn watch_changes<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&str, Change),
+    {
+        if self.sources.is_empty() {
+            return self.watch_bus_changes(f);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+
+        for source in &self.sources {
+            let source = Arc::clone(source);
+            let sender = sender.clone();
+
+            thread::spawn(move || {
+                let watched = source.watch_changes(&mut |player, change| {
+                    let _ = sender.send(Watched::Change(player.to_string(), change));
+                });
+
+                if let Err(why) = watched {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        let bus = self.bus_only();
+
+        thread::spawn(move || {
+            let watched = bus.watch_bus_changes(|player, change| {
+                let _ = sender.send(Watched::Change(player.to_string(), change));
+            });
+
+            let _ = sender.send(Watched::Stopped(watched));
+        });
+
+        receive(receiver, |player, change| f(player, change))
+    }
+
+    fn watch_bus_changes<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&str, Change),
+    {
+        self.bus.watch_changes(&mut f)
+    }
+
+    /// Returns the playback position in microseconds.
+    pub fn position(&self, player: &str) -> Result<i64> {
+        self.reaching(player).position(player)
+    }
+
+    /// Sets the absolute playback position in microseconds.
+    pub fn set_position(&self, player: &str, position: i64) -> Result<()> {
+        self.reaching(player).set_position(player, position)
+    }
+
+    /// Seeks by a relative offset in microseconds.
+    pub fn seek(&self, player: &str, offset: i64) -> Result<()> {
+        self.reaching(player).seek(player, offset)
+    }
+
+    /// The tracks in the player's track list, such as its queue, each with
+    /// its metadata.
+    pub fn tracks(&self, player: &str) -> Result<Vec<Metadata>> {
+        let m = self.track_list(player)?;
+
+        let tracks = m
+            .tracks(player)
+            .map_err(|why| mpris_error(player, "Failed to get tracks", why))?;
+
+        if tracks.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let metadata = m
+            .tracks_metadata(player, &tracks)
+            .map_err(|why| mpris_error(player, "Failed to get track metadata", why))?;
+
+        Ok(tracks
+            .iter()
+            .zip(metadata)
+            .map(|(id, raw)| {
+                let mut values = mpris_values(&raw);
+
+                // Some players leave the id out, though it is how tracks are
+                // told apart.
+                values
+                    .entry(String::from("mpris:trackid"))
+                    .or_insert_with(|| MetadataValue::Text(id.to_string()));
+
+                Metadata::new(values)
+            })
+            .collect())
+    }
+
+    /// Adds the track at `uri` to the track list after the track with the
+    /// id `after`, or at the start with `None`.
+    pub fn add_track(&self, player: &str, uri: &str, after: Option<&str>) -> Result<()> {
+        self.editable_track_list(player)?
+            .add_track(player, uri, after.unwrap_or(mpris::NO_TRACK))
+            .map_err(|why| mpris_error(player, "Failed to add track", why))
+    }
+
+    pub fn remove_track(&self, player: &str, track_id: &str) -> Result<()> {
+        self.editable_track_list(player)?
+            .remove_track(player, track_id)
+            .map_err(|why| mpris_error(player, "Failed to remove track", why))
+    }
+
+    /// Skips to the track with the id `track_id` in the track list.
+    pub fn go_to(&self, player: &str, track_id: &str) -> Result<()> {
+        self.track_list(player)?
+            .go_to(player, track_id)
+            .map_err(|why| mpris_error(player, "Failed to go to track", why))
+    }
+
+    /// The MPRIS connection, if the player has a track list.
+    fn track_list(&self, player: &str) -> Result<&Mpris> {
+        if self.source(player).is_some() {
+            return Err(unsupported(player, "show its queue"));
+        }
+
+        let m = match &self.mpris {
+            Some(m) => m,
+            None => {
+                return Err(Error::player_command(
+                    player,
+                    "Track lists are not supported by the playerctl backend",
+                ))
+            }
+        };
+
+        match m.has_track_list(player) {
+            Ok(true) => Ok(m),
+            Ok(false) => Err(Error::Unsupported {
+                player: player.to_string(),
+                action: String::from("show its queue"),
+            }),
+            Err(why) => Err(mpris_error(player, "Failed to look for a track list", why)),
+        }
+    }
+
+    /// Like [`Backend::track_list`], if tracks can also be added and removed.
+    fn editable_track_list(&self, player: &str) -> Result<&Mpris> {
+        let m = self.track_list(player)?;
+
+        match m.can_edit_tracks(player) {
+            Ok(true) => Ok(m),
+            Ok(false) => Err(Error::Unsupported {
+                player: player.to_string(),
+                action: String::from("edit its queue"),
+            }),
+            Err(why) => Err(mpris_error(player, "Failed to get CanEditTracks", why)),
+        }
+    }
+}
+
+impl PlayerBackend for SessionBus {
+    fn owns(&self, _player: &str) -> bool {
+        true
+    }
+
+    fn list_players(&self) -> Result<Vec<String>> {
+        self.0
+            .list_players()
+            .map_err(|why| Error::Other(format!("Failed to get player list: {}", why)))
+    }
+
+    fn playback_status(&self, player: &str) -> Result<PlaybackStatus> {
+        self.0
+            .playback_status(player)
+            .map_err(|why| mpris_error(player, "Failed to get status", why))?
+            .trim()
+            .parse()
+            .map_err(|why| Error::player_command(player, why))
+    }
+
+    fn metadata(&self, player: &str) -> Result<Metadata> {
+        let raw = self
+            .0
+            .metadata(player)
+            .map_err(|why| mpris_error(player, "Failed to get metadata", why))?;
+
+        Ok(Metadata::new(mpris_values(&raw)))
+    }
+
+    fn player_command(&self, player: &str, command: &str) -> Result<()> {
+        let method = match command {
+            "play-pause" => "PlayPause",
+            "play" => "Play",
+            "pause" => "Pause",
+            "next" => "Next",
+            "previous" => "Previous",
+            "stop" => "Stop",
+            _ => {
+                return Err(Error::InvalidArgument(format!(
+                    "Unknown player command: {}",
+                    command
+                )))
+            }
+        };
+
+        self.0
+            .call(player, method)
+            .map_err(|why| mpris_error(player, &format!("Failed to {}", command), why))
+    }
+
+    fn open_uri(&self, player: &str, uri: &str) -> Result<()> {
+        self.0
+            .open_uri(player, uri)
+            .map_err(|why| mpris_error(player, "Failed to open URI", why))
+    }
+
+    fn can(&self, player: &str, capability: Capability) -> bool {
+        self.0
+            .capability(player, capability.property())
+            .unwrap_or(true)
+    }
+
+    fn volume(&self, player: &str) -> Result<f64> {
+        self.0
+            .volume(player)
+            .map_err(|why| mpris_error(player, "Failed to get volume", why))
+    }
+
+    fn set_volume(&self, player: &str, volume: f64) -> Result<()> {
+        self.0
+            .set_volume(player, volume)
+            .map_err(|why| mpris_error(player, "Failed to set volume", why))
+    }
+
+    fn loop_status(&self, player: &str) -> Result<LoopStatus> {
+        self.0
+            .loop_status(player)
+            .map_err(|why| mpris_error(player, "Failed to get loop status", why))?
+            .trim()
+            .parse()
+            .map_err(|why| Error::player_command(player, why))
+    }
+
+    fn set_loop_status(&self, player: &str, status: LoopStatus) -> Result<()> {
+        self.0
+            .set_loop_status(player, &status.to_string())
+            .map_err(|why| mpris_error(player, "Failed to set loop status", why))
+    }
+
+    fn shuffle(&self, player: &str) -> Result<bool> {
+        self.0
+            .shuffle(player)
+            .map_err(|why| mpris_error(player, "Failed to get shuffle", why))
+    }
+
+    fn set_shuffle(&self, player: &str, shuffle: bool) -> Result<()> {
+        self.0
+            .set_shuffle(player, shuffle)
+            .map_err(|why| mpris_error(player, "Failed to set shuffle", why))
+    }
+
+    fn position(&self, player: &str) -> Result<i64> {
+        self.0
+            .position(player)
+            .map_err(|why| mpris_error(player, "Failed to get position", why))
+    }
+
+    fn set_position(&self, player: &str, position: i64) -> Result<()> {
+        self.0
+            .set_position(player, position)
+            .map_err(|why| mpris_error(player, "Failed to set position", why))
+    }
+
+    fn seek(&self, player: &str, offset: i64) -> Result<()> {
+        self.0
+            .seek(player, offset)
+            .map_err(|why| mpris_error(player, "Failed to seek", why))
+    }
+
+    fn watch_changes(&self, f: &mut dyn FnMut(&str, Change)) -> Result<()> {
+        self.0
+            .watch_changes(f)
+            .map_err(|why| Error::Other(format!("Failed to watch players: {}", why)))
+    }
+}
+
+impl PlayerBackend for Playerctl {
+    fn owns(&self, _player: &str) -> bool {
+        true
+    }
+
+    fn list_players(&self) -> Result<Vec<String>> {
+        playerctl::list_players(self.timeout)
+    }
+
+    fn playback_status(&self, player: &str) -> Result<PlaybackStatus> {
+        playerctl::query(player, &["status"], self.timeout)?
+            .trim()
+            .parse()
+            .map_err(|why| Error::player_command(player, why))
+    }
+
+    fn metadata(&self, player: &str) -> Result<Metadata> {
+        let table = playerctl::query(player, &["metadata"], self.timeout)?;
+
+        let mut values = BTreeMap::new();
+
+        // Each line looks like `<player> <key>   <value>`.
+        for line in table.lines() {
+            let mut parts = line.splitn(2, ' ');
+            let rest = match (parts.next(), parts.next()) {
+                (Some(_), Some(rest)) => rest.trim_start(),
+                _ => continue,
+            };
+
+            match rest.split_once(char::is_whitespace) {
+                Some((key, value)) => {
+                    values.insert(key.into(), MetadataValue::parse(key, value.trim()))
+                }
+                None => values.insert(rest.into(), MetadataValue::Text(String::new())),
+            };
+        }
+
+        Ok(Metadata::new(values))
+    }
+
+    fn player_command(&self, player: &str, command: &str) -> Result<()> {
+        playerctl::query(player, &[command], self.timeout).map(|_| ())
+    }
+
+    fn open_uri(&self, player: &str, uri: &str) -> Result<()> {
+        playerctl::query(player, &["open", uri], self.timeout).map(|_| ())
+    }
+
+    fn volume(&self, player: &str) -> Result<f64> {
+        playerctl::query(player, &["volume"], self.timeout).and_then(|v| parse_number(&v))
+    }
+
+    fn set_volume(&self, player: &str, volume: f64) -> Result<()> {
+        playerctl::query(player, &["volume", &volume.to_string()], self.timeout).map(|_| ())
+    }
+
+    fn loop_status(&self, player: &str) -> Result<LoopStatus> {
+        playerctl::query(player, &["loop"], self.timeout)?
+            .trim()
+            .parse()
+            .map_err(|why| Error::player_command(player, why))
+    }
+
+    fn set_loop_status(&self, player: &str, status: LoopStatus) -> Result<()> {
+        playerctl::query(player, &["loop", &status.to_string()], self.timeout).map(|_| ())
+    }
+
+    fn shuffle(&self, player: &str) -> Result<bool> {
+        Ok(playerctl::query(player, &["shuffle"], self.timeout)?.trim() == "On")
+    }
+
+    fn set_shuffle(&self, player: &str, shuffle: bool) -> Result<()> {
+        let value = if shuffle { "On" } else { "Off" };
+
+        playerctl::query(player, &["shuffle", value], self.timeout).map(|_| ())
+    }
+
+    fn position(&self, player: &str) -> Result<i64> {
+        parse_number(&playerctl::query(player, &["position"], self.timeout)?)
+            .map(|seconds| (seconds * 1_000_000.0) as i64)
+    }
+
+    fn set_position(&self, player: &str, position: i64) -> Result<()> {
+        let seconds = (position as f64 / 1_000_000.0).to_string();
+
+        playerctl::query(player, &["position", &seconds], self.timeout).map(|_| ())
+    }
+
+    fn seek(&self, player: &str, offset: i64) -> Result<()> {
+        let sign = if offset < 0 { '-' } else { '+' };
+        let seconds = format!("{}{}", offset.abs() as f64 / 1_000_000.0, sign);
+
+        playerctl::query(player, &["position", &seconds], self.timeout).map(|_| ())
+    }
+
+    /// Only ever reports [`Change::Properties`].
+    fn watch_changes(&self, f: &mut dyn FnMut(&str, Change)) -> Result<()> {
+        let mut child = playerctl::spawn([
+            "--all-players",
+            "--follow",
+            "--format={{playerInstance}}\t{{status}}\t{{volume}}\t{{mpris:trackid}}\t{{xesam:title}}\t{{xesam:artist}}\t{{xesam:album}}",
+            "metadata",
+        ])?;
+
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines() {
+                if let Some((player, _)) = line?.split_once('\t') {
+                    f(player, Change::Properties);
+                }
+            }
+        }
+
+        child.wait()?;
+
+        Ok(())
+    }
+}
+
+/// Follows the playback status of every player through playerctl, which,
+/// unlike other changes, it can report on its own.
+fn playerctl_watch_status(mut f: impl FnMut(&str, PlaybackStatus)) -> Result<()> {
+    let mut child = playerctl::spawn([
+        "--all-players",
+        "--follow",
+        "--format={{playerInstance}}\t{{status}}",
+        "status",
+    ])?;
+
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let line = line?;
+
+            if let Some((player, status)) = line.split_once('\t') {
+                if let Ok(status) = status.parse() {
+                    f(player, status);
+                }
+            }
+        }
+    }
+
+    child.wait()?;
+
+    Ok(())
+}
+
+/// Passes what the watchers report to `f` until the session bus watcher
+/// stops.
+fn receive<T>(receiver: Receiver<Watched<T>>, mut f: impl FnMut(&str, T)) -> Result<()> {
+    for watched in receiver {
+        match watched {
+            Watched::Change(player, change) => f(&player, change),
+            Watched::Stopped(result) => return result,
+        }
+    }
+
+    Ok(())
+}
+
+fn unsupported(player: &str, action: &str) -> Error {
+    Error::Unsupported {
+        player: player.to_string(),
+        action: action.to_string(),
+    }
+}
+
+fn mpris_values(raw: &HashMap<String, OwnedValue>) -> BTreeMap<String, MetadataValue> {
+    raw.iter()
+        .map(|(k, v)| (k.clone(), mpris::metadata_value(v)))
+        .collect()
+}
+
+fn parse_number(s: &str) -> Result<f64> {
+    s.trim().parse().map_err(|why| {
+        Error::Other(format!(
+            "Unexpected playerctl output '{}': {}",
+            s.trim(),
+            why
+        ))
+    })
+}
+
+/// Converts a D-Bus error, recognizing the errors raised for players that are not running.
+fn mpris_error(player: &str, action: &str, why: zbus::Error) -> Error {
+    let unknown = match &why {
+        zbus::Error::MethodError(name, _, _) => {
+            let name = name.as_str();
+
+            name == "org.freedesktop.DBus.Error.ServiceUnknown"
+                || name == "org.freedesktop.DBus.Error.NameHasNoOwner"
+        }
+        zbus::Error::FDO(e) => matches!(
+            **e,
+            zbus::fdo::Error::ServiceUnknown(_) | zbus::fdo::Error::NameHasNoOwner(_)
+        ),
+        _ => false,
+    };
+
+    if unknown {
+        Error::UnknownPlayer(player.to_string())
+    } else {
+        Error::player_command(player, format!("{}: {}", action, why))
+    }
+}
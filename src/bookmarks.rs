@@ -0,0 +1,156 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use multiplayerctl::{Error, Metadata, PlaybackStatus, PlayerManager, Result};
+use serde_json::json;
+
+use crate::follow;
+
+/// How often positions are saved while a player is playing.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Positions this early in a track, in microseconds, are not saved, so that
+/// starting a track over does not lose where it was left off.
+const MIN_POSITION: i64 = 30_000_000;
+
+/// Tracks with less than this left to play, in microseconds, count as
+/// finished, and their positions are forgotten. Longer than
+/// `SAVE_INTERVAL`, so that the end is always noticed.
+const FINISHED: i64 = 30_000_000;
+
+/// Positions in microseconds, by track.
+type Bookmarks = BTreeMap<String, i64>;
+
+/// Seeks the current player to where its track was left off.
+pub fn resume(manager: &PlayerManager) -> Result<String> {
+    let player = manager.current()?;
+    let metadata = player.metadata()?;
+
+    let position = track_key(&metadata).and_then(|key| load(manager).remove(&key));
+
+    match position {
+        Some(p) => player.set_position(p).map(|_| String::new()),
+        None => Err(Error::Other(String::from(
+            "No position is saved for this track.",
+        ))),
+    }
+}
+
+/// Saves the positions of the players included by `[bookmarks]` as they
+/// play, and with `auto_resume` seeks back to them, until watching the
+/// players fails.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let mut bookmarks = load(&manager);
+    // The track each player was on when last looked at.
+    let mut tracks: HashMap<String, String> = HashMap::new();
+    let playing = Cell::new(false);
+
+    follow::watch(
+        manager,
+        |_| playing.get().then_some(SAVE_INTERVAL),
+        |manager| {
+            let config = &manager.config().bookmarks;
+
+            let players: Vec<String> = manager
+                .players()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|name| config.includes(name))
+                .collect();
+
+            tracks.retain(|name, _| players.contains(name));
+            playing.set(false);
+
+            let mut changed = false;
+
+            for name in players {
+                let player = manager.player(&name);
+                let metadata = player.metadata().unwrap_or_default();
+
+                let key = match track_key(&metadata) {
+                    Some(k) => k,
+                    None => continue,
+                };
+
+                let started = tracks.insert(name, key.clone()).as_ref() != Some(&key);
+
+                match player.status() {
+                    Ok(PlaybackStatus::Playing) => playing.set(true),
+                    Ok(PlaybackStatus::Paused) => (),
+                    // Stopped players report no useful position.
+                    _ => continue,
+                }
+
+                let position = match player.position() {
+                    Ok(p) => p,
+                    Err(_) => continue,
+                };
+
+                let saved = bookmarks.get(&key).copied();
+
+                // Only when started over, not when it was already playing
+                // further along.
+                if started && config.auto_resume && position < MIN_POSITION {
+                    if let Some(saved) = saved {
+                        if let Err(why) = player.set_position(saved) {
+                            eprintln!("{}", why);
+                        }
+
+                        continue;
+                    }
+                }
+
+                if metadata.length().is_some_and(|l| position >= l - FINISHED) {
+                    changed |= bookmarks.remove(&key).is_some();
+                } else if position >= MIN_POSITION && saved != Some(position) {
+                    bookmarks.insert(key, position);
+                    changed = true;
+                }
+            }
+
+            if changed {
+                if let Err(why) = store(manager, &bookmarks) {
+                    eprintln!("{}", why);
+                }
+            }
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+/// What a track is remembered by: its location, which unlike its track id
+/// stays the same between runs of the player, or else its artist and title.
+fn track_key(metadata: &Metadata) -> Option<String> {
+    if let Some(url) = metadata.url() {
+        return Some(url.to_string());
+    }
+
+    let title = metadata.title()?;
+
+    Some(match metadata.artist() {
+        Some(artist) => format!("{} - {}", artist, title),
+        None => title.to_string(),
+    })
+}
+
+fn bookmarks_file(manager: &PlayerManager) -> PathBuf {
+    manager.state_path().join("bookmarks.json")
+}
+
+fn load(manager: &PlayerManager) -> Bookmarks {
+    fs::read_to_string(bookmarks_file(manager))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn store(manager: &PlayerManager, bookmarks: &Bookmarks) -> Result<()> {
+    fs::write(bookmarks_file(manager), json!(bookmarks).to_string())
+        .map_err(|why| Error::Other(format!("Failed to write the bookmarks: {}", why)))
+}
@@ -0,0 +1,97 @@
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::process::{Child, ExitStatus};
+use std::sync::Mutex;
+
+/// The pids of the children that are guarded and not yet reaped, for
+/// [`ChildGuard::kill_all`]. Only changed with the lock held, so that a pid
+/// is never killed after it was reaped and may belong to another process.
+static RUNNING: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// A long-running child process, such as `playerctl --follow`, that is
+/// killed and waited for when dropped. However its owner stops reading from
+/// it, be it on a switch, an error or a closed stdout, it neither keeps
+/// running nor lingers as a zombie.
+pub struct ChildGuard(Child);
+
+impl ChildGuard {
+    pub fn new(child: Child) -> ChildGuard {
+        lock().push(child.id());
+
+        ChildGuard(child)
+    }
+
+    /// Waits for the child to exit, like [`Child::wait`].
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        let mut running = lock();
+        let status = self.0.wait();
+
+        running.retain(|pid| *pid != self.0.id());
+
+        status
+    }
+
+    /// Returns the exit status if the child has exited, like
+    /// [`Child::try_wait`].
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        let mut running = lock();
+        let status = self.0.try_wait();
+
+        if let Ok(Some(_)) = status {
+            running.retain(|pid| *pid != self.0.id());
+        }
+
+        status
+    }
+
+    /// Kills and reaps every guarded child that is still running, e.g.
+    /// before exiting on a signal, when their guards are not dropped.
+    pub fn kill_all() {
+        let mut running = lock();
+
+        for pid in running.drain(..) {
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGKILL);
+                libc::waitpid(pid as libc::pid_t, std::ptr::null_mut(), 0);
+            }
+        }
+    }
+}
+
+impl Deref for ChildGuard {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        &self.0
+    }
+}
+
+impl DerefMut for ChildGuard {
+    fn deref_mut(&mut self) -> &mut Child {
+        &mut self.0
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let mut running = lock();
+
+        // Gone from the list once reaped, by try_wait for a child that has
+        // exited by itself, or by kill_all.
+        if running.contains(&self.0.id()) {
+            if let Ok(None) = self.0.try_wait() {
+                let _ = self.0.kill();
+                let _ = self.0.wait();
+            }
+
+            running.retain(|pid| *pid != self.0.id());
+        }
+    }
+}
+
+/// A panic elsewhere does not make the pids any less valid.
+fn lock() -> std::sync::MutexGuard<'static, Vec<u32>> {
+    RUNNING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
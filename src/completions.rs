@@ -0,0 +1,85 @@
+use std::io::{self, Write};
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+use multiplayerctl::{Error, Result};
+
+use crate::Opt;
+
+/// Prints the completion script for `shell`. Where the shell allows it,
+/// `--player` completes the names printed by `multiplayerctl list` at the time
+/// of completion instead of file names.
+pub fn generate(shell: Shell) -> Result<()> {
+    let mut script = Vec::new();
+
+    clap_complete::generate(shell, &mut Opt::command(), "multiplayerctl", &mut script);
+
+    let script = String::from_utf8(script)
+        .map_err(|why| Error::Other(format!("Invalid completion script: {}", why)))?;
+
+    let script = match shell {
+        Shell::Bash => bash(script),
+        Shell::Zsh => zsh(&script),
+        Shell::Fish => fish(&script),
+        _ => script,
+    };
+
+    io::stdout().write_all(script.as_bytes())?;
+
+    Ok(())
+}
+
+/// Wraps the generated function, answering for `--player` itself.
+fn bash(script: String) -> String {
+    script
+        + r#"
+_multiplayerctl_players() {
+    case "${COMP_WORDS[COMP_CWORD-1]}" in
+        -p|--player)
+            COMPREPLY=($(compgen -W "$(multiplayerctl list 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}"))
+            return 0
+            ;;
+    esac
+
+    _multiplayerctl "$@"
+}
+
+complete -F _multiplayerctl_players -o bashdefault -o default multiplayerctl
+"#
+}
+
+/// Gives every `--player` spec an action listing the players.
+fn zsh(script: &str) -> String {
+    let helper = r#"
+(( $+functions[_multiplayerctl_players] )) ||
+_multiplayerctl_players() {
+    local players
+    players=(${(f)"$(multiplayerctl list 2>/dev/null)"})
+    _describe 'player' players
+}
+"#;
+
+    let mut output = String::new();
+
+    for (i, line) in script.lines().enumerate() {
+        output.push_str(&line.replace("]:PLAYER:_default'", "]:PLAYER:_multiplayerctl_players'"));
+        output.push('\n');
+
+        // Right after `#compdef`, so it is defined before it is used.
+        if i == 0 {
+            output.push_str(helper);
+        }
+    }
+
+    output
+}
+
+fn fish(script: &str) -> String {
+    script
+        .lines()
+        .map(|line| match line.contains(" -l player ") {
+            true => format!("{} -f -a \"(multiplayerctl list 2>/dev/null)\"\n", line),
+            false => format!("{}\n", line),
+        })
+        .collect()
+}
@@ -0,0 +1,1088 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use glob::Pattern;
+
+use crate::error::{Error, Result};
+use crate::player::PlaybackStatus;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Settings read from `$XDG_CONFIG_HOME/multiplayerctl/config.toml`.
+///
+/// Every key is optional; a missing file is the same as an empty one.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The backend to use when `--backend` is not given (`mpris` or `playerctl`).
+    pub backend: Option<String>,
+    /// Players that are never listed or selected. Entries may be glob
+    /// patterns such as `firefox.*`.
+    pub ignore: Vec<String>,
+    /// Players preferred when a new current player has to be picked, highest
+    /// first. Written either as a list or as a string like `spotify > mpv > firefox.*`.
+    #[serde(deserialize_with = "deserialize_priority")]
+    pub priority: Vec<String>,
+    /// Shares one current player between all sessions of the user, instead
+    /// of keeping one per D-Bus session.
+    pub shared_state: bool,
+    /// Treats all instances of an application, such as the windows of a
+    /// browser (`firefox.instance_1_23`), as a single player.
+    pub group_instances: bool,
+    /// Friendly names for players, e.g. `"chromium.instance*" = "Browser"`.
+    /// Keys may be glob patterns; an exact match wins over a pattern.
+    pub aliases: BTreeMap<String, String>,
+    /// Format strings used when a command is run without `--format`.
+    pub formats: Formats,
+    pub icons: IconsConfig,
+    pub art: ArtConfig,
+    pub switch: SwitchConfig,
+    pub volume: VolumeConfig,
+    pub daemon: DaemonConfig,
+    pub waybar: WaybarConfig,
+    pub polybar: PolybarConfig,
+    pub i3blocks: I3blocksConfig,
+    pub notify: NotifyConfig,
+    pub hooks: HooksConfig,
+    pub history: HistoryConfig,
+    pub scrobble: ScrobbleConfig,
+    /// Has the daemon publish the current player to an MQTT broker and take
+    /// commands from it.
+    pub mqtt: Option<MqttConfig>,
+    pub serve: ServeConfig,
+    pub stream: StreamConfig,
+    pub lock: LockConfig,
+    pub suspend: SuspendConfig,
+    pub headphones: HeadphonesConfig,
+    pub bookmarks: BookmarksConfig,
+    pub kdeconnect: KdeConnectConfig,
+    /// Lists an MPD server as a player of its own.
+    pub mpd: Option<MpdConfig>,
+    /// Lists the groups of a Snapcast server as players.
+    pub snapcast: Option<SnapcastConfig>,
+    pub remote: RemoteConfig,
+    pub playerctl: PlayerctlConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Formats {
+    pub volume: Option<String>,
+    pub position: Option<String>,
+    pub status: Option<String>,
+    pub metadata: Option<String>,
+    pub now_playing: Option<String>,
+}
+
+/// Icons shown by the `{status_icon}` and `{player_icon}` fields.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconsConfig {
+    /// Icons for `Playing`, `Paused` and `Stopped`, e.g. `Playing = ""`.
+    /// Statuses left out keep ▶, ⏸ and ⏹.
+    pub status: BTreeMap<String, String>,
+    /// Icons for players, e.g. `spotify = ""`. Keys may be glob patterns,
+    /// as with `aliases`.
+    pub players: BTreeMap<String, String>,
+}
+
+/// Where cover art comes from.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArtConfig {
+    /// Looks up the album on MusicBrainz and downloads its cover from the
+    /// Cover Art Archive for tracks without `mpris:artUrl`, as browsers often
+    /// leave it out.
+    pub musicbrainz: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwitchConfig {
+    /// Whether cycling past the last player starts over at the first one.
+    pub wrap: bool,
+    /// A command such as `fzf` or `rofi -dmenu` used by `switch --interactive`.
+    /// It gets the players on stdin and prints the chosen one. Without it, a
+    /// built-in prompt is used.
+    pub picker: Option<String>,
+    /// Leaves players reporting `CanControl = false` out when cycling with
+    /// `--next` or `--back`.
+    pub skip_uncontrollable: bool,
+    /// Leaves stopped players out when cycling with `--next` or `--back`.
+    pub skip_stopped: bool,
+    /// Leaves players on other devices, such as phones connected through KDE
+    /// Connect, out when cycling or switching to a playing player.
+    pub local_only: bool,
+}
+
+impl Default for SwitchConfig {
+    fn default() -> Self {
+        Self {
+            wrap: true,
+            picker: None,
+            skip_uncontrollable: false,
+            skip_stopped: false,
+            local_only: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VolumeConfig {
+    /// The highest volume `volume` will set, e.g. `1.5` to allow boosting.
+    pub max: f64,
+}
+
+impl Default for VolumeConfig {
+    fn default() -> Self {
+        Self { max: 1.0 }
+    }
+}
+
+impl VolumeConfig {
+    /// The highest volume to set, or the default if `max` is not a volume.
+    pub fn limit(&self) -> f64 {
+        match self.max.is_finite() && self.max >= 0.0 {
+            true => self.max,
+            false => VolumeConfig::default().max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Keeps the current player as it is instead of switching to whichever
+    /// player most recently started playing.
+    pub sticky: bool,
+    /// Pauses all other players whenever the current player starts playing.
+    pub exclusive: bool,
+    /// Registers an `org.mpris.MediaPlayer2.multiplayerctl` player that
+    /// forwards everything to the current player, for desktop media controls.
+    pub mpris_proxy: bool,
+    /// Grabs the media keys of input devices, so that they control the
+    /// current player rather than whichever player the desktop picks.
+    pub media_keys: bool,
+    /// Keeps the system, or also the screen, from going idle while the
+    /// current player is playing.
+    pub inhibit_idle: IdleInhibit,
+    /// An address such as `127.0.0.1:9184` to serve Prometheus metrics on,
+    /// at `/metrics`.
+    pub metrics: Option<String>,
+}
+
+/// What the daemon keeps awake while the current player is playing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleInhibit {
+    #[default]
+    None,
+    /// Keeps the system from suspending or otherwise acting on being idle,
+    /// through logind.
+    System,
+    /// Also keeps the screen from blanking or locking, through the screen
+    /// saver.
+    Screen,
+}
+
+/// What the daemon does while the screen is locked.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockConfig {
+    /// Which players to pause on locking.
+    pub pause: PauseScope,
+    /// Plays the paused players again on unlocking.
+    pub resume: bool,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            pause: PauseScope::None,
+            resume: true,
+        }
+    }
+}
+
+/// What the daemon does when the system suspends.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SuspendConfig {
+    /// Which players to pause before suspending.
+    pub pause: PauseScope,
+    /// Plays the paused players again after waking up.
+    pub resume: bool,
+}
+
+impl Default for SuspendConfig {
+    fn default() -> Self {
+        Self {
+            pause: PauseScope::None,
+            resume: false,
+        }
+    }
+}
+
+/// What the daemon does when audio moves off headphones.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeadphonesConfig {
+    /// Which players to pause when the default output stops being
+    /// headphones.
+    pub pause: PauseScope,
+    /// Plays the paused players again once headphones are back.
+    pub resume: bool,
+    /// Glob patterns for the sink or port names that count as headphones.
+    pub outputs: Vec<String>,
+}
+
+impl HeadphonesConfig {
+    /// Whether a sink, playing through `port`, counts as headphones.
+    pub fn matches(&self, sink: &str, port: Option<&str>) -> bool {
+        self.outputs.iter().any(|pattern| {
+            matches_pattern(pattern, sink) || port.is_some_and(|p| matches_pattern(pattern, p))
+        })
+    }
+}
+
+impl Default for HeadphonesConfig {
+    fn default() -> Self {
+        Self {
+            pause: PauseScope::None,
+            resume: false,
+            outputs: vec![
+                String::from("*headphone*"),
+                String::from("*headset*"),
+                String::from("bluez*"),
+            ],
+        }
+    }
+}
+
+/// Where the daemon remembers how far tracks were played, for podcasts and
+/// audiobooks.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BookmarksConfig {
+    /// Players whose positions are remembered. Entries may be glob patterns;
+    /// empty means none.
+    pub players: Vec<String>,
+    /// Seeks to the remembered position whenever one of these players starts
+    /// a track over, rather than leaving it to `multiplayerctl resume`.
+    pub auto_resume: bool,
+}
+
+impl BookmarksConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.players.is_empty()
+    }
+
+    /// Whether positions are remembered for `player`.
+    pub fn includes(&self, player: &str) -> bool {
+        self.players.iter().any(|p| matches_pattern(p, player))
+    }
+}
+
+/// Players on phones and other devices, proxied by KDE Connect. Entries are
+/// glob patterns matched against the name they are shown with, which
+/// includes the device's name.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KdeConnectConfig {
+    /// Players that are never listed or selected, like `ignore`.
+    pub exclude: Vec<String>,
+    /// Players preferred over all others when a new current player has to
+    /// be picked.
+    pub pin: Vec<String>,
+}
+
+impl KdeConnectConfig {
+    pub fn excludes(&self, name: &str) -> bool {
+        self.exclude.iter().any(|p| matches_pattern(p, name))
+    }
+
+    pub fn pins(&self, name: &str) -> bool {
+        self.pin.iter().any(|p| matches_pattern(p, name))
+    }
+}
+
+/// Which players the daemon pauses when it pauses on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PauseScope {
+    None,
+    /// The current player, if it is playing.
+    Current,
+    /// Every player that is playing.
+    All,
+}
+
+/// Shell commands the daemon runs on events, with the details in `MPCTL_*`
+/// environment variables.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Run when a player starts a new track.
+    pub on_track_change: Option<String>,
+    /// Run when the current player changes.
+    pub on_switch: Option<String>,
+    /// Run when a player starts playing.
+    pub on_play: Option<String>,
+    /// Run when a player is paused.
+    pub on_pause: Option<String>,
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        self.on_track_change.is_none()
+            && self.on_switch.is_none()
+            && self.on_play.is_none()
+            && self.on_pause.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Has the daemon log every track played to `history.sqlite` in the
+    /// state directory, for `multiplayerctl history`.
+    pub enabled: bool,
+}
+
+/// Where the daemon submits what is played, and for which players.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScrobbleConfig {
+    /// Players whose tracks are submitted. Entries may be glob patterns;
+    /// empty means every player.
+    pub players: Vec<String>,
+    /// Players whose tracks are never submitted, such as browsers playing
+    /// videos. Entries may be glob patterns.
+    pub ignore: Vec<String>,
+    /// Scrobbles to Last.fm once set up with `multiplayerctl scrobble auth`.
+    pub lastfm: Option<LastfmConfig>,
+    pub listenbrainz: Option<ListenBrainzConfig>,
+}
+
+impl ScrobbleConfig {
+    /// Whether any service to submit to is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.lastfm.is_some() || self.listenbrainz.is_some()
+    }
+
+    /// Whether tracks played by `player` are submitted.
+    pub fn includes(&self, player: &str) -> bool {
+        (self.players.is_empty() || self.players.iter().any(|p| matches_pattern(p, player)))
+            && !self.ignore.iter().any(|p| matches_pattern(p, player))
+    }
+}
+
+/// The Last.fm API account to scrobble through, from
+/// <https://www.last.fm/api/account/create>.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LastfmConfig {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+/// Submits listens to ListenBrainz, or another server with the same API.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenBrainzConfig {
+    /// The user token from <https://listenbrainz.org/settings/>.
+    pub token: String,
+    pub url: String,
+}
+
+impl Default for ListenBrainzConfig {
+    fn default() -> Self {
+        Self {
+            token: String::new(),
+            url: String::from("https://api.listenbrainz.org"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub client_id: String,
+    /// The prefix of every topic, e.g. `multiplayerctl/current/status`.
+    pub topic: String,
+    /// Announces the player to Home Assistant through MQTT discovery.
+    pub discovery: bool,
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: 1883,
+            username: None,
+            password: None,
+            client_id: String::from("multiplayerctl"),
+            topic: String::from("multiplayerctl"),
+            discovery: true,
+            discovery_prefix: String::from("homeassistant"),
+        }
+    }
+}
+
+/// An MPD server, reached over TCP, or over its Unix socket if `host` is a
+/// path.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MpdConfig {
+    pub host: String,
+    pub port: u16,
+    pub password: Option<String>,
+    /// The name the server is listed under.
+    pub name: String,
+    /// MPD's `music_directory`, so that tracks get a `file://` URL for
+    /// cover art and lyrics next to them.
+    pub music_directory: Option<PathBuf>,
+}
+
+impl Default for MpdConfig {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: 6600,
+            password: None,
+            name: String::from("mpd"),
+            music_directory: None,
+        }
+    }
+}
+
+/// Another machine whose players commands control, through SSH.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// Runs every command there, as with `--host`, e.g. `me@htpc`.
+    pub host: Option<String>,
+    /// The multiplayerctl to run there, for when it is not on the `PATH` of
+    /// non-interactive SSH sessions.
+    pub command: String,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            command: String::from("multiplayerctl"),
+        }
+    }
+}
+
+/// How playerctl is run, by the `playerctl` backend and for `--format`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerctlConfig {
+    /// How many seconds a playerctl command may take before it is killed,
+    /// as some players hang while shutting down. `0` waits forever.
+    pub timeout: f64,
+    /// When the current player does not answer in time, makes the next
+    /// player current and runs the command on that one instead.
+    pub skip_stalled: bool,
+}
+
+impl Default for PlayerctlConfig {
+    fn default() -> Self {
+        Self {
+            timeout: 5.0,
+            skip_stalled: false,
+        }
+    }
+}
+
+impl PlayerctlConfig {
+    /// The timeout as a [`Duration`], or `None` to wait forever.
+    pub fn limit(&self) -> Option<Duration> {
+        Duration::try_from_secs_f64(self.timeout)
+            .ok()
+            .filter(|limit| !limit.is_zero())
+    }
+}
+
+/// A Snapcast server, reached over its JSON-RPC control port.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapcastConfig {
+    pub host: String,
+    pub port: u16,
+    /// What the names of the groups are prefixed with, e.g.
+    /// `snapcast.kitchen`.
+    pub name: String,
+}
+
+impl Default for SnapcastConfig {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: 1705,
+            name: String::from("snapcast"),
+        }
+    }
+}
+
+/// Settings for `multiplayerctl serve`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServeConfig {
+    /// The address to listen on when `--bind` is not given.
+    pub bind: String,
+    /// A bearer token every request has to carry. Without it, anyone who can
+    /// reach the address can control the players.
+    pub token: Option<String>,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            bind: String::from("127.0.0.1:8080"),
+            token: None,
+        }
+    }
+}
+
+/// Files the daemon keeps up to date with what is playing, for streaming
+/// software such as OBS to show.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreamConfig {
+    /// Where to write the now-playing text.
+    pub text: Option<PathBuf>,
+    /// The now-playing text, using the same `{field}` placeholders as
+    /// `list --format`.
+    pub format: String,
+    /// Where to copy the cover art.
+    pub art: Option<PathBuf>,
+    /// Keeps showing the track while it is paused, instead of clearing the
+    /// files until it plays again.
+    pub paused: bool,
+}
+
+impl StreamConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.text.is_some() || self.art.is_some()
+    }
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            text: None,
+            format: String::from("{artist} - {title}"),
+            art: None,
+            paused: false,
+        }
+    }
+}
+
+/// Templates for `multiplayerctl waybar`, using the same `{field}`
+/// placeholders as `list --format`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaybarConfig {
+    pub text: String,
+    pub tooltip: String,
+}
+
+impl Default for WaybarConfig {
+    fn default() -> Self {
+        Self {
+            text: String::from("{artist} - {title}"),
+            tooltip: String::from("{player}: {status}\n{album}"),
+        }
+    }
+}
+
+/// Settings for `multiplayerctl polybar`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolybarConfig {
+    /// The text shown, using the same `{field}` placeholders as `list
+    /// --format`, and polybar's own formatting tags such as `%{F#888}`.
+    pub text: String,
+    pub actions: ClickActions,
+}
+
+impl Default for PolybarConfig {
+    fn default() -> Self {
+        Self {
+            text: String::from("{status_icon} {artist} - {title}"),
+            actions: ClickActions::default(),
+        }
+    }
+}
+
+/// What clicking or scrolling on a bar module runs, as the arguments to
+/// `multiplayerctl`, e.g. `"volume +5%"`. Left empty, a button does nothing.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClickActions {
+    pub left: String,
+    pub middle: String,
+    pub right: String,
+    pub scroll_up: String,
+    pub scroll_down: String,
+}
+
+impl Default for ClickActions {
+    fn default() -> Self {
+        Self {
+            left: String::from("toggle"),
+            middle: String::from("switch"),
+            right: String::from("next"),
+            scroll_up: String::from("previous"),
+            scroll_down: String::from("next"),
+        }
+    }
+}
+
+/// Settings for `multiplayerctl i3blocks`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct I3blocksConfig {
+    /// The text shown, using the same `{field}` placeholders as `list
+    /// --format`.
+    pub text: String,
+    pub actions: BlockActions,
+}
+
+impl Default for I3blocksConfig {
+    fn default() -> Self {
+        Self {
+            text: String::from("{status_icon} {artist} - {title}"),
+            actions: BlockActions::default(),
+        }
+    }
+}
+
+/// Like [`ClickActions`], for i3blocks and i3status-rust blocks, where
+/// scrolling changes the volume rather than the track.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlockActions {
+    pub left: String,
+    pub middle: String,
+    pub right: String,
+    pub scroll_up: String,
+    pub scroll_down: String,
+}
+
+impl BlockActions {
+    /// The action for a button as i3blocks numbers them, from 1 for the left
+    /// one to 5 for scrolling down.
+    pub fn for_button(&self, button: u8) -> Option<&str> {
+        let action = match button {
+            1 => &self.left,
+            2 => &self.middle,
+            3 => &self.right,
+            4 => &self.scroll_up,
+            5 => &self.scroll_down,
+            _ => return None,
+        };
+
+        Some(action.trim()).filter(|a| !a.is_empty())
+    }
+}
+
+impl Default for BlockActions {
+    fn default() -> Self {
+        Self {
+            left: String::from("toggle"),
+            middle: String::from("switch"),
+            right: String::from("next"),
+            scroll_up: String::from("volume +5%"),
+            scroll_down: String::from("volume -5%"),
+        }
+    }
+}
+
+/// Settings for `multiplayerctl notify`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// How long notifications stay up in milliseconds; `-1` leaves it to the
+    /// notification server and `0` keeps them until dismissed.
+    pub timeout: i32,
+    pub urgency: Urgency,
+    /// The notification title, using the same `{field}` placeholders as
+    /// `list --format`.
+    pub summary: String,
+    pub body: String,
+    /// Announces each track only once per player, so that switching back to
+    /// a player does not repeat the notification for the track it was playing.
+    pub suppress_duplicates: bool,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            timeout: -1,
+            urgency: Urgency::Low,
+            summary: String::from("{title}"),
+            body: String::from("{artist}\n{album}"),
+            suppress_duplicates: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+/// The prefix of the environment variables that override settings, such as
+/// `MULTIPLAYERCTL_IGNORE` for `ignore` and `MULTIPLAYERCTL_DAEMON__STICKY`
+/// for `daemon.sticky`.
+pub const ENV_PREFIX: &str = "MULTIPLAYERCTL_";
+
+/// Variables with [`ENV_PREFIX`] that name a file or directory rather than a
+/// setting.
+const ENV_PATHS: [&str; 4] = ["CONFIG", "STATE_DIR", "CACHE_DIR", "SOCKET"];
+
+impl Config {
+    /// Loads the config from `path`, or from `$MULTIPLAYERCTL_CONFIG` or the
+    /// default location if `None`, and applies the overrides from the
+    /// environment. A missing file at the default location yields the
+    /// default config.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        Config::load_file(path)?.with_overrides(&env_overrides())
+    }
+
+    fn load_file(path: Option<&Path>) -> Result<Config> {
+        let (path, required) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match default_config_path() {
+                Some(p) => (p, env::var_os("MULTIPLAYERCTL_CONFIG").is_some()),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(v) => v,
+            Err(why) if why.kind() == ErrorKind::NotFound && !required => {
+                return Ok(Config::default())
+            }
+            Err(why) => {
+                return Err(Error::Config(format!(
+                    "Failed to read config file {}: {}",
+                    path.display(),
+                    why
+                )))
+            }
+        };
+
+        Config::parse(&contents).map_err(|why| {
+            Error::Config(format!("Invalid config file {}: {}", path.display(), why))
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<Config> {
+        toml::from_str(contents).map_err(|why| Error::Config(why.to_string()))
+    }
+
+    /// Overrides settings with `(variable, value)` pairs as returned by
+    /// [`env_overrides`]. Values are read as TOML if they can be, so that
+    /// `true` is a boolean where one is expected, and lists may also be
+    /// given separated by commas.
+    pub fn with_overrides(self, overrides: &[(String, String)]) -> Result<Config> {
+        if overrides.is_empty() {
+            return Ok(self);
+        }
+
+        let mut config = toml::Value::try_from(&self)
+            .map_err(|why| Error::Other(format!("Failed to serialize the config: {}", why)))?;
+
+        for (variable, value) in overrides {
+            let key = variable[ENV_PREFIX.len()..].to_lowercase();
+            let path: Vec<&str> = key.split("__").collect();
+
+            let mut error = None;
+
+            for candidate in override_values(value) {
+                // The error for the plain string says best what was expected.
+                let is_string = candidate.is_str();
+                let mut attempt = config.clone();
+
+                if !set_value(&mut attempt, &path, candidate) {
+                    return Err(Error::Config(format!(
+                        "Invalid {}: {} is not a section",
+                        variable,
+                        path[..path.len() - 1].join(".")
+                    )));
+                }
+
+                match attempt.clone().try_into::<Config>() {
+                    Ok(_) => {
+                        config = attempt;
+                        error = None;
+                        break;
+                    }
+                    Err(why) if is_string || error.is_none() => error = Some(why),
+                    Err(_) => (),
+                }
+            }
+
+            if let Some(why) = error {
+                return Err(Error::Config(format!("Invalid {}: {}", variable, why)));
+            }
+        }
+
+        config
+            .try_into()
+            .map_err(|why| Error::Config(format!("Invalid environment override: {}", why)))
+    }
+
+    /// Describes the settings that parse but cannot work as intended, such
+    /// as unknown backends or malformed glob patterns, which would otherwise
+    /// be taken literally.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Some(backend) = self.backend.as_deref() {
+            if !matches!(backend, "mpris" | "playerctl") {
+                problems.push(format!(
+                    "backend: unknown backend \"{}\", expected \"mpris\" or \"playerctl\"",
+                    backend
+                ));
+            }
+        }
+
+        if self.playerctl.timeout < 0.0 || self.playerctl.timeout.is_nan() {
+            problems.push(format!(
+                "playerctl.timeout: {} is not a number of seconds, so playerctl is waited for forever",
+                self.playerctl.timeout
+            ));
+        }
+
+        if !self.volume.max.is_finite() || self.volume.max < 0.0 {
+            problems.push(format!(
+                "volume.max: {} is not a volume, so 1.0 is used instead",
+                self.volume.max
+            ));
+        }
+
+        let patterns = [
+            ("ignore", &self.ignore),
+            ("priority", &self.priority),
+            ("kdeconnect.exclude", &self.kdeconnect.exclude),
+            ("kdeconnect.pin", &self.kdeconnect.pin),
+            ("bookmarks.players", &self.bookmarks.players),
+            ("scrobble.players", &self.scrobble.players),
+            ("scrobble.ignore", &self.scrobble.ignore),
+        ];
+
+        for status in self.icons.status.keys() {
+            if !matches!(status.as_str(), "Playing" | "Paused" | "Stopped") {
+                problems.push(format!(
+                    "icons.status: unknown status \"{}\", expected \"Playing\", \"Paused\" or \"Stopped\"",
+                    status
+                ));
+            }
+        }
+
+        let aliases: Vec<String> = self.aliases.keys().cloned().collect();
+        let icons: Vec<String> = self.icons.players.keys().cloned().collect();
+
+        for (key, patterns) in patterns
+            .into_iter()
+            .chain([("aliases", &aliases), ("icons.players", &icons)])
+        {
+            for pattern in patterns {
+                if let Err(why) = Pattern::new(pattern) {
+                    problems.push(format!(
+                        "{}: \"{}\" is not a valid glob pattern ({}), so it only matches itself",
+                        key, pattern, why
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
+    pub fn is_ignored(&self, player: &str) -> bool {
+        self.ignore.iter().any(|p| matches_pattern(p, player))
+    }
+
+    /// Returns the alias configured for `player`, if any.
+    pub fn alias(&self, player: &str) -> Option<&str> {
+        lookup_player(&self.aliases, player)
+    }
+
+    /// The icon configured for `player`, or nothing.
+    pub fn player_icon(&self, player: &str) -> &str {
+        lookup_player(&self.icons.players, player).unwrap_or_default()
+    }
+
+    /// The icon configured for `status`, or else ▶, ⏸ or ⏹. An unknown
+    /// status is shown as stopped.
+    pub fn status_icon(&self, status: Option<PlaybackStatus>) -> &str {
+        let status = status.unwrap_or(PlaybackStatus::Stopped);
+
+        match self.icons.status.get(&status.to_string()) {
+            Some(icon) => icon,
+            None => match status {
+                PlaybackStatus::Playing => "▶",
+                PlaybackStatus::Paused => "⏸",
+                PlaybackStatus::Stopped => "⏹",
+            },
+        }
+    }
+}
+
+fn deserialize_priority<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Priority {
+        List(Vec<String>),
+        Chain(String),
+    }
+
+    Ok(match Priority::deserialize(deserializer)? {
+        Priority::List(v) => v,
+        Priority::Chain(s) => s
+            .split('>')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(String::from)
+            .collect(),
+    })
+}
+
+/// The variables with [`ENV_PREFIX`] that override settings, as
+/// `(variable, value)` pairs.
+pub fn env_overrides() -> Vec<(String, String)> {
+    let mut overrides: Vec<(String, String)> = env::vars_os()
+        .filter_map(|(variable, value)| {
+            Some((variable.into_string().ok()?, value.into_string().ok()?))
+        })
+        .filter(|(variable, _)| {
+            variable
+                .strip_prefix(ENV_PREFIX)
+                .is_some_and(|key| !key.is_empty() && !ENV_PATHS.contains(&key))
+        })
+        .collect();
+
+    // Applied in a fixed order, so that errors do not vary between runs.
+    overrides.sort();
+
+    overrides
+}
+
+/// Whether the environment changes any setting or file, in which case
+/// commands cannot be left to a daemon started without it.
+pub fn has_env_overrides() -> bool {
+    ENV_PATHS
+        .iter()
+        .filter(|name| **name != "SOCKET")
+        .any(|name| env::var_os(format!("{}{}", ENV_PREFIX, name)).is_some())
+        || !env_overrides().is_empty()
+}
+
+/// The values an override may stand for, most specific first: a TOML value,
+/// a string, and a comma-separated list.
+fn override_values(value: &str) -> Vec<toml::Value> {
+    let mut values: Vec<toml::Value> = format!("v = {}", value)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .into_iter()
+        .collect();
+
+    values.push(toml::Value::String(value.to_string()));
+
+    values.push(toml::Value::Array(
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| toml::Value::String(v.to_string()))
+            .collect(),
+    ));
+
+    values
+}
+
+/// Sets the value at `path` in `config`, creating the sections on the way.
+/// Fails if one of them is not a section.
+fn set_value(config: &mut toml::Value, path: &[&str], value: toml::Value) -> bool {
+    let (name, sections) = match path.split_last() {
+        Some(v) => v,
+        None => return false,
+    };
+
+    let mut table = config.as_table_mut();
+
+    for section in sections {
+        table = table.and_then(|t| {
+            t.entry(*section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+        });
+    }
+
+    match table {
+        Some(table) => {
+            table.insert(name.to_string(), value);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns `$MULTIPLAYERCTL_CONFIG` if set, or else
+/// `$XDG_CONFIG_HOME/multiplayerctl/config.toml` (or the `~/.config` equivalent).
+pub fn default_config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("MULTIPLAYERCTL_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(v) => PathBuf::from(v),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_base.join("multiplayerctl").join("config.toml"))
+}
+
+/// The value `map` has for `player`, by its name or else the first glob
+/// pattern matching it.
+fn lookup_player<'a>(map: &'a BTreeMap<String, String>, player: &str) -> Option<&'a str> {
+    map.get(player)
+        .or_else(|| {
+            map.iter()
+                .find(|(pattern, _)| matches_pattern(pattern, player))
+                .map(|(_, value)| value)
+        })
+        .map(String::as_str)
+}
+
+/// Matches a player name against a glob pattern, falling back to a plain
+/// comparison if the pattern is not valid glob syntax.
+pub fn matches_pattern(pattern: &str, player: &str) -> bool {
+    match Pattern::new(pattern) {
+        Ok(p) => p.matches(player),
+        Err(_) => pattern == player,
+    }
+}
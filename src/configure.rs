@@ -0,0 +1,200 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use multiplayerctl::config::default_config_path;
+use multiplayerctl::{Config, Error, Result};
+use serde_json::json;
+
+use crate::{apply_overrides, json_line, ConfigCommand, Opt};
+
+/// Written by `config edit` when there is no config file yet. Everything is
+/// commented out, so that it behaves like no file at all.
+const TEMPLATE: &str = r#"# multiplayerctl configuration. Every key is optional, and the README lists
+# them all. Check this file with `multiplayerctl config validate`.
+
+# Backend used when --backend is not given: "mpris" or "playerctl".
+# backend = "mpris"
+
+# Players that are never listed or selected. Glob patterns are allowed.
+# ignore = ["playerctld", "firefox.*"]
+
+# Players preferred when a new current player has to be picked, highest first.
+# priority = "spotify > mpv > firefox.*"
+
+# Friendly names shown by `list`, `player` and the {player_name} field.
+# [aliases]
+# "chromium.instance*" = "Browser"
+
+# Icons for the {status_icon} and {player_icon} fields.
+# [icons.status]
+# Playing = "▶"
+# [icons.players]
+# spotify = "♫"
+
+# Formats used when --format is not passed on the command line.
+# [formats]
+# now_playing = "{status_icon} {artist} - {title} ({position}/{length})"
+
+# [switch]
+# wrap = true
+# skip_stopped = false
+
+# [daemon]
+# sticky = false
+# exclusive = false
+"#;
+
+/// Keys whose values are left out of `config show`, so that it can be
+/// shared in bug reports.
+const SECRETS: [&str; 3] = ["api_secret", "password", "token"];
+
+pub fn run(opt: &Opt, command: &ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Path => path(opt),
+        ConfigCommand::Show => show(opt),
+        ConfigCommand::Validate => validate(opt),
+        ConfigCommand::Edit => edit(opt),
+    }
+}
+
+/// The file `--config` names, or else the default one, which need not exist.
+fn config_path(opt: &Opt) -> Result<PathBuf> {
+    opt.config
+        .clone()
+        .or_else(default_config_path)
+        .ok_or_else(|| {
+            Error::Config(String::from(
+                "There is no config file, as neither XDG_CONFIG_HOME nor HOME is set.",
+            ))
+        })
+}
+
+fn path(opt: &Opt) -> Result<()> {
+    let path = config_path(opt)?;
+
+    match opt.json {
+        true => print!(
+            "{}",
+            json_line(json!({ "path": path, "exists": path.exists() }))
+        ),
+        false => println!("{}", path.display()),
+    }
+
+    Ok(())
+}
+
+/// Prints the settings commands run with: the defaults, overridden by the
+/// file, the environment and the command line, in that order.
+fn show(opt: &Opt) -> Result<()> {
+    let mut config = Config::load(opt.config.as_deref())?;
+
+    apply_overrides(opt, &mut config);
+
+    if opt.backend.is_some() {
+        config.backend = opt.backend.clone();
+    }
+
+    let mut value = toml::Value::try_from(&config)
+        .map_err(|why| Error::Other(format!("Failed to serialize the config: {}", why)))?;
+
+    redact(&mut value);
+
+    match opt.json {
+        true => print!("{}", json_line(json!(value))),
+        false => print!(
+            "{}",
+            toml::to_string_pretty(&value)
+                .map_err(|why| Error::Other(format!("Failed to serialize the config: {}", why)))?
+        ),
+    }
+
+    Ok(())
+}
+
+fn redact(value: &mut toml::Value) {
+    if let toml::Value::Table(table) = value {
+        for (key, value) in table.iter_mut() {
+            match SECRETS.contains(&key.as_str()) {
+                true => *value = toml::Value::String(String::from("<hidden>")),
+                false => redact(value),
+            }
+        }
+    }
+}
+
+/// Checks that the file parses and that its settings make sense, printing
+/// every problem found.
+fn validate(opt: &Opt) -> Result<()> {
+    let path = config_path(opt)?;
+
+    if !path.exists() && opt.config.is_none() {
+        println!("There is no {}, so the defaults are used.", path.display());
+
+        return Ok(());
+    }
+
+    let problems = Config::load(Some(&path))?.problems();
+
+    for problem in &problems {
+        println!("{}: {}", path.display(), problem);
+    }
+
+    match problems.len() {
+        0 => {
+            println!("{} is valid.", path.display());
+
+            Ok(())
+        }
+        1 => Err(Error::Config(format!("{} has 1 problem.", path.display()))),
+        count => Err(Error::Config(format!(
+            "{} has {} problems.",
+            path.display(),
+            count
+        ))),
+    }
+}
+
+/// Opens the file in `$VISUAL` or `$EDITOR`, creating it from a template if
+/// it does not exist yet, and validates it once the editor exits.
+fn edit(opt: &Opt) -> Result<()> {
+    let path = config_path(opt)?;
+
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|why| {
+                Error::Config(format!("Failed to create {}: {}", parent.display(), why))
+            })?;
+        }
+
+        fs::write(&path, TEMPLATE).map_err(|why| {
+            Error::Config(format!("Failed to create {}: {}", path.display(), why))
+        })?;
+    }
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"));
+
+    // Through the shell, so that editors given with arguments such as
+    // `code --wait` work.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .map_err(|why| Error::Other(format!("Failed to run '{}': {}", editor, why)))?;
+
+    if !status.success() {
+        return Err(Error::Other(format!(
+            "'{}' exited with {}, so the config was not checked.",
+            editor, status
+        )));
+    }
+
+    validate(opt)
+}
@@ -0,0 +1,431 @@
+use std::fs::{remove_file, DirBuilder};
+use std::io::{Error, ErrorKind};
+use std::os::fd::FromRawFd;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use clap::Parser;
+use multiplayerctl::config::{IdleInhibit, PauseScope};
+use multiplayerctl::{ipc, Error as CommandError, PlaybackStatus, PlayerManager};
+
+#[cfg(feature = "serve")]
+use crate::metrics::Metrics;
+use crate::{
+    apply_default_formats, bookmarks, execute, headphones, hooks, inhibit, is_oneshot, lock,
+    needs_player, proxy, reload, schedule, service, sleep, stream, suspend, template, Args, Opt,
+};
+
+/// The first file descriptor systemd passes with socket activation.
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Serves one-shot commands sent by other invocations until killed. Unless
+/// `daemon.sticky` is set, it also makes whichever player most recently
+/// started playing the current one. It also offers the D-Bus service, and with
+/// `daemon.mpris_proxy` it mirrors the current player as an MPRIS player.
+pub fn run(manager: PlayerManager, opt: &Opt) -> Result<(), Error> {
+    let listener = match activated_listener() {
+        Some(listener) => listener,
+        None => bind()?,
+    };
+
+    let manager = Arc::new(manager);
+
+    // Which parts of the daemon run is decided once, so a reload only
+    // changes how those that do behave.
+    let startup = manager.config();
+
+    thread::scope(|scope| {
+        let config = &startup.daemon;
+
+        // Also when sticky, as a reload may turn that off.
+        scope.spawn(|| follow_playing(&manager));
+
+        let reloaded = Arc::clone(&manager);
+
+        scope.spawn(move || {
+            if let Err(why) = reload::run(reloaded, opt) {
+                eprintln!("{}", why);
+            }
+        });
+
+        let served = Arc::clone(&manager);
+
+        scope.spawn(move || {
+            if let Err(why) = service::run(served) {
+                eprintln!("{}", why);
+            }
+        });
+
+        // Also without hooks, as a reload may add some.
+        let hooked = Arc::clone(&manager);
+
+        scope.spawn(move || {
+            if let Err(why) = hooks::run(hooked) {
+                eprintln!("{}", why);
+            }
+        });
+
+        if manager.config().stream.is_enabled() {
+            let streamed = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = stream::run(streamed) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        if manager.config().bookmarks.is_enabled() {
+            let bookmarked = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = bookmarks::run(bookmarked) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        #[cfg(feature = "history")]
+        if manager.config().history.enabled {
+            let recorded = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = crate::listens::record(recorded) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        #[cfg(feature = "scrobble")]
+        if manager.config().scrobble.is_enabled() {
+            let scrobbled = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = crate::scrobble::run(scrobbled) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        #[cfg(feature = "mqtt")]
+        if manager.config().mqtt.is_some() {
+            let published = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = crate::mqtt::run(published) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        #[cfg(feature = "serve")]
+        let metrics = config.metrics.as_ref().map(|bind| {
+            let metrics = Arc::new(Metrics::new());
+            let counted = Arc::clone(&manager);
+            let recorded = Arc::clone(&metrics);
+
+            scope.spawn(move || {
+                if let Err(why) = crate::metrics::run(counted, recorded, bind) {
+                    eprintln!("{}", why);
+                }
+            });
+
+            metrics
+        });
+
+        #[cfg(feature = "media-keys")]
+        if config.media_keys {
+            let controlled = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = crate::media_keys::run(controlled) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        let timed = Arc::clone(&manager);
+
+        scope.spawn(move || {
+            if let Err(why) = sleep::run(timed) {
+                eprintln!("{}", why);
+            }
+        });
+
+        let scheduled = Arc::clone(&manager);
+
+        scope.spawn(move || {
+            if let Err(why) = schedule::run(scheduled) {
+                eprintln!("{}", why);
+            }
+        });
+
+        if config.inhibit_idle != IdleInhibit::None {
+            let awake = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = inhibit::run(awake) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        if manager.config().lock.pause != PauseScope::None {
+            let locked = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = lock::run(locked) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        if manager.config().suspend.pause != PauseScope::None {
+            let suspended = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = suspend::run(suspended) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        if manager.config().headphones.pause != PauseScope::None {
+            let unplugged = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = headphones::run(unplugged) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        if config.mpris_proxy {
+            let mirrored = Arc::clone(&manager);
+
+            scope.spawn(move || {
+                if let Err(why) = proxy::run(mirrored) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+
+            stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+
+            let handled = Arc::clone(&manager);
+            #[cfg(feature = "serve")]
+            let metrics = metrics.clone();
+
+            // Each on its own, so that commands which download something
+            // keep no one else waiting.
+            scope.spawn(move || {
+                let response = match ipc::read_request(&mut stream) {
+                    #[cfg(feature = "serve")]
+                    Ok(args) => match &metrics {
+                        Some(m) => m.time(&args.clone(), || handle(&handled, args)),
+                        None => handle(&handled, args),
+                    },
+                    #[cfg(not(feature = "serve"))]
+                    Ok(args) => handle(&handled, args),
+                    Err(_) => return,
+                };
+
+                let _ = ipc::write_response(&mut stream, &response);
+            });
+        }
+
+        Ok(())
+    })
+}
+
+/// Listens on the daemon's socket, unless another daemon already does.
+fn bind() -> Result<UnixListener, Error> {
+    let path = match ipc::socket_path() {
+        Some(p) => p,
+        None => {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "$XDG_RUNTIME_DIR is not set",
+            ))
+        }
+    };
+
+    if UnixStream::connect(&path).is_ok() {
+        return Err(Error::new(
+            ErrorKind::AddrInUse,
+            "Another daemon is already running.",
+        ));
+    }
+
+    // Left behind by a daemon that did not shut down cleanly.
+    let _ = remove_file(&path);
+
+    if let Some(dir) = path.parent() {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+
+    UnixListener::bind(&path)
+}
+
+/// The socket systemd listens on for the daemon and passed on to it, as set
+/// up by `multiplayerctl service install`.
+fn activated_listener() -> Option<UnixListener> {
+    let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+    let fds: u32 = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+
+    if pid != std::process::id() || fds != 1 {
+        return None;
+    }
+
+    // The first passed fd is always 3, and hooks need not inherit it.
+    unsafe { libc::fcntl(SD_LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC) };
+
+    Some(unsafe { UnixListener::from_raw_fd(SD_LISTEN_FDS_START) })
+}
+
+/// Switches to every player that starts playing, like playerctld does, and
+/// in exclusive mode pauses the others.
+fn follow_playing(manager: &PlayerManager) {
+    let watched = manager.backend().watch_status(|player, status| {
+        if status != PlaybackStatus::Playing {
+            return;
+        }
+
+        let config = &manager.config().daemon;
+
+        if !config.sticky && manager.current_name().ok().as_deref() != Some(player) {
+            // Fails for ignored players, which should not become current anyway.
+            let _ = manager.switch_to(player);
+        }
+
+        if config.exclusive {
+            pause_others(manager);
+        }
+    });
+
+    if let Err(why) = watched {
+        eprintln!("{}", why);
+    }
+}
+
+/// Pauses every playing player other than the current one, if the current
+/// player is playing.
+pub fn pause_others(manager: &PlayerManager) {
+    let current = match manager.current() {
+        Ok(p) => p,
+        Err(_) => return,
+    };
+
+    if current.status() != Ok(PlaybackStatus::Playing) {
+        return;
+    }
+
+    for name in manager.players().unwrap_or_default() {
+        let player = manager.player(&name);
+
+        if name != current.name() && player.status() == Ok(PlaybackStatus::Playing) {
+            let _ = player.pause();
+        }
+    }
+}
+
+/// Pauses the players in `scope` that are playing, returning their names so
+/// that they can be [resumed](resume) later.
+pub fn pause_playing(manager: &PlayerManager, scope: PauseScope) -> Vec<String> {
+    let players = match scope {
+        PauseScope::None => Vec::new(),
+        PauseScope::Current => manager.current_name().into_iter().collect(),
+        PauseScope::All => manager.players().unwrap_or_default(),
+    };
+
+    players
+        .into_iter()
+        .filter(|name| {
+            let player = manager.player(name);
+
+            player.status() == Ok(PlaybackStatus::Playing) && player.pause().is_ok()
+        })
+        .collect()
+}
+
+/// Plays the players paused by [`pause_playing`] again, as far as they are
+/// still around.
+pub fn resume(manager: &PlayerManager, players: &[String]) {
+    for name in players {
+        let _ = manager.player(name).play();
+    }
+}
+
+/// Runs a one-shot command through the daemon if `forward` is set and one
+/// is running, or with `manager` otherwise, like the CLI would.
+pub fn command(
+    manager: &PlayerManager,
+    forward: bool,
+    args: Vec<String>,
+) -> Result<String, CommandError> {
+    if forward {
+        if let Ok(response) = ipc::send(&args) {
+            return response;
+        }
+    }
+
+    handle(manager, args)
+}
+
+/// Runs a one-shot command given as the arguments to `multiplayerctl`.
+pub fn handle(manager: &PlayerManager, args: Vec<String>) -> Result<String, CommandError> {
+    let argv = std::iter::once(String::from("multiplayerctl")).chain(args);
+
+    let mut opt = match Opt::try_parse_from(argv) {
+        Ok(o) => o.resolve_player(),
+        Err(why) => return Err(CommandError::InvalidArgument(why.to_string())),
+    };
+
+    if !is_oneshot(&opt.command) {
+        return Err(CommandError::InvalidArgument(String::from(
+            "The daemon cannot run this command.",
+        )));
+    }
+
+    // Only clients that know their output goes to a terminal ask for colors.
+    template::set_color(opt.color == "always");
+
+    let targeted;
+
+    let manager = match &opt.player {
+        Some(p) => {
+            targeted = manager.targeting(p);
+            &targeted
+        }
+        None => manager,
+    };
+
+    if needs_player(&opt.command) {
+        manager.init()?;
+    }
+
+    // The formats in effect here, which may have been reloaded, rather than
+    // those the client read.
+    if !opt.json {
+        apply_default_formats(&mut opt.command, &manager.config());
+    }
+
+    let switching = matches!(opt.command, Args::Switch { .. });
+    let output = execute(manager, opt.command, opt.json)?;
+
+    // A player switched to while already playing does not announce anything.
+    if switching && manager.config().daemon.exclusive {
+        pause_others(manager);
+    }
+
+    Ok(output)
+}
@@ -0,0 +1,339 @@
+use std::fs;
+use std::os::unix::net::UnixStream;
+use std::process::Command;
+
+use multiplayerctl::config::default_config_path;
+use multiplayerctl::mpris::Mpris;
+use multiplayerctl::{default_state_path, ipc, Capability, Config, Error, PlayerManager, Result};
+use serde_json::{json, Value};
+
+use crate::{backend, json_line, Opt};
+
+/// Every capability a player may lack, in the order they are listed.
+const CAPABILITIES: [Capability; 6] = [
+    Capability::Control,
+    Capability::Play,
+    Capability::Pause,
+    Capability::GoNext,
+    Capability::GoPrevious,
+    Capability::Seek,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Ok,
+    Warning,
+    Failure,
+}
+
+/// The result of looking at one thing multiplayerctl depends on.
+struct Check {
+    name: &'static str,
+    outcome: Outcome,
+    message: String,
+    /// What to do about it, unless it is fine.
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Check {
+        Check {
+            name,
+            outcome: Outcome::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warning(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Check {
+        Check {
+            name,
+            outcome: Outcome::Warning,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn failure(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Check {
+        Check {
+            name,
+            outcome: Outcome::Failure,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Prints what is wrong with the setup and how to fix it, failing if
+/// anything would keep commands from working.
+pub fn run(opt: &Opt, config: Result<Config>) -> Result<()> {
+    let mut checks = Vec::new();
+
+    let config = match config {
+        Ok(config) => {
+            let path = opt.config.clone().or_else(default_config_path);
+
+            checks.push(match path.filter(|p| p.exists()) {
+                Some(path) => Check::ok("Config", path.display().to_string()),
+                None => Check::ok("Config", "none, using the defaults"),
+            });
+
+            config
+        }
+        Err(why) => {
+            checks.push(Check::failure(
+                "Config",
+                why.to_string(),
+                "Fix the file, or pass another one with --config. Until then the defaults are used.",
+            ));
+
+            Config::default()
+        }
+    };
+
+    let backend = backend(opt, &config);
+
+    checks.push(session_bus(backend.uses_playerctl()));
+    checks.push(playerctl(backend.uses_playerctl()));
+    checks.push(state_directory());
+    checks.push(daemon());
+
+    // Nothing is written, and the state directory may be what is broken.
+    let manager = PlayerManager::with_cache_path(backend, std::env::temp_dir()).with_config(config);
+
+    // Ignored players too, as a player missing from `list` may be ignored
+    // by mistake.
+    let players = manager.backend().list_players().unwrap_or_default();
+
+    if let Some(check) = playerctld(&manager, &players) {
+        checks.push(check);
+    }
+
+    if players.is_empty() {
+        checks.push(Check::warning(
+            "Players",
+            "none found",
+            "Start a player that supports MPRIS. Browsers need their media integration enabled, and mpv the mpv-mpris plugin.",
+        ));
+    }
+
+    let output = match opt.json {
+        true => json_line(json!({
+            "checks": checks.iter().map(check_json).collect::<Vec<_>>(),
+            "players": players.iter().map(|p| player_json(&manager, p)).collect::<Vec<_>>(),
+        })),
+        false => report(&manager, &checks, &players),
+    };
+
+    print!("{}", output);
+
+    match checks
+        .iter()
+        .filter(|c| c.outcome == Outcome::Failure)
+        .count()
+    {
+        0 => Ok(()),
+        1 => Err(Error::Other(String::from("1 check failed."))),
+        failed => Err(Error::Other(format!("{} checks failed.", failed))),
+    }
+}
+
+fn session_bus(uses_playerctl: bool) -> Check {
+    let why = match Mpris::new() {
+        Ok(_) => return Check::ok("Session bus", "connected"),
+        Err(why) => why,
+    };
+
+    let fix = "Run multiplayerctl inside your desktop session, or point DBUS_SESSION_BUS_ADDRESS at its bus, usually unix:path=$XDG_RUNTIME_DIR/bus.";
+
+    // Falling back to playerctl only helps if playerctl can reach the bus.
+    match uses_playerctl {
+        true => Check::warning("Session bus", format!("unavailable ({})", why), fix),
+        false => Check::failure("Session bus", format!("unavailable ({})", why), fix),
+    }
+}
+
+fn playerctl(uses_playerctl: bool) -> Check {
+    let version = Command::new("playerctl")
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+    let fix = "Install playerctl from your distribution's packages.";
+
+    match (version, uses_playerctl) {
+        (Some(version), true) => Check::ok("playerctl", format!("{}, used as the backend", version)),
+        (Some(version), false) => Check::ok("playerctl", version),
+        (None, true) => Check::failure("playerctl", "not found, but used as the backend", fix),
+        (None, false) => Check::warning(
+            "playerctl",
+            "not found; it is only needed for --format and --follow output, and as a fallback backend",
+            fix,
+        ),
+    }
+}
+
+fn state_directory() -> Check {
+    let path = match default_state_path() {
+        Ok(p) => p,
+        Err(why) => {
+            return Check::failure(
+                "State directory",
+                why.to_string(),
+                "Set XDG_STATE_HOME or HOME to a directory you can write to.",
+            )
+        }
+    };
+
+    let probe = path.join(format!(".doctor.{}", std::process::id()));
+
+    match fs::write(&probe, b"").and_then(|_| fs::remove_file(&probe)) {
+        Ok(()) => Check::ok("State directory", format!("{} is writable", path.display())),
+        Err(why) => Check::failure(
+            "State directory",
+            format!("{} is not writable ({})", path.display(), why),
+            format!(
+                "Make {} yours and writable, or set XDG_STATE_HOME.",
+                path.display()
+            ),
+        ),
+    }
+}
+
+fn daemon() -> Check {
+    let path = match ipc::socket_path() {
+        Some(p) => p,
+        None => return Check::warning(
+            "Daemon",
+            "cannot be reached, as XDG_RUNTIME_DIR is not set",
+            "Log in through a session manager that sets XDG_RUNTIME_DIR, such as systemd-logind.",
+        ),
+    };
+
+    match UnixStream::connect(&path) {
+        Ok(_) => Check::ok("Daemon", format!("listening on {}", path.display())),
+        Err(_) => Check::warning(
+            "Daemon",
+            "not running",
+            "Start `multiplayerctl daemon` with your session, so that it switches to players as they start playing and answers commands faster.",
+        ),
+    }
+}
+
+/// playerctld mirrors whichever player was active last, so it shows up as
+/// a second copy of that player.
+fn playerctld(manager: &PlayerManager, players: &[String]) -> Option<Check> {
+    if !players.iter().any(|p| p == "playerctld") {
+        return None;
+    }
+
+    Some(match manager.config().is_ignored("playerctld") {
+        true => Check::ok("playerctld", "running, and ignored"),
+        false => Check::warning(
+            "playerctld",
+            "running, and listed as a player that mirrors whichever player was active last",
+            "Add \"playerctld\" to `ignore` in the config, or stop it, e.g. with `systemctl --user stop playerctld`.",
+        ),
+    })
+}
+
+fn report(manager: &PlayerManager, checks: &[Check], players: &[String]) -> String {
+    let mut output = String::new();
+
+    for check in checks {
+        let label = match check.outcome {
+            Outcome::Ok => "ok",
+            Outcome::Warning => "warn",
+            Outcome::Failure => "FAIL",
+        };
+
+        output.push_str(&format!("{:<5} {}: {}\n", label, check.name, check.message));
+
+        if let Some(fix) = &check.fix {
+            output.push_str(&format!("      {}\n", fix));
+        }
+    }
+
+    if players.is_empty() {
+        return output;
+    }
+
+    output.push_str("\nPlayers:\n");
+
+    let width = players.iter().map(|p| p.chars().count()).max().unwrap_or(0);
+
+    for name in players {
+        let player = manager.player(name);
+
+        let status = match player.status() {
+            Ok(s) => s.to_string(),
+            Err(why) => format!("unresponsive ({})", why),
+        };
+
+        let missing: Vec<String> = CAPABILITIES
+            .iter()
+            .filter(|c| !player.can(**c))
+            .map(|c| c.to_string())
+            .collect();
+
+        let mut notes = Vec::new();
+
+        if !missing.is_empty() {
+            notes.push(format!("cannot {}", missing.join(", ")));
+        }
+
+        if manager.config().is_ignored(name) {
+            notes.push(String::from("ignored"));
+        }
+
+        let display_name = manager.display_name(name);
+
+        if display_name != *name {
+            notes.push(format!("shown as {}", display_name));
+        }
+
+        output.push_str(&format!("  {:<width$}  {}", name, status, width = width));
+
+        if !notes.is_empty() {
+            output.push_str(&format!("  ({})", notes.join("; ")));
+        }
+
+        output.push('\n');
+    }
+
+    output
+}
+
+fn check_json(check: &Check) -> Value {
+    let outcome = match check.outcome {
+        Outcome::Ok => "ok",
+        Outcome::Warning => "warning",
+        Outcome::Failure => "failure",
+    };
+
+    json!({
+        "check": check.name,
+        "outcome": outcome,
+        "message": check.message,
+        "fix": check.fix,
+    })
+}
+
+fn player_json(manager: &PlayerManager, name: &str) -> Value {
+    let player = manager.player(name);
+
+    let capabilities: serde_json::Map<String, Value> = CAPABILITIES
+        .iter()
+        .map(|c| (c.property().to_string(), json!(player.can(*c))))
+        .collect();
+
+    json!({
+        "name": name,
+        "player_name": manager.display_name(name),
+        "status": player.status().ok().map(|s| s.to_string()),
+        "ignored": manager.config().is_ignored(name),
+        "capabilities": capabilities,
+    })
+}
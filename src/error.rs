@@ -0,0 +1,97 @@
+use std::fmt;
+use std::time::Duration;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The playerctl binary could not be executed.
+    PlayerctlMissing,
+    /// There are no (non-ignored) players to pick from.
+    NoPlayers,
+    /// A player was asked for by name but is not running.
+    UnknownPlayer(String),
+    /// A player failed to carry out a command or report a property.
+    PlayerCommand { player: String, message: String },
+    /// The player does not report a metadata field, e.g. a stream without an album.
+    MissingMetadata { player: String, field: String },
+    /// The player does not support an action, e.g. seeking in a live stream.
+    Unsupported { player: String, action: String },
+    /// A condition did not come true in time.
+    Timeout(String),
+    /// A player did not answer within the playerctl timeout, e.g. because
+    /// it hung while shutting down.
+    Stalled { player: String, timeout: Duration },
+    /// A value given on the command line could not be understood.
+    InvalidArgument(String),
+    /// The config file could not be read or parsed.
+    Config(String),
+    /// An error reported by the daemon, carrying its exit code.
+    Remote { code: i32, message: String },
+    /// Anything else, e.g. failing to read or write the state file.
+    Other(String),
+}
+
+impl Error {
+    pub fn player_command(player: &str, message: impl fmt::Display) -> Error {
+        Error::PlayerCommand {
+            player: player.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// The process exit code used when a command fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(_) | Error::Config(_) => 1,
+            Error::InvalidArgument(_) => 2,
+            Error::PlayerctlMissing => 3,
+            Error::NoPlayers => 4,
+            Error::UnknownPlayer(_) => 5,
+            Error::PlayerCommand { .. } => 6,
+            Error::MissingMetadata { .. } => 7,
+            Error::Unsupported { .. } => 8,
+            Error::Timeout(_) => 9,
+            Error::Stalled { .. } => 10,
+            Error::Remote { code, .. } => *code,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::PlayerctlMissing => {
+                write!(
+                    f,
+                    "Failed to execute playerctl. Are you sure it is installed?"
+                )
+            }
+            Error::NoPlayers => write!(f, "No players found!"),
+            Error::UnknownPlayer(player) => write!(f, "No player named {}", player),
+            Error::PlayerCommand { player, message } => write!(f, "{}: {}", player, message),
+            Error::MissingMetadata { player, field } => {
+                write!(f, "{} does not report a {}", player, field)
+            }
+            Error::Unsupported { player, action } => write!(f, "{} cannot {}", player, action),
+            Error::Stalled { player, timeout } => write!(
+                f,
+                "{} did not answer within {:?} and may be hung",
+                player, timeout
+            ),
+            Error::Timeout(why)
+            | Error::InvalidArgument(why)
+            | Error::Config(why)
+            | Error::Remote { message: why, .. }
+            | Error::Other(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(why: std::io::Error) -> Self {
+        Error::Other(why.to_string())
+    }
+}
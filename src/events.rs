@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+use multiplayerctl::mpris::PROXY_PLAYER;
+use multiplayerctl::{Change, Error, PlayerManager, Result};
+use serde_json::{json, Map, Value};
+
+use crate::follow;
+
+enum Message {
+    Changed(String, Change),
+    Switched,
+}
+
+/// Prints a line for every player event: players appearing and going away,
+/// switches, and changes to the status, track, position or volume. With
+/// `--once`, only the first of them.
+pub fn run(manager: PlayerManager, options: follow::Options, json: bool) -> Result<()> {
+    let manager = Arc::new(manager);
+    let (sender, receiver) = mpsc::channel();
+
+    let watcher = Arc::clone(&manager);
+    let changes = sender.clone();
+
+    thread::spawn(move || {
+        let watched = watcher.backend().watch_changes(|player, change| {
+            let _ = changes.send(Message::Changed(player.to_string(), change));
+        });
+
+        if let Err(why) = watched {
+            eprintln!("{}", why);
+        }
+    });
+
+    let switches = manager.watch_current()?;
+
+    thread::spawn(
+        move || {
+            while switches.wait().is_ok() && sender.send(Message::Switched).is_ok() {}
+        },
+    );
+
+    let mut players: HashMap<String, Snapshot> = manager
+        .players()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| {
+            let snapshot = Snapshot::of(&manager, &name);
+            (name, snapshot)
+        })
+        .collect();
+
+    let mut current = manager.current_name().ok();
+
+    for message in receiver {
+        let mut events = Vec::new();
+
+        match message {
+            Message::Switched => {
+                let switched = manager.current_name().ok();
+
+                if switched != current {
+                    if let Some(player) = &switched {
+                        let mut event = Event::new("switched", player);
+                        event.set("previous", json!(current));
+                        events.push(event);
+                    }
+
+                    current = switched;
+                }
+            }
+            Message::Changed(player, _)
+                if player == PROXY_PLAYER || manager.config().is_ignored(&player) => {}
+            Message::Changed(player, Change::Appeared) => {
+                events.push(Event::new("player-appeared", &player));
+                players.insert(player.clone(), Snapshot::of(&manager, &player));
+            }
+            Message::Changed(player, Change::Vanished) => {
+                events.push(Event::new("player-vanished", &player));
+                players.remove(&player);
+            }
+            Message::Changed(player, Change::Seeked(position)) => {
+                let mut event = Event::new("seeked", &player);
+                event.set("position", json!(position as f64 / 1_000_000.0));
+                events.push(event);
+            }
+            Message::Changed(player, Change::Properties) => {
+                let new = Snapshot::of(&manager, &player);
+                let old = players.get(&player).cloned().unwrap_or_default();
+
+                if new.status != old.status {
+                    let mut event = Event::new("status-changed", &player);
+                    event.set("status", json!(new.status));
+                    events.push(event);
+                }
+
+                if new.track != old.track {
+                    let mut event = Event::new("track-changed", &player);
+                    event.set("title", json!(new.track.title));
+                    event.set("artist", json!(new.track.artist));
+                    event.set("album", json!(new.track.album));
+                    events.push(event);
+                }
+
+                if new.volume != old.volume {
+                    let mut event = Event::new("volume-changed", &player);
+                    event.set("volume", json!(new.volume));
+                    events.push(event);
+                }
+
+                players.insert(player, new);
+            }
+        }
+
+        let mut stdout = io::stdout().lock();
+
+        for event in &events {
+            writeln!(stdout, "{}", event.render(json))?;
+        }
+
+        stdout.flush()?;
+
+        // Events that came about together, such as a new track starting to
+        // play, make one change.
+        if options.once && !events.is_empty() {
+            return Ok(());
+        }
+    }
+
+    Err(Error::Other(String::from("Stopped watching players.")))
+}
+
+/// What events are told apart by.
+#[derive(Clone, Default, PartialEq)]
+struct Snapshot {
+    status: Option<String>,
+    track: Track,
+    volume: Option<f64>,
+}
+
+#[derive(Clone, Default, PartialEq)]
+struct Track {
+    id: Option<String>,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+}
+
+impl Snapshot {
+    fn of(manager: &PlayerManager, name: &str) -> Self {
+        let player = manager.player(name);
+        let metadata = player.metadata().unwrap_or_default();
+
+        Self {
+            status: player.status().ok().map(|s| s.to_string()),
+            track: Track {
+                id: metadata.track_id().map(String::from),
+                title: metadata.title().map(String::from),
+                artist: metadata.artist(),
+                album: metadata.album().map(String::from),
+            },
+            volume: player.volume().ok(),
+        }
+    }
+}
+
+struct Event {
+    kind: &'static str,
+    player: String,
+    /// The details, in the order they are printed.
+    details: Vec<(&'static str, Value)>,
+}
+
+impl Event {
+    fn new(kind: &'static str, player: &str) -> Self {
+        Self {
+            kind,
+            player: player.to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    fn set(&mut self, key: &'static str, value: Value) {
+        self.details.push((key, value));
+    }
+
+    /// Renders the event as a JSON object, or as tab-separated event name,
+    /// player and details.
+    fn render(&self, json: bool) -> String {
+        if json {
+            let mut object = Map::new();
+
+            object.insert(String::from("event"), json!(self.kind));
+            object.insert(String::from("player"), json!(self.player));
+
+            for (key, value) in &self.details {
+                object.insert(key.to_string(), value.clone());
+            }
+
+            return Value::Object(object).to_string();
+        }
+
+        let mut line = format!("{}\t{}", self.kind, self.player);
+
+        for (_, value) in &self.details {
+            line.push('\t');
+
+            match value {
+                Value::String(s) => line.push_str(s),
+                Value::Null => (),
+                other => line.push_str(&other.to_string()),
+            }
+        }
+
+        line
+    }
+}
@@ -0,0 +1,69 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use multiplayerctl::{PlaybackStatus, Player, Result};
+
+/// How often the volume is changed while fading.
+const STEP: Duration = Duration::from_millis(50);
+
+/// Lowers the volume of `player` to nothing over `duration`, pauses it, and
+/// puts the volume back where it was. Players whose volume cannot be read are
+/// paused right away.
+pub fn pause(player: &Player, duration: Duration) -> Result<()> {
+    let volume = match player.status() {
+        Ok(PlaybackStatus::Playing) => player.volume().ok(),
+        _ => None,
+    };
+
+    let volume = match volume {
+        Some(v) => v,
+        None => return player.pause(),
+    };
+
+    let faded = ramp(player, volume, 0.0, duration).and_then(|_| player.pause());
+
+    // The volume is restored even if fading or pausing failed halfway.
+    player.set_volume(volume).and(faded)
+}
+
+/// Starts `player` at no volume and raises it to where it was over
+/// `duration`. Players that are already playing are left alone.
+pub fn play(player: &Player, duration: Duration) -> Result<()> {
+    let volume = match player.status() {
+        Ok(PlaybackStatus::Playing) => return Ok(()),
+        _ => player.volume().ok(),
+    };
+
+    let volume = match volume {
+        Some(v) => v,
+        None => return player.play(),
+    };
+
+    player.set_volume(0.0)?;
+
+    if let Err(why) = player.play() {
+        player.set_volume(volume)?;
+
+        return Err(why);
+    }
+
+    ramp(player, 0.0, volume, duration)
+}
+
+/// Moves the volume of `player` from `from` to `to` over `duration`, ending
+/// exactly at `to`.
+pub fn ramp(player: &Player, from: f64, to: f64, duration: Duration) -> Result<()> {
+    let start = Instant::now();
+
+    loop {
+        let progress = (start.elapsed().as_secs_f64() / duration.as_secs_f64()).min(1.0);
+
+        player.set_volume(from + (to - from) * progress)?;
+
+        if progress >= 1.0 {
+            return Ok(());
+        }
+
+        thread::sleep(STEP);
+    }
+}
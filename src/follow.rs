@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use multiplayerctl::{Error, PlayerManager, Result};
+
+/// How commands that follow changes print them, as given on the command line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    /// Waits for the next change and exits once it is printed, rather than
+    /// printing how things are first and going on.
+    pub once: bool,
+    /// Waits for changes to settle for this long before printing, so that a
+    /// burst of them, as browsers send for every track, prints once.
+    pub debounce: Option<Duration>,
+    /// Leaves out lines identical to the one printed before. [`run`] always
+    /// does.
+    pub dedupe: bool,
+}
+
+/// Prints `render`'s output whenever a player changes, the current player is
+/// switched, or the interval returned by `tick` after each render has passed,
+/// skipping lines identical to the last one.
+pub fn run<T, F>(manager: PlayerManager, options: Options, tick: T, render: F) -> Result<()>
+where
+    T: Fn(&PlayerManager) -> Option<Duration>,
+    F: Fn(&PlayerManager) -> String,
+{
+    let mut last = String::new();
+    let mut first = true;
+
+    watch_debounced(Arc::new(manager), options.debounce, tick, |manager| {
+        let line = render(manager);
+        let changed = line != last;
+
+        // With --once, the first line only tells what a change is.
+        let skip = options.once && first;
+        first = false;
+
+        if !changed {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        if !skip {
+            let mut stdout = io::stdout().lock();
+
+            writeln!(stdout, "{}", line)?;
+            stdout.flush()?;
+        }
+
+        last = line;
+
+        match options.once && !skip {
+            true => Ok(ControlFlow::Break(())),
+            false => Ok(ControlFlow::Continue(())),
+        }
+    })
+}
+
+/// Calls `on_change` right away and then on the same occasions as [`run`],
+/// until it breaks.
+pub fn watch<T, F>(manager: Arc<PlayerManager>, tick: T, on_change: F) -> Result<()>
+where
+    T: Fn(&PlayerManager) -> Option<Duration>,
+    F: FnMut(&PlayerManager) -> Result<ControlFlow<()>>,
+{
+    watch_debounced(manager, None, tick, on_change)
+}
+
+/// Like [`watch`], but after a change waits until there has been none for
+/// `debounce` before calling `on_change`.
+fn watch_debounced<T, F>(
+    manager: Arc<PlayerManager>,
+    debounce: Option<Duration>,
+    tick: T,
+    mut on_change: F,
+) -> Result<()>
+where
+    T: Fn(&PlayerManager) -> Option<Duration>,
+    F: FnMut(&PlayerManager) -> Result<ControlFlow<()>>,
+{
+    let (sender, receiver) = mpsc::channel();
+
+    let watcher = Arc::clone(&manager);
+    let player_changes = sender.clone();
+
+    thread::spawn(move || {
+        let watched = watcher.backend().watch_changes(|_, _| {
+            let _ = player_changes.send(());
+        });
+
+        if let Err(why) = watched {
+            eprintln!("{}", why);
+        }
+    });
+
+    let switches = manager.watch_current()?;
+
+    thread::spawn(
+        move || {
+            while switches.wait().is_ok() && sender.send(()).is_ok() {}
+        },
+    );
+
+    loop {
+        if on_change(&manager)?.is_break() {
+            return Ok(());
+        }
+
+        let received = match tick(&manager) {
+            Some(t) => receiver.recv_timeout(t),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match (received, debounce) {
+            (Err(RecvTimeoutError::Disconnected), _) => return Err(stopped()),
+            // Ticks come on their own, so there is nothing to settle.
+            (Err(RecvTimeoutError::Timeout), _) => (),
+            (Ok(()), Some(debounce)) => loop {
+                match receiver.recv_timeout(debounce) {
+                    Ok(()) => (),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return Err(stopped()),
+                }
+            },
+            (Ok(()), None) => (),
+        }
+
+        // Render once for a burst of changes.
+        while receiver.try_recv().is_ok() {}
+    }
+}
+
+fn stopped() -> Error {
+    Error::Other(String::from("Stopped watching players."))
+}
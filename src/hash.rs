@@ -0,0 +1,26 @@
+//! A hash for the names of files kept across runs, such as the current player
+//! of a session or cached cover art. Unlike those of `DefaultHasher`, and of
+//! the standard `Hash` implementations, its values stay the same from one
+//! Rust release to the next, so the files are found again after a rebuild.
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hashes `parts` with 64-bit FNV-1a, each followed by a byte that cannot
+/// occur in UTF-8, so that `["ab", "c"]` and `["a", "bc"]` differ.
+pub(crate) fn stable_hash<I, P>(parts: I) -> u64
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<[u8]>,
+{
+    let mut hash = OFFSET_BASIS;
+
+    for part in parts {
+        for byte in part.as_ref().iter().chain(&[0xff]) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+
+    hash
+}
@@ -0,0 +1,89 @@
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+use multiplayerctl::config::HeadphonesConfig;
+use multiplayerctl::{pulse, Error, PlayerManager, Result};
+
+use crate::daemon::{pause_playing, resume};
+
+/// Pauses the players given by `headphones.pause` when the default output
+/// stops being headphones, and with `headphones.resume` plays them again
+/// once it is headphones again, until `pactl subscribe` stops.
+///
+/// Works with PulseAudio as well as PipeWire through `pipewire-pulse`.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let config = &manager.config().headphones;
+
+    let mut subscription = pulse::spawn(["subscribe"])?;
+
+    let events = BufReader::new(subscription.stdout.take().unwrap());
+
+    let mut was_on_headphones = on_headphones(config);
+    let mut paused = Vec::new();
+
+    for event in events.lines() {
+        let event = event?;
+
+        // The default sink changes on the server, its port on the sink, and
+        // unplugging shows on the card first.
+        let relevant = [" on sink #", " on server", " on card #"]
+            .iter()
+            .any(|e| event.contains(e));
+
+        if !relevant {
+            continue;
+        }
+
+        let now = match on_headphones(config) {
+            Some(n) => n,
+            None => continue,
+        };
+
+        match (was_on_headphones, now) {
+            (Some(true), false) => paused = pause_playing(&manager, config.pause),
+            (Some(false), true) if config.resume => resume(&manager, &paused),
+            _ => (),
+        }
+
+        if now {
+            paused.clear();
+        }
+
+        was_on_headphones = Some(now);
+    }
+
+    let _ = subscription.wait();
+
+    Err(Error::Other(String::from("pactl subscribe stopped.")))
+}
+
+/// Whether the default output is headphones, or `None` if it cannot be told.
+fn on_headphones(config: &HeadphonesConfig) -> Option<bool> {
+    let (sink, port) = default_output()?;
+
+    Some(config.matches(&sink, port.as_deref()))
+}
+
+/// The default sink and its active port, if it has ports.
+fn default_output() -> Option<(String, Option<String>)> {
+    let info = pulse::run(["info"]).ok()?;
+    let sink = info
+        .lines()
+        .find_map(|line| line.strip_prefix("Default Sink: "))?
+        .trim()
+        .to_string();
+
+    let sinks = pulse::run(["list", "sinks"]).ok()?;
+
+    // Each sink's `Name:` comes before its `Active Port:`.
+    let port = sinks
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.strip_prefix("Name: ") != Some(&sink))
+        .skip(1)
+        .take_while(|line| !line.starts_with("Name: "))
+        .find_map(|line| line.strip_prefix("Active Port: "))
+        .map(String::from);
+
+    Some((sink, port))
+}
@@ -0,0 +1,149 @@
+//! The listening log kept by the daemon when `history.enabled` is set.
+
+use std::path::Path;
+use std::time::Duration;
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::player::Metadata;
+
+/// One track played by one player.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Listen {
+    pub id: i64,
+    pub player: String,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    /// The length of the track in seconds.
+    pub length: Option<f64>,
+    /// Unix timestamps.
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    /// How long the track was actually playing, in seconds.
+    pub played: f64,
+    /// Whether (nearly) all of the track was played.
+    pub finished: bool,
+}
+
+/// The `history.sqlite` database in the state directory.
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open(path: &Path) -> Result<History> {
+        let connection = Connection::open(path).map_err(history_error)?;
+
+        // The daemon writes while the CLI reads.
+        connection
+            .busy_timeout(Duration::from_secs(5))
+            .map_err(history_error)?;
+
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS listens (
+                    id INTEGER PRIMARY KEY,
+                    player TEXT NOT NULL,
+                    artist TEXT,
+                    title TEXT,
+                    album TEXT,
+                    length REAL,
+                    started_at INTEGER NOT NULL,
+                    ended_at INTEGER,
+                    played REAL NOT NULL DEFAULT 0,
+                    finished INTEGER NOT NULL DEFAULT 0
+                );
+                CREATE INDEX IF NOT EXISTS listens_started_at ON listens (started_at);",
+            )
+            .map_err(history_error)?;
+
+        Ok(History { connection })
+    }
+
+    /// Records that `player` started playing the track described by
+    /// `metadata` at `started_at`, returning the id of the new entry.
+    pub fn start(&self, player: &str, metadata: &Metadata, started_at: i64) -> Result<i64> {
+        self.connection
+            .execute(
+                "INSERT INTO listens (player, artist, title, album, length, started_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    player,
+                    metadata.artist(),
+                    metadata.title(),
+                    metadata.album(),
+                    metadata.length_secs(),
+                    started_at
+                ],
+            )
+            .map_err(history_error)?;
+
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// Records that the listen `id` ended at `ended_at` after `played`
+    /// seconds of playback. It counts as finished when at least 90% of the
+    /// track was played.
+    pub fn finish(&self, id: i64, ended_at: i64, played: f64) -> Result<()> {
+        self.connection
+            .execute(
+                "UPDATE listens
+                 SET ended_at = ?2, played = ?3, finished = length > 0 AND ?3 >= length * 0.9
+                 WHERE id = ?1",
+                params![id, ended_at, played],
+            )
+            .map_err(history_error)?;
+
+        Ok(())
+    }
+
+    /// Returns the `limit` most recent listens, newest first.
+    pub fn recent(&self, limit: usize) -> Result<Vec<Listen>> {
+        self.query(
+            "SELECT * FROM listens ORDER BY started_at DESC, id DESC LIMIT ?1",
+            params![limit as i64],
+        )
+    }
+
+    /// Returns the listens that started at or after `since`, oldest first.
+    pub fn since(&self, since: i64) -> Result<Vec<Listen>> {
+        self.query(
+            "SELECT * FROM listens WHERE started_at >= ?1 ORDER BY started_at, id",
+            params![since],
+        )
+    }
+
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<Listen>> {
+        let mut statement = self.connection.prepare(sql).map_err(history_error)?;
+
+        let listens = statement
+            .query_map(params, listen)
+            .and_then(|rows| rows.collect())
+            .map_err(history_error)?;
+
+        Ok(listens)
+    }
+}
+
+fn listen(row: &rusqlite::Row) -> rusqlite::Result<Listen> {
+    Ok(Listen {
+        id: row.get("id")?,
+        player: row.get("player")?,
+        artist: row.get("artist")?,
+        title: row.get("title")?,
+        album: row.get("album")?,
+        length: row.get("length")?,
+        started_at: row.get("started_at")?,
+        ended_at: row.get("ended_at")?,
+        played: row.get("played")?,
+        finished: row.get("finished")?,
+    })
+}
+
+fn history_error(why: rusqlite::Error) -> Error {
+    Error::Other(format!("Failed to access the listening history: {}", why))
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+
+use multiplayerctl::{PlaybackStatus, Player, PlayerManager, Result};
+
+use crate::follow;
+
+/// Runs the configured `[hooks]` as players change, until watching them
+/// fails. Only changes are reported, not the state found at startup.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let mut players: Option<HashMap<String, State>> = None;
+    let mut current = None;
+
+    follow::watch(
+        manager,
+        |_| None,
+        |manager| {
+            let hooks = &manager.config().hooks;
+            let previous_players = players.take();
+
+            let mut states = HashMap::new();
+
+            for name in manager.players().unwrap_or_default() {
+                let player = manager.player(&name);
+                let state = State::of(&player);
+
+                if let Some(previous) = &previous_players {
+                    let old = previous.get(&name).cloned().unwrap_or_default();
+
+                    if state.status != old.status {
+                        match state.status {
+                            Some(PlaybackStatus::Playing) => {
+                                spawn(&hooks.on_play, "play", manager, &player, None)
+                            }
+                            Some(PlaybackStatus::Paused) => {
+                                spawn(&hooks.on_pause, "pause", manager, &player, None)
+                            }
+                            _ => (),
+                        }
+                    }
+
+                    if state.track != old.track && !state.track.is_empty() {
+                        spawn(
+                            &hooks.on_track_change,
+                            "track-change",
+                            manager,
+                            &player,
+                            None,
+                        );
+                    }
+                }
+
+                states.insert(name, state);
+            }
+
+            let new_current = manager.current_name().ok();
+
+            if previous_players.is_some() && new_current != current {
+                if let Some(name) = &new_current {
+                    let previous = current.as_deref();
+
+                    spawn(
+                        &hooks.on_switch,
+                        "switch",
+                        manager,
+                        &manager.player(name),
+                        previous,
+                    );
+                }
+            }
+
+            players = Some(states);
+            current = new_current;
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+/// What is compared between two looks at a player.
+#[derive(Clone, Default)]
+struct State {
+    status: Option<PlaybackStatus>,
+    /// Identifies the track; empty if the player reports nothing about it.
+    track: String,
+}
+
+impl State {
+    fn of(player: &Player) -> Self {
+        let metadata = player.metadata().unwrap_or_default();
+
+        let track = match (metadata.track_id(), metadata.title()) {
+            (None, None) => String::new(),
+            _ => format!(
+                "{:?} {:?} {:?} {:?}",
+                metadata.track_id(),
+                metadata.title(),
+                metadata.artists(),
+                metadata.album()
+            ),
+        };
+
+        Self {
+            status: player.status().ok(),
+            track,
+        }
+    }
+}
+
+/// Starts `hook` through the shell without waiting for it, describing the
+/// event and `player` in `MPCTL_*` variables.
+fn spawn(
+    hook: &Option<String>,
+    event: &str,
+    manager: &PlayerManager,
+    player: &Player,
+    previous_player: Option<&str>,
+) {
+    let hook = match hook {
+        Some(h) => h,
+        None => return,
+    };
+
+    let metadata = player.metadata().unwrap_or_default();
+
+    let mut command = Command::new("sh");
+
+    command
+        .arg("-c")
+        .arg(hook)
+        .stdin(Stdio::null())
+        .env("MPCTL_EVENT", event)
+        .env("MPCTL_PLAYER", player.name())
+        .env("MPCTL_PLAYER_NAME", manager.display_name(player.name()))
+        .env(
+            "MPCTL_STATUS",
+            player.status().map(|s| s.to_string()).unwrap_or_default(),
+        )
+        .env("MPCTL_TITLE", metadata.title().unwrap_or_default())
+        .env("MPCTL_ARTIST", metadata.artist().unwrap_or_default())
+        .env("MPCTL_ALBUM", metadata.album().unwrap_or_default())
+        .env("MPCTL_TRACKID", metadata.track_id().unwrap_or_default())
+        .env("MPCTL_ART_URL", metadata.art_url().unwrap_or_default())
+        .env("MPCTL_URL", metadata.url().unwrap_or_default())
+        .env(
+            "MPCTL_LENGTH",
+            metadata
+                .length_secs()
+                .map(|l| l.to_string())
+                .unwrap_or_default(),
+        );
+
+    if let Some(previous) = previous_player {
+        command.env("MPCTL_PREVIOUS_PLAYER", previous);
+    }
+
+    match command.spawn() {
+        // Waited for elsewhere, so that slow hooks don't hold up the next event.
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(why) => eprintln!("Failed to run {} hook: {}", event, why),
+    }
+}
@@ -0,0 +1,33 @@
+use std::env;
+
+use multiplayerctl::{PlayerManager, Result};
+
+use crate::template::render_fields;
+use crate::{daemon, player_fields};
+
+/// Runs the action configured for the button clicked, from `click` or the
+/// `BLOCK_BUTTON` i3blocks sets, and then prints the block's text, so that
+/// one command both handles clicks and refreshes the block.
+pub fn run(manager: &PlayerManager, click: Option<u8>, forward: bool) -> Result<()> {
+    let button = click.or_else(|| env::var("BLOCK_BUTTON").ok()?.trim().parse().ok());
+    let config = manager.config();
+
+    if let Some(action) = button.and_then(|b| config.i3blocks.actions.for_button(b)) {
+        let args = action.split_whitespace().map(String::from).collect();
+
+        // The text is printed all the same, to keep the block from emptying.
+        if let Err(why) = daemon::command(manager, forward, args) {
+            eprintln!("{}", why);
+        }
+    }
+
+    // Without a player, the empty line hides the block.
+    let text = match manager.current_or_init() {
+        Ok(player) => render_fields(&config.i3blocks.text, &player_fields(manager, &player)),
+        Err(_) => String::new(),
+    };
+
+    println!("{}", text);
+
+    Ok(())
+}
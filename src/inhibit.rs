@@ -0,0 +1,142 @@
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use multiplayerctl::config::IdleInhibit;
+use multiplayerctl::{Error, PlaybackStatus, PlayerManager, Result};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedFd;
+
+use crate::follow;
+
+/// Keeps the system, and with `daemon.inhibit_idle = "screen"` also the
+/// screen, awake while the current player is playing, until watching the
+/// players fails.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let inhibitors = Inhibitors::new(manager.config().daemon.inhibit_idle)?;
+    let mut held: Option<Held> = None;
+
+    follow::watch(
+        manager,
+        |_| None,
+        |manager| {
+            let playing = manager
+                .current_or_init()
+                .ok()
+                .filter(|p| matches!(p.status(), Ok(PlaybackStatus::Playing)));
+
+            match (&held, playing) {
+                (None, Some(player)) => {
+                    let reason = format!("{} is playing", manager.display_name(player.name()));
+                    held = Some(inhibitors.inhibit(&reason));
+                }
+                (Some(_), None) => inhibitors.release(held.take().unwrap()),
+                _ => (),
+            }
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+/// Whatever can be asked to keep things awake.
+struct Inhibitors {
+    logind: Option<Proxy<'static>>,
+    screensaver: Option<Proxy<'static>>,
+}
+
+/// The inhibitors taken, released by `Inhibitors::release`.
+struct Held {
+    /// Kept open for as long as logind should not go idle.
+    logind: Option<OwnedFd>,
+    screensaver: Option<u32>,
+}
+
+impl Inhibitors {
+    /// Fails only if nothing that was asked for can be talked to.
+    fn new(mode: IdleInhibit) -> Result<Inhibitors> {
+        let logind = Connection::system().and_then(|c| {
+            Proxy::new(
+                &c,
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+            )
+        });
+
+        let logind = match logind {
+            Ok(l) => Some(l),
+            Err(why) if mode == IdleInhibit::Screen => {
+                eprintln!("Failed to talk to logind: {}", why);
+                None
+            }
+            Err(why) => return Err(Error::Other(format!("Failed to talk to logind: {}", why))),
+        };
+
+        if mode != IdleInhibit::Screen {
+            return Ok(Inhibitors {
+                logind,
+                screensaver: None,
+            });
+        }
+
+        let screensaver = Connection::session().and_then(|c| {
+            Proxy::new(
+                &c,
+                "org.freedesktop.ScreenSaver",
+                "/org/freedesktop/ScreenSaver",
+                "org.freedesktop.ScreenSaver",
+            )
+        });
+
+        let screensaver = match screensaver {
+            Ok(s) => Some(s),
+            Err(why) if logind.is_some() => {
+                eprintln!("Failed to talk to the screen saver: {}", why);
+                None
+            }
+            Err(why) => {
+                return Err(Error::Other(format!(
+                    "Failed to talk to the screen saver: {}",
+                    why
+                )))
+            }
+        };
+
+        Ok(Inhibitors {
+            logind,
+            screensaver,
+        })
+    }
+
+    /// Takes every inhibitor it can, reporting the ones it cannot.
+    fn inhibit(&self, reason: &str) -> Held {
+        let logind = self.logind.as_ref().and_then(|logind| {
+            logind
+                .call("Inhibit", &("idle", "multiplayerctl", reason, "block"))
+                .map_err(|why| eprintln!("Failed to keep the system awake: {}", why))
+                .ok()
+        });
+
+        let screensaver = self.screensaver.as_ref().and_then(|screensaver| {
+            screensaver
+                .call("Inhibit", &("multiplayerctl", reason))
+                .map_err(|why| eprintln!("Failed to keep the screen awake: {}", why))
+                .ok()
+        });
+
+        Held {
+            logind,
+            screensaver,
+        }
+    }
+
+    fn release(&self, held: Held) {
+        drop(held.logind);
+
+        if let (Some(screensaver), Some(cookie)) = (&self.screensaver, held.screensaver) {
+            if let Err(why) = screensaver.call::<_, _, ()>("UnInhibit", &(cookie)) {
+                eprintln!("Failed to let the screen go idle: {}", why);
+            }
+        }
+    }
+}
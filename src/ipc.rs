@@ -0,0 +1,123 @@
+//! The protocol spoken between the CLI and `multiplayerctl daemon`.
+//!
+//! A request is the command line of the invocation, each argument terminated
+//! by a NUL byte. The daemon answers with `ok` or `err <exit code>` on the
+//! first line, followed by the output (or error message) of the command.
+
+use std::env;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::Error as CommandError;
+use crate::manager::{default_runtime_path, session_key};
+
+/// Returns `$MULTIPLAYERCTL_SOCKET`, or else
+/// `$XDG_RUNTIME_DIR/multiplayerctl/daemon-<session>.sock` if the runtime
+/// directory is known. Each session has its own daemon, since a daemon only
+/// sees the players on its own session bus.
+pub fn socket_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("MULTIPLAYERCTL_SOCKET") {
+        return Some(PathBuf::from(path));
+    }
+
+    let name = match session_key() {
+        Some(key) => format!("daemon-{}.sock", key),
+        None => String::from("daemon.sock"),
+    };
+
+    default_runtime_path().map(|dir| dir.join(name))
+}
+
+/// How long to wait for the daemon to run a command, which may take a while
+/// for those that download something.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sends a command line to the daemon. Fails only if no daemon is listening,
+/// for the command to be run some other way: once it has been sent, it may
+/// have run, so a daemon that does not answer is the command's error.
+pub fn send(args: &[String]) -> Result<Result<String, CommandError>, Error> {
+    let path = match socket_path() {
+        Some(p) => p,
+        None => {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "$XDG_RUNTIME_DIR is not set",
+            ))
+        }
+    };
+
+    let mut stream = UnixStream::connect(path)?;
+
+    Ok(exchange(&mut stream, args).unwrap_or_else(|why| {
+        Err(CommandError::Other(match why.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => format!(
+                "The daemon did not answer within {} seconds.",
+                RESPONSE_TIMEOUT.as_secs()
+            ),
+            _ => format!("The daemon did not answer: {}", why),
+        }))
+    }))
+}
+
+fn exchange(
+    stream: &mut UnixStream,
+    args: &[String],
+) -> Result<Result<String, CommandError>, Error> {
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+
+    for arg in args {
+        stream.write_all(arg.as_bytes())?;
+        stream.write_all(b"\0")?;
+    }
+
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (status, output) = match response.split_once('\n') {
+        Some(v) => v,
+        None => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Malformed response from daemon",
+            ))
+        }
+    };
+
+    if status == "ok" {
+        return Ok(Ok(output.to_string()));
+    }
+
+    match status.strip_prefix("err ").and_then(|c| c.parse().ok()) {
+        Some(code) => Ok(Err(CommandError::Remote {
+            code,
+            message: output.to_string(),
+        })),
+        None => Err(Error::new(
+            ErrorKind::InvalidData,
+            "Malformed response from daemon",
+        )),
+    }
+}
+
+/// Reads a command line sent by [`send`].
+pub fn read_request(stream: &mut UnixStream) -> Result<Vec<String>, Error> {
+    let mut request = String::new();
+    stream.read_to_string(&mut request)?;
+
+    Ok(request.split_terminator('\0').map(String::from).collect())
+}
+
+pub fn write_response(
+    stream: &mut UnixStream,
+    response: &Result<String, CommandError>,
+) -> Result<(), Error> {
+    match response {
+        Ok(output) => write!(stream, "ok\n{}", output),
+        Err(why) => write!(stream, "err {}\n{}", why.exit_code(), why),
+    }
+}
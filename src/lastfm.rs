@@ -0,0 +1,134 @@
+//! A minimal client for the parts of the Last.fm API used for scrobbling.
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::scrobbler::{Scrobbler, Track};
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const AUTH_URL: &str = "https://www.last.fm/api/auth/";
+
+/// Talks to Last.fm with an API account, and once authorized with the
+/// session key of a user.
+pub struct Lastfm {
+    api_key: String,
+    api_secret: String,
+    session: Option<String>,
+}
+
+impl Lastfm {
+    pub fn new(api_key: &str, api_secret: &str, session: Option<String>) -> Lastfm {
+        Lastfm {
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+            session,
+        }
+    }
+
+    /// Requests a token for the user to authorize at [`Lastfm::auth_url`].
+    pub fn token(&self) -> Result<String> {
+        let response = self.call("auth.getToken", &mut Vec::new())?;
+
+        string_at(&response, &["token"])
+    }
+
+    pub fn auth_url(&self, token: &str) -> String {
+        format!("{}?api_key={}&token={}", AUTH_URL, self.api_key, token)
+    }
+
+    /// Trades a token the user has authorized for a session key, which does
+    /// not expire.
+    pub fn session(&self, token: &str) -> Result<String> {
+        let response = self.call("auth.getSession", &mut vec![("token", token.to_string())])?;
+
+        string_at(&response, &["session", "key"])
+    }
+
+    fn track_params(&self, track: &Track) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("artist", track.artist.clone()),
+            ("track", track.title.clone()),
+        ];
+
+        if let Some(album) = &track.album {
+            params.push(("album", album.clone()));
+        }
+
+        if let Some(length) = track.length {
+            params.push(("duration", (length as u64).to_string()));
+        }
+
+        params
+    }
+
+    /// Calls a signed API method, adding the key, the session and the
+    /// signature to `params`.
+    fn call(&self, method: &str, params: &mut Vec<(&str, String)>) -> Result<Value> {
+        params.push(("method", method.to_string()));
+        params.push(("api_key", self.api_key.clone()));
+
+        if let Some(session) = &self.session {
+            params.push(("sk", session.clone()));
+        }
+
+        params.push(("api_sig", self.signature(params)));
+        params.push(("format", String::from("json")));
+
+        let form: Vec<(&str, &str)> = params.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+        let response = match ureq::post(API_URL).send_form(&form) {
+            Ok(r) => r,
+            // Last.fm explains failures in the body.
+            Err(ureq::Error::Status(_, r)) => r,
+            Err(why) => return Err(lastfm_error(method, why)),
+        };
+
+        let body: Value = response
+            .into_string()
+            .map_err(|why| lastfm_error(method, why))
+            .and_then(|s| serde_json::from_str(&s).map_err(|why| lastfm_error(method, why)))?;
+
+        match body.get("message").and_then(Value::as_str) {
+            Some(message) if body.get("error").is_some() => Err(lastfm_error(method, message)),
+            _ => Ok(body),
+        }
+    }
+
+    /// The MD5 of the parameters sorted by name and concatenated, followed by
+    /// the API secret.
+    fn signature(&self, params: &[(&str, String)]) -> String {
+        let mut sorted: Vec<_> = params.iter().collect();
+        sorted.sort_by_key(|(k, _)| *k);
+
+        let mut signed: String = sorted.iter().map(|(k, v)| format!("{}{}", k, v)).collect();
+        signed.push_str(&self.api_secret);
+
+        format!("{:x}", md5::compute(signed))
+    }
+}
+
+impl Scrobbler for Lastfm {
+    fn now_playing(&self, track: &Track) -> Result<()> {
+        self.call("track.updateNowPlaying", &mut self.track_params(track))
+            .map(|_| ())
+    }
+
+    fn scrobble(&self, track: &Track, started_at: i64) -> Result<()> {
+        let mut params = self.track_params(track);
+        params.push(("timestamp", started_at.to_string()));
+
+        self.call("track.scrobble", &mut params).map(|_| ())
+    }
+}
+
+fn string_at(value: &Value, path: &[&str]) -> Result<String> {
+    path.iter()
+        .try_fold(value, |v, key| v.get(key))
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| Error::Other(format!("Unexpected response from Last.fm: {}", value)))
+}
+
+fn lastfm_error(method: &str, why: impl std::fmt::Display) -> Error {
+    Error::Other(format!("Last.fm {} failed: {}", method, why))
+}
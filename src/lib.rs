@@ -0,0 +1,60 @@
+//! Control several MPRIS players while keeping track of which one is "current".
+//!
+//! This is the library behind the `multiplayerctl` binary. Programs such as
+//! bars or daemons can use it directly instead of parsing CLI output:
+//!
+//! ```no_run
+//! use multiplayerctl::{Backend, PlayerManager};
+//!
+//! let manager = PlayerManager::new(Backend::new("mpris")).unwrap();
+//! manager.init().unwrap();
+//! manager.switch_next().unwrap();
+//!
+//! let player = manager.current().unwrap();
+//! player.set_volume(0.5).unwrap();
+//! println!("{}: {}", player.name(), player.status().unwrap());
+//! ```
+
+pub mod art;
+mod backend;
+mod child;
+pub mod config;
+mod error;
+mod hash;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod ipc;
+#[cfg(feature = "scrobble")]
+pub mod lastfm;
+#[cfg(feature = "scrobble")]
+pub mod listenbrainz;
+pub mod lyrics;
+mod manager;
+pub mod mpd;
+pub mod mpris;
+mod player;
+pub mod playerctl;
+pub mod pulse;
+#[cfg(feature = "scrobble")]
+pub mod scrobbler;
+pub mod snapcast;
+mod watch;
+
+pub use backend::{Backend, PlayerBackend};
+pub use child::ChildGuard;
+pub use config::Config;
+pub use error::{Error, Result};
+pub use manager::{
+    default_cache_path, default_runtime_path, default_state_path, session_key, PlayerManager,
+};
+pub use player::{Capability, Change, LoopStatus, Metadata, MetadataValue, PlaybackStatus, Player};
+pub use watch::FileWatcher;
+
+/// Sent with requests to web services, several of which ask clients to
+/// identify themselves.
+#[cfg(feature = "http")]
+const USER_AGENT: &str = concat!(
+    "multiplayerctl/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/Duckonaut/multiplayerctl)"
+);
@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use multiplayerctl::{Metadata, PlayerManager, Result};
+use serde_json::{json, Value};
+
+use crate::{art, follow, playing};
+
+/// Prints a compact JSON object with everything about the current player
+/// whenever any of it changes, and every second while playing to keep the
+/// position current, so that one `deflisten` feeds a whole eww widget.
+pub fn run(manager: PlayerManager, options: follow::Options) -> Result<()> {
+    let art = RefCell::new(None);
+
+    follow::run(
+        manager,
+        options,
+        |manager| playing(manager).then_some(Duration::from_secs(1)),
+        |manager| render(manager, &mut art.borrow_mut()).to_string(),
+    )
+}
+
+/// The track the art was last looked up for, and what was found.
+type ArtLookup = Option<(ArtKey, Option<PathBuf>)>;
+
+/// What the art depends on: the player, the track and its `mpris:artUrl`,
+/// and the artist and album for covers found through MusicBrainz.
+type ArtKey = (
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+fn render(manager: &PlayerManager, art: &mut ArtLookup) -> Value {
+    let none = json!({
+        "player": null,
+        "player_name": null,
+        "status": null,
+        "metadata": null,
+        "position": null,
+        "volume": null,
+        "art": null,
+    });
+
+    let player = match manager.current_or_init() {
+        Ok(p) => p,
+        Err(_) => return none,
+    };
+
+    let metadata = player.metadata().unwrap_or_default();
+
+    let art = cover_art(manager, player.name(), &metadata, art);
+
+    json!({
+        "player": player.name(),
+        "player_name": manager.display_name(player.name()),
+        "status": player.status().ok().map(|s| s.to_string()),
+        "metadata": {
+            "title": metadata.title(),
+            "artist": metadata.artist(),
+            "artists": metadata.artists(),
+            "album": metadata.album(),
+            "length": metadata.length_secs(),
+            "url": metadata.url(),
+        },
+        "position": player.position().ok().map(|p| p as f64 / 1_000_000.0),
+        "volume": player.volume().ok(),
+        "art": art,
+    })
+}
+
+/// The cover art of the track, fetched only when the track changes: this
+/// renders every second while playing, and failed downloads are not cached.
+fn cover_art(
+    manager: &PlayerManager,
+    player: &str,
+    metadata: &Metadata,
+    last: &mut ArtLookup,
+) -> Option<PathBuf> {
+    let key = (
+        player.to_string(),
+        metadata.track_id().map(String::from),
+        metadata.art_url().map(String::from),
+        metadata.artist(),
+        metadata.album().map(String::from),
+    );
+
+    if let Some((looked_up, found)) = last {
+        if *looked_up == key {
+            return found.clone();
+        }
+    }
+
+    let found = art::fetch(
+        manager.cache_path(),
+        player,
+        metadata,
+        None,
+        manager.config().art.musicbrainz,
+    )
+    .ok();
+
+    *last = Some((key, found.clone()));
+
+    found
+}
@@ -0,0 +1,195 @@
+//! A client for submitting listens to ListenBrainz.
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::error::{Error, Result};
+use crate::scrobbler::{Scrobbler, Track};
+
+/// ListenBrainz takes at most this many listens per request.
+const MAX_LISTENS: usize = 1000;
+
+/// Submits listens with a user token. Listens that cannot be submitted
+/// because ListenBrainz is unreachable are queued in a file and sent along
+/// with a later submission, or by [`Scrobbler::flush`].
+pub struct ListenBrainz {
+    url: String,
+    token: String,
+    queue: PathBuf,
+}
+
+/// Why a submission failed.
+enum Failure {
+    /// Worth trying again later.
+    Unreachable(String),
+    Rejected(String),
+}
+
+impl ListenBrainz {
+    /// Talks to the server at `url`, such as `https://api.listenbrainz.org`,
+    /// keeping unsent listens in the file at `queue`.
+    pub fn new(url: &str, token: &str, queue: PathBuf) -> ListenBrainz {
+        ListenBrainz {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            queue,
+        }
+    }
+
+    fn submit(&self, listen_type: &str, payload: &[Value]) -> std::result::Result<(), Failure> {
+        let body = json!({ "listen_type": listen_type, "payload": payload });
+
+        let submitted = ureq::post(&format!("{}/1/submit-listens", self.url))
+            .set("Authorization", &format!("Token {}", self.token))
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string());
+
+        match submitted {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(code, response)) => {
+                let message = response
+                    .into_string()
+                    .ok()
+                    .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+                    .and_then(|v| v.get("error").and_then(Value::as_str).map(String::from))
+                    .unwrap_or_else(|| format!("status {}", code));
+
+                match code {
+                    429 | 500.. => Err(Failure::Unreachable(message)),
+                    _ => Err(Failure::Rejected(message)),
+                }
+            }
+            Err(why) => Err(Failure::Unreachable(why.to_string())),
+        }
+    }
+
+    fn queued(&self) -> Result<Vec<Value>> {
+        let contents = match fs::read_to_string(&self.queue) {
+            Ok(c) => c,
+            Err(why) if why.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(why) => return Err(queue_error(why)),
+        };
+
+        // A line cut short by a crash is dropped rather than blocking the rest.
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    fn enqueue(&self, listen: &Value) -> Result<()> {
+        if let Some(dir) = self.queue.parent() {
+            fs::create_dir_all(dir).map_err(queue_error)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.queue)
+            .map_err(queue_error)?;
+
+        writeln!(file, "{}", listen).map_err(queue_error)
+    }
+}
+
+impl Scrobbler for ListenBrainz {
+    fn now_playing(&self, track: &Track) -> Result<()> {
+        match self.submit(
+            "playing_now",
+            &[json!({ "track_metadata": metadata(track) })],
+        ) {
+            Ok(()) => self.flush(),
+            Err(Failure::Unreachable(why) | Failure::Rejected(why)) => {
+                Err(listenbrainz_error(&why))
+            }
+        }
+    }
+
+    fn scrobble(&self, track: &Track, started_at: i64) -> Result<()> {
+        let listen = json!({ "listened_at": started_at, "track_metadata": metadata(track) });
+
+        match self.submit("single", std::slice::from_ref(&listen)) {
+            Ok(()) => self.flush(),
+            Err(Failure::Unreachable(why)) => {
+                self.enqueue(&listen)?;
+
+                Err(listenbrainz_error(&format!(
+                    "{}; queued the listen for later",
+                    why
+                )))
+            }
+            Err(Failure::Rejected(why)) => Err(listenbrainz_error(&why)),
+        }
+    }
+
+    fn flush(&self) -> Result<()> {
+        let queued = self.queued()?;
+        let mut rejected = None;
+
+        for (i, listens) in queued.chunks(MAX_LISTENS).enumerate() {
+            match self.submit("import", listens) {
+                Ok(()) => (),
+                // Keeps what is left for the next try.
+                Err(Failure::Unreachable(why)) => {
+                    let left: String = queued[i * MAX_LISTENS..]
+                        .iter()
+                        .map(|listen| format!("{}\n", listen))
+                        .collect();
+
+                    fs::write(&self.queue, left).map_err(queue_error)?;
+
+                    return Err(listenbrainz_error(&why));
+                }
+                // Retrying would fail the same way.
+                Err(Failure::Rejected(why)) => {
+                    rejected = Some(format!(
+                        "{} queued listens were rejected: {}",
+                        listens.len(),
+                        why
+                    ))
+                }
+            }
+        }
+
+        if !queued.is_empty() {
+            fs::remove_file(&self.queue).map_err(queue_error)?;
+        }
+
+        match rejected {
+            Some(why) => Err(listenbrainz_error(&why)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn metadata(track: &Track) -> Value {
+    let mut metadata = json!({
+        "artist_name": track.artist,
+        "track_name": track.title,
+        "additional_info": {
+            "submission_client": "multiplayerctl",
+            "submission_client_version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+
+    if let Some(album) = &track.album {
+        metadata["release_name"] = json!(album);
+    }
+
+    if let Some(length) = track.length {
+        metadata["additional_info"]["duration_ms"] = json!((length * 1000.0) as u64);
+    }
+
+    metadata
+}
+
+fn listenbrainz_error(why: &str) -> Error {
+    Error::Other(format!("ListenBrainz submission failed: {}", why))
+}
+
+fn queue_error(why: std::io::Error) -> Error {
+    Error::Other(format!("Failed to access the ListenBrainz queue: {}", why))
+}
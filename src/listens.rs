@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use multiplayerctl::history::{History, Listen};
+use multiplayerctl::{Error, PlaybackStatus, PlayerManager, Result};
+use serde::Serialize;
+
+use crate::{follow, format_timestamp};
+
+/// Logs every track the players play to the listening history, until
+/// watching the players fails.
+pub fn record(manager: Arc<PlayerManager>) -> Result<()> {
+    let history = History::open(&database(&manager))?;
+    let mut playing: HashMap<String, Playback> = HashMap::new();
+
+    follow::watch(
+        manager,
+        |_| None,
+        |manager| {
+            let players = manager.players().unwrap_or_default();
+
+            // Players that went away are done with their track.
+            playing.retain(|name, playback| {
+                let running = players.contains(name);
+
+                if !running {
+                    playback.finish(&history);
+                }
+
+                running
+            });
+
+            for name in players {
+                let player = manager.player(&name);
+                let metadata = player.metadata().unwrap_or_default();
+                let status = player.status().ok();
+
+                let track = format!(
+                    "{:?} {:?} {:?} {:?}",
+                    metadata.track_id(),
+                    metadata.title(),
+                    metadata.artists(),
+                    metadata.album()
+                );
+
+                if let Some(playback) = playing.get(&name) {
+                    if playback.track != track {
+                        playback.finish(&history);
+                        playing.remove(&name);
+                    }
+                }
+
+                if let Some(playback) = playing.get_mut(&name) {
+                    playback.update(status);
+                    continue;
+                }
+
+                // A track only counts once it plays.
+                if status != Some(PlaybackStatus::Playing) || metadata.title().is_none() {
+                    continue;
+                }
+
+                match history.start(&name, &metadata, unix_time()) {
+                    Ok(id) => {
+                        playing.insert(
+                            name,
+                            Playback {
+                                id,
+                                track,
+                                played: Duration::ZERO,
+                                playing_since: Some(Instant::now()),
+                            },
+                        );
+                    }
+                    Err(why) => eprintln!("{}", why),
+                }
+            }
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+/// The track a player is on, as it is being logged.
+struct Playback {
+    id: i64,
+    track: String,
+    /// How long it played before it was last paused.
+    played: Duration,
+    playing_since: Option<Instant>,
+}
+
+impl Playback {
+    fn update(&mut self, status: Option<PlaybackStatus>) {
+        match (status, self.playing_since) {
+            (Some(PlaybackStatus::Playing), None) => self.playing_since = Some(Instant::now()),
+            (Some(PlaybackStatus::Playing), Some(_)) => (),
+            (_, Some(since)) => {
+                self.played += since.elapsed();
+                self.playing_since = None;
+            }
+            (_, None) => (),
+        }
+    }
+
+    fn finish(&self, history: &History) {
+        let played = self.played + self.playing_since.map_or(Duration::ZERO, |s| s.elapsed());
+
+        if let Err(why) = history.finish(self.id, unix_time(), played.as_secs_f64()) {
+            eprintln!("{}", why);
+        }
+    }
+}
+
+/// Lists the `limit` most recent listens, newest first.
+pub fn print(manager: &PlayerManager, limit: usize, json: bool) -> Result<String> {
+    let listens = open(manager)?.recent(limit)?;
+
+    if json {
+        return serde_json::to_string(&listens)
+            .map(|line| format!("{}\n", line))
+            .map_err(|why| Error::Other(why.to_string()));
+    }
+
+    Ok(listens
+        .iter()
+        .map(|listen| format!("{}\n", describe(listen)))
+        .collect())
+}
+
+fn describe(listen: &Listen) -> String {
+    let note = match (listen.ended_at, listen.finished) {
+        (None, _) => " (in progress)",
+        (Some(_), false) => " (skipped)",
+        (Some(_), true) => "",
+    };
+
+    format!(
+        "{}  {}  {} - {}{}",
+        format_timestamp(listen.started_at),
+        listen.player,
+        listen.artist.as_deref().unwrap_or("?"),
+        listen.title.as_deref().unwrap_or("?"),
+        note
+    )
+}
+
+/// Totals for the listens over the last `period` (`day`, `week`, `month`,
+/// `year` or `all`), with the `limit` most played artists and tracks.
+pub fn stats(manager: &PlayerManager, period: &str, limit: usize, json: bool) -> Result<String> {
+    let period = period.to_lowercase();
+
+    let days = match period.as_str() {
+        "day" => Some(1),
+        "week" => Some(7),
+        "month" => Some(30),
+        "year" => Some(365),
+        _ => None,
+    };
+
+    let since = days.map_or(0, |d| unix_time() - d * 24 * 60 * 60);
+    let listens = open(manager)?.since(since)?;
+
+    let mut artists = Tally::default();
+    let mut tracks = Tally::default();
+    let mut players = Tally::default();
+
+    for listen in &listens {
+        let artist = listen.artist.as_deref().unwrap_or("?");
+        let title = listen.title.as_deref().unwrap_or("?");
+
+        artists.add(artist.to_string(), listen.played);
+        tracks.add(format!("{} - {}", artist, title), listen.played);
+        players.add(manager.display_name(&listen.player), listen.played);
+    }
+
+    let stats = Stats {
+        period,
+        since: days.map(|_| since),
+        listens: listens.len(),
+        listening_time: listens.iter().map(|l| l.played).sum(),
+        artists: artists.top(limit),
+        tracks: tracks.top(limit),
+        players: players.top(usize::MAX),
+    };
+
+    if json {
+        return serde_json::to_string(&stats)
+            .map(|line| format!("{}\n", line))
+            .map_err(|why| Error::Other(why.to_string()));
+    }
+
+    let mut output = format!(
+        "{} of listening over {} tracks\n",
+        format_duration(stats.listening_time),
+        stats.listens
+    );
+
+    for (heading, counts) in [
+        ("Top artists", &stats.artists),
+        ("Top tracks", &stats.tracks),
+        ("Players", &stats.players),
+    ] {
+        if counts.is_empty() {
+            continue;
+        }
+
+        let width = counts.iter().map(|c| c.listens.to_string().len()).max();
+
+        output.push_str(&format!("\n{}\n", heading));
+
+        for count in counts {
+            output.push_str(&format!(
+                "  {:>width$}  {:>7}  {}\n",
+                count.listens,
+                format_duration(count.listening_time),
+                count.name,
+                width = width.unwrap_or(1)
+            ));
+        }
+    }
+
+    Ok(output)
+}
+
+#[derive(Serialize)]
+struct Stats {
+    period: String,
+    /// Unix timestamp; absent for `all`.
+    since: Option<i64>,
+    listens: usize,
+    /// In seconds.
+    listening_time: f64,
+    artists: Vec<Count>,
+    tracks: Vec<Count>,
+    players: Vec<Count>,
+}
+
+#[derive(Serialize)]
+struct Count {
+    name: String,
+    listens: usize,
+    listening_time: f64,
+}
+
+/// Listens and listening time per artist, track or player.
+#[derive(Default)]
+struct Tally(HashMap<String, (usize, f64)>);
+
+impl Tally {
+    fn add(&mut self, name: String, played: f64) {
+        let entry = self.0.entry(name).or_default();
+        entry.0 += 1;
+        entry.1 += played;
+    }
+
+    /// The `limit` entries listened to most often, then longest.
+    fn top(self, limit: usize) -> Vec<Count> {
+        let mut counts: Vec<Count> = self
+            .0
+            .into_iter()
+            .map(|(name, (listens, listening_time))| Count {
+                name,
+                listens,
+                listening_time,
+            })
+            .collect();
+
+        counts.sort_by(|a, b| {
+            b.listens
+                .cmp(&a.listens)
+                .then(b.listening_time.total_cmp(&a.listening_time))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        counts.truncate(limit);
+
+        counts
+    }
+}
+
+/// Formats seconds as e.g. `2h 05m`, `12m 30s` or `40s`.
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds as u64;
+
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
+fn open(manager: &PlayerManager) -> Result<History> {
+    let path = database(manager);
+
+    if !path.exists() {
+        return Err(Error::Other(String::from(
+            "There is no listening history yet. Set `enabled = true` in the [history] section of the config and run the daemon.",
+        )));
+    }
+
+    History::open(&path)
+}
+
+fn database(manager: &PlayerManager) -> PathBuf {
+    manager.state_path().join("history.sqlite")
+}
+
+fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
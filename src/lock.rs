@@ -0,0 +1,131 @@
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use multiplayerctl::{Error, PlayerManager, Result};
+use zbus::blocking::{Connection, MessageIterator, Proxy};
+use zbus::message::Type;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::MatchRule;
+
+use crate::daemon::{pause_playing, resume};
+
+/// Screen lockers announce themselves under either name.
+const SCREENSAVER_INTERFACES: &[&str] = &["org.freedesktop.ScreenSaver", "org.gnome.ScreenSaver"];
+const LOGIND: &str = "org.freedesktop.login1";
+const LOGIND_SESSION: &str = "org.freedesktop.login1.Session";
+
+/// Pauses the players given by `lock.pause` when the screen is locked, and
+/// plays them again on unlocking with `lock.resume`. Never returns unless
+/// neither the screen saver nor logind can be listened to.
+///
+/// Both the session's screen saver and logind's `Lock` and `Unlock` signals
+/// are listened to, since desktops differ in which of them they send.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let config = &manager.config().lock;
+    let (sender, receiver) = mpsc::channel();
+
+    let screensaver = sender.clone();
+
+    thread::spawn(move || {
+        if let Err(why) = watch_screensaver(&screensaver) {
+            eprintln!("Failed to watch the screen saver: {}", why);
+        }
+    });
+
+    thread::spawn(move || {
+        if let Err(why) = watch_logind(&sender) {
+            eprintln!("Failed to watch logind for locking: {}", why);
+        }
+    });
+
+    // The players paused on locking, while the screen is locked.
+    let mut paused: Option<Vec<String>> = None;
+
+    // Both sources may announce the same lock, so repeats are ignored.
+    for locked in receiver {
+        if locked && paused.is_none() {
+            paused = Some(pause_playing(&manager, config.pause));
+        } else if !locked {
+            if let Some(players) = paused.take().filter(|_| config.resume) {
+                resume(&manager, &players);
+            }
+        }
+    }
+
+    Err(Error::Other(String::from(
+        "Stopped watching for the screen to lock.",
+    )))
+}
+
+fn watch_screensaver(sender: &Sender<bool>) -> zbus::Result<()> {
+    let connection = Connection::session()?;
+
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .member("ActiveChanged")?
+        .build();
+
+    for message in MessageIterator::for_match_rule(rule, &connection, None)? {
+        let message = message?;
+        let header = message.header();
+
+        let from_screensaver = header
+            .interface()
+            .is_some_and(|i| SCREENSAVER_INTERFACES.contains(&i.as_str()));
+
+        if !from_screensaver {
+            continue;
+        }
+
+        if let Ok(active) = message.body().deserialize::<bool>() {
+            if sender.send(active).is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn watch_logind(sender: &Sender<bool>) -> zbus::Result<()> {
+    let connection = Connection::system()?;
+
+    let manager = Proxy::new(
+        &connection,
+        LOGIND,
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )?;
+
+    let session: zbus::Result<OwnedObjectPath> =
+        manager.call("GetSessionByPID", &(std::process::id()));
+
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(LOGIND)?
+        .interface(LOGIND_SESSION)?;
+
+    // A daemon started as a service is outside of any session, so it goes by
+    // whichever session locks.
+    let rule = match session {
+        Ok(path) => rule.path(path)?.build(),
+        Err(_) => rule.build(),
+    };
+
+    for message in MessageIterator::for_match_rule(rule, &connection, None)? {
+        let message = message?;
+
+        let locked = match message.header().member().map(|m| m.as_str()) {
+            Some("Lock") => true,
+            Some("Unlock") => false,
+            _ => continue,
+        };
+
+        if sender.send(locked).is_err() {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
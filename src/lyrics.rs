@@ -0,0 +1,234 @@
+//! Lyrics for a track, from an `.lrc` file next to it or from
+//! [LRCLIB](https://lrclib.net), kept in the cache once downloaded.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::art::percent_decode;
+use crate::error::{Error, Result};
+use crate::hash::stable_hash;
+use crate::player::Metadata;
+
+#[cfg(feature = "http")]
+const SEARCH_URL: &str = "https://lrclib.net/api/search";
+
+/// How long to wait for LRCLIB, so that the daemon is not held up for long.
+#[cfg(feature = "http")]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The lyrics of a track, synced to it if possible.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lyrics {
+    /// Each line with when it starts, in microseconds, in order. Empty if
+    /// the lyrics are not synced.
+    pub synced: Vec<(i64, String)>,
+    /// The lyrics as plain text, if there are no synced ones.
+    pub plain: Option<String>,
+    /// Whether the track is known to have no lyrics.
+    pub instrumental: bool,
+}
+
+impl Lyrics {
+    /// Parses the synced lyrics of an LRC file, where lines look like
+    /// `[01:23.45] Words` and may carry several timestamps.
+    pub fn from_lrc(lrc: &str) -> Lyrics {
+        let mut synced = Vec::new();
+
+        for line in lrc.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+
+            while let Some((tag, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                // Tags such as `[ar:Artist]` carry no time and are skipped.
+                match parse_timestamp(tag) {
+                    Some(time) => times.push(time),
+                    None => break,
+                }
+
+                rest = after;
+            }
+
+            synced.extend(
+                times
+                    .into_iter()
+                    .map(|time| (time, rest.trim().to_string())),
+            );
+        }
+
+        synced.sort_by_key(|(time, _)| *time);
+
+        Lyrics {
+            synced,
+            plain: None,
+            instrumental: false,
+        }
+    }
+
+    /// The line sung at `position`, in microseconds. `None` before the first
+    /// line, or if the lyrics are not synced.
+    pub fn line_at(&self, position: i64) -> Option<&str> {
+        let sung = self.synced.partition_point(|(time, _)| *time <= position);
+
+        sung.checked_sub(1).map(|i| self.synced[i].1.as_str())
+    }
+
+    /// When the line after the one sung at `position` starts, in
+    /// microseconds.
+    pub fn next_line_at(&self, position: i64) -> Option<i64> {
+        self.synced
+            .iter()
+            .map(|(time, _)| *time)
+            .find(|time| *time > position)
+    }
+
+    /// All of the lyrics as plain text, one line per line.
+    pub fn text(&self) -> String {
+        match &self.plain {
+            Some(plain) => plain.clone(),
+            None => self
+                .synced
+                .iter()
+                .map(|(_, line)| line.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Finds the lyrics of the track described by `metadata`: in an `.lrc` file
+/// next to it, in `cache_dir/lyrics`, or on LRCLIB, whose answer is cached.
+pub fn fetch(cache_dir: &Path, player: &str, metadata: &Metadata) -> Result<Lyrics> {
+    let title = metadata.title().ok_or_else(|| Error::MissingMetadata {
+        player: player.to_string(),
+        field: String::from("title"),
+    })?;
+
+    if let Some(lrc) = metadata
+        .url()
+        .and_then(|url| url.strip_prefix("file://"))
+        .and_then(|path| {
+            fs::read_to_string(Path::new(&percent_decode(path)).with_extension("lrc")).ok()
+        })
+    {
+        let lyrics = Lyrics::from_lrc(&lrc);
+
+        // Without timestamps, it is taken as plain text.
+        return Ok(match lyrics.synced.is_empty() {
+            true => Lyrics {
+                plain: Some(lrc),
+                ..lyrics
+            },
+            false => lyrics,
+        });
+    }
+
+    let lyrics_dir = cache_dir.join("lyrics");
+
+    fs::create_dir_all(&lyrics_dir)
+        .map_err(|why| Error::Other(format!("Failed to create lyrics directory: {}", why)))?;
+
+    let key = stable_hash([
+        metadata.artists().join("\n"),
+        title.to_string(),
+        metadata.album().unwrap_or_default().to_string(),
+        metadata
+            .length()
+            .map_or(String::new(), |l| (l / 1_000_000).to_string()),
+    ]);
+    let cached = lyrics_dir.join(format!("{:016x}.json", key));
+
+    let entry = match fs::read_to_string(&cached)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+    {
+        Some(entry) => entry,
+        None => {
+            let results = search(metadata, title)
+                .map_err(|why| Error::Other(format!("Failed to look up lyrics: {}", why)))?;
+
+            let entry = closest(results, metadata.length_secs())
+                .ok_or_else(|| Error::Other(format!("No lyrics found for {}", title)))?;
+
+            write_cache(&cached, &entry)?;
+
+            entry
+        }
+    };
+
+    Ok(from_entry(&entry))
+}
+
+/// Picks the result whose duration is closest to the track's, as the same
+/// song is often there in several versions.
+fn closest(results: Vec<Value>, length: Option<f64>) -> Option<Value> {
+    let distance = |entry: &Value| match (entry["duration"].as_f64(), length) {
+        (Some(duration), Some(length)) => (duration - length).abs(),
+        _ => 0.0,
+    };
+
+    results
+        .into_iter()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+}
+
+/// Reads an LRCLIB record, preferring its synced lyrics.
+fn from_entry(entry: &Value) -> Lyrics {
+    let text = |key: &str| entry[key].as_str().filter(|s| !s.trim().is_empty());
+
+    let mut lyrics = match (text("syncedLyrics"), text("plainLyrics")) {
+        (Some(synced), _) => Lyrics::from_lrc(synced),
+        (None, plain) => Lyrics {
+            plain: plain.map(String::from),
+            ..Lyrics::default()
+        },
+    };
+
+    lyrics.instrumental = entry["instrumental"].as_bool().unwrap_or(false);
+
+    lyrics
+}
+
+#[cfg(feature = "http")]
+fn search(
+    metadata: &Metadata,
+    title: &str,
+) -> std::result::Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let mut request = ureq::get(SEARCH_URL)
+        .set("User-Agent", crate::USER_AGENT)
+        .timeout(REQUEST_TIMEOUT)
+        .query("track_name", title);
+
+    if let Some(artist) = metadata.artist() {
+        request = request.query("artist_name", &artist);
+    }
+
+    Ok(serde_json::from_str(&request.call()?.into_string()?)?)
+}
+
+#[cfg(not(feature = "http"))]
+fn search(
+    _metadata: &Metadata,
+    _title: &str,
+) -> std::result::Result<Vec<Value>, Box<dyn std::error::Error>> {
+    Err("multiplayerctl was built without the `http` feature".into())
+}
+
+/// Writes to a temporary file first, so readers never see a partial record.
+fn write_cache(path: &Path, entry: &Value) -> Result<()> {
+    let temporary = path.with_extension("json.part");
+
+    fs::write(&temporary, entry.to_string())
+        .and_then(|_| fs::rename(&temporary, path))
+        .map_err(|why| Error::Other(format!("Failed to write {}: {}", path.display(), why)))
+}
+
+/// Parses an LRC timestamp like `01:23.45` into microseconds.
+fn parse_timestamp(tag: &str) -> Option<i64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: u32 = minutes.trim().parse().ok()?;
+    let seconds: f64 = seconds.trim().parse().ok()?;
+
+    Some(((f64::from(minutes) * 60.0 + seconds) * 1_000_000.0).round() as i64)
+}
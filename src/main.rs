@@ -1,643 +1,3044 @@
-use once_cell::sync::Lazy;
-use std::env;
-use std::fs::{create_dir_all, File};
-use std::io::{BufRead, BufReader};
-use std::os::fd::AsRawFd;
-use std::process::{Command, Stdio};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::{
-    io::{Error, Read, Write},
-    path::PathBuf,
+mod bookmarks;
+mod completions;
+mod configure;
+mod daemon;
+mod doctor;
+mod events;
+mod fade;
+mod follow;
+mod headphones;
+mod hooks;
+mod i3blocks;
+mod inhibit;
+mod listen;
+#[cfg(feature = "history")]
+mod listens;
+mod lock;
+#[cfg(feature = "media-keys")]
+mod media_keys;
+#[cfg(feature = "serve")]
+mod metrics;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod notify;
+mod picker;
+mod polybar;
+mod proxy;
+mod reload;
+mod remote;
+mod schedule;
+#[cfg(feature = "scrobble")]
+mod scrobble;
+mod scroll;
+#[cfg(feature = "serve")]
+mod serve;
+mod service;
+mod shutdown;
+mod sleep;
+mod stream;
+mod suspend;
+mod systemd;
+mod template;
+#[cfg(feature = "tray")]
+mod tray;
+#[cfg(feature = "tui")]
+mod tui;
+mod wait;
+mod waybar;
+
+use clap::{ArgGroup, Parser, Subcommand};
+use clap_complete::Shell;
+use multiplayerctl::{
+    art, ipc, lyrics, mpd::Mpd, playerctl, pulse, session_key, snapcast::Snapcast, Backend, Config,
+    Error, Metadata, PlaybackStatus, Player, PlayerManager, Result,
 };
-use structopt::StructOpt;
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::io::{self, IsTerminal, Read, Write};
+use std::ops::ControlFlow;
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing_subscriber::EnvFilter;
 
-static CHANGE_SIGNAL_HANDLER: Lazy<Arc<AtomicBool>> =
-    Lazy::new(|| Arc::new(AtomicBool::new(false)));
+use lyrics::Lyrics;
+use schedule::Ramp;
+use template::render_styled;
 
-#[derive(Debug, StructOpt)]
-#[structopt(
+#[derive(Debug, Parser)]
+#[command(
     name = "multiplayerctl",
+    version,
     about = "Simplifies audio player control across multiple players via playerctl, allowing you to switch focus."
 )]
+struct Opt {
+    #[arg(
+        long = "backend",
+        value_parser = ["mpris", "playerctl"],
+        help = "The backend used to talk to players (default: mpris). Falls back to playerctl if the session bus is unavailable."
+    )]
+    backend: Option<String>,
+    #[arg(
+        long = "color",
+        value_name = "WHEN",
+        global = true,
+        value_parser = ["never", "auto", "always"],
+        default_value = "auto",
+        help = "Whether templates may color the output with tags such as `{bold}` or `{color:green}`: never, auto (when printing to a terminal and NO_COLOR is not set) or always."
+    )]
+    color: String,
+    #[arg(
+        long = "config",
+        help = "The config file to use instead of $XDG_CONFIG_HOME/multiplayerctl/config.toml."
+    )]
+    config: Option<PathBuf>,
+    #[arg(
+        long = "host",
+        value_name = "HOST",
+        help = "Run the command with the multiplayerctl on another machine, over SSH, e.g. `me@htpc`. An empty host runs it here despite `remote.host` in the config."
+    )]
+    host: Option<String>,
+    #[arg(
+        long = "ignore",
+        help = "A player to leave out of listing and switching, in addition to the config. Accepts glob patterns and may be repeated."
+    )]
+    ignore: Vec<String>,
+    #[arg(
+        long = "ignore-instance",
+        help = "Treat all instances of an application (e.g. browser windows) as one player, preferring the one that is playing."
+    )]
+    ignore_instance: bool,
+    #[arg(
+        long = "json",
+        global = true,
+        help = "Print query results as JSON (one object per line when following)."
+    )]
+    json: bool,
+    #[arg(
+        long = "once",
+        global = true,
+        help = "When following changes, as with --follow, `waybar` or `events`, wait for the next one, print it and exit."
+    )]
+    once: bool,
+    #[arg(
+        long = "debounce",
+        value_name = "MS",
+        global = true,
+        value_parser = parse_debounce,
+        help = "When following changes, wait until they have settled for this many milliseconds before printing, so that a burst of them prints once, e.g. `200` or `0.5s`."
+    )]
+    debounce: Option<Duration>,
+    #[arg(
+        long = "dedupe",
+        global = true,
+        help = "When following changes, leave out lines identical to the one printed before."
+    )]
+    dedupe: bool,
+    #[arg(
+        short = 'p',
+        long = "player",
+        global = true,
+        help = "The player to control instead of the current one. For `switch`, the player to switch to."
+    )]
+    player: Option<String>,
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Log the playerctl and D-Bus calls made and why players are picked to standard error; -vv for more. RUST_LOG takes precedence, e.g. RUST_LOG=multiplayerctl=debug,zbus=trace."
+    )]
+    verbose: u8,
+    #[command(subcommand)]
+    command: Args,
+}
+
+/// Widens a command from the current player to several players.
+#[derive(Debug, Clone, clap::Args)]
+struct Targets {
+    #[arg(short = 'a', long = "all", help = "Apply to every player.")]
+    all: bool,
+    #[arg(
+        short = 'o',
+        long = "others",
+        conflicts_with = "all",
+        help = "Apply to every player except the current one."
+    )]
+    others: bool,
+}
+
+impl Opt {
+    /// Moves `--player` into `switch`, where it names the player to switch to
+    /// rather than the player to control.
+    fn resolve_player(mut self) -> Self {
+        if let Args::Switch { player, .. } = &mut self.command {
+            *player = self.player.take();
+        }
+
+        self
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
 enum Args {
-    #[structopt(about = "Lists all available players.")]
-    List,
-    #[structopt(about = "Toggles play/pause for the current player.")]
+    #[command(about = "Lists all available players.")]
+    List {
+        #[arg(
+            short = 's',
+            long = "status",
+            help = "Show each player's status, volume and title, marking the current player with `*`."
+        )]
+        status: bool,
+        #[arg(
+            short = 'f',
+            long = "format",
+            help = "The format to print each player with, e.g. `{name}\\t{status}\\t{title}`. Available fields: those of templates (see the README) and current."
+        )]
+        format: Option<String>,
+        #[arg(
+            short = 'n',
+            long = "numbered",
+            conflicts_with = "format",
+            help = "Number the players from 1, as `switch N` counts them."
+        )]
+        numbered: bool,
+    },
+    #[command(about = "Toggles play/pause for the current player.")]
     Toggle,
-    #[structopt(about = "Plays the current player.")]
-    Play,
-    #[structopt(about = "Pauses the current player.")]
-    Pause,
-    #[structopt(about = "Switches the current player to the next available one.")]
+    #[command(about = "Plays the current player.")]
+    Play {
+        #[command(flatten)]
+        targets: Targets,
+        #[arg(
+            long = "fade",
+            value_name = "DURATION",
+            value_parser = parse_interval,
+            help = "Fade the volume in from nothing over this long, e.g. `3s`, then leave it where it was."
+        )]
+        fade: Option<Duration>,
+    },
+    #[command(about = "Pauses the current player.")]
+    Pause {
+        #[command(flatten)]
+        targets: Targets,
+        #[arg(
+            long = "fade",
+            value_name = "DURATION",
+            value_parser = parse_interval,
+            help = "Fade the volume out over this long, e.g. `3s`, before pausing, then put it back where it was."
+        )]
+        fade: Option<Duration>,
+    },
+    #[command(about = "Stops the current player.")]
+    Stop {
+        #[command(flatten)]
+        targets: Targets,
+    },
+    #[command(about = "Opens a URI in the current player.")]
+    Open {
+        #[arg(
+            value_name = "URI",
+            help = "The URI (file path, stream URL, ...) to open."
+        )]
+        uri: String,
+    },
+    #[command(about = "Brings the current player's window to the front.")]
+    Raise,
+    #[command(about = "Quits the current player application.")]
+    QuitPlayer,
+    #[command(
+        about = "Switches the current player to the next available one.",
+        group(ArgGroup::new("direction").args(["number", "next", "back", "playing", "interactive", "index"]))
+    )]
     Switch {
-        #[structopt(short = "p", long = "player", help = "The player to switch to.")]
+        // Taken from the global `--player`, see `Opt::resolve_player`.
+        #[arg(skip)]
         player: Option<String>,
-        #[structopt(
-            short = "n",
+        #[arg(
+            value_name = "N",
+            help = "Switch to the Nth player, counting from 1 in the order of `list --numbered`. The same as `--index N`."
+        )]
+        number: Option<usize>,
+        #[arg(
+            short = 'n',
             long = "next",
             help = "Whether to switch to the next player (default behaviour)."
         )]
         next: bool,
-        #[structopt(
-            short = "b",
+        #[arg(
+            short = 'b',
             long = "back",
             help = "Whether to switch to the previous player."
         )]
         back: bool,
+        #[arg(
+            long = "playing",
+            help = "Switch to a player that is currently playing, preferring one other than the current player."
+        )]
+        playing: bool,
+        #[arg(
+            short = 'i',
+            long = "interactive",
+            help = "Pick the player from a fuzzy finder (see `switch.picker` in the config)."
+        )]
+        interactive: bool,
+        #[arg(
+            long = "index",
+            value_name = "N",
+            help = "Switch to the Nth player, counting from 1 in the order of `list`."
+        )]
+        index: Option<usize>,
+        #[arg(
+            long = "local-only",
+            help = "Leave out players on other devices, such as phones connected through KDE Connect (see `switch.local_only` in the config)."
+        )]
+        local_only: bool,
     },
-    #[structopt(about = "Plays next track on the current player.")]
+    #[command(about = "Plays next track on the current player.")]
     Next,
-    #[structopt(about = "Plays previous track on the current player.")]
+    #[command(about = "Plays previous track on the current player.")]
     Previous,
-    #[structopt(about = "Prints or sets the volume of the current player.")]
+    #[command(about = "Mutes or unmutes the current player, restoring its previous volume.")]
+    Mute {
+        #[arg(
+            value_name = "STATE",
+            value_parser = ["on", "off", "toggle"],
+            ignore_case = true,
+            default_value = "toggle",
+            help = "Whether to mute, unmute, or toggle."
+        )]
+        state: String,
+    },
+    #[command(about = "Prints or sets the loop status of the current player.")]
+    Loop {
+        #[arg(
+            value_name = "STATUS",
+            value_parser = ["None", "Track", "Playlist", "cycle"],
+            ignore_case = true,
+            help = "The loop status to set, or `cycle` to advance None → Playlist → Track → None."
+        )]
+        status: Option<String>,
+    },
+    #[command(about = "Prints or sets the shuffle state of the current player.")]
+    Shuffle {
+        #[arg(
+            value_name = "STATE",
+            value_parser = ["on", "off", "toggle"],
+            ignore_case = true,
+            help = "Whether to turn shuffle on, off, or toggle it."
+        )]
+        state: Option<String>,
+    },
+    #[command(about = "Prints or sets the volume of the current player.")]
     Volume {
-        #[structopt(name = "VALUE", help = "The volume to set the current player to.")]
+        #[arg(
+            value_name = "VALUE",
+            allow_hyphen_values = true,
+            help = "The volume to set the current player to, e.g. `0.5` or `50%`. A leading `+` or `-` adjusts it relative to the current volume."
+        )]
         value: Option<String>,
-        #[structopt(
-            short = "f",
+        #[arg(
+            short = 'f',
             long = "format",
-            help = "The format to use when printing the volume."
+            help = "The template to print the volume with, e.g. `{volume_percent}%`. Templates in playerctl's syntax, such as `{{ volume }}`, are passed on to it."
         )]
         format: Option<String>,
+        #[arg(
+            short = 'F',
+            long = "follow",
+            help = "Whether to block and print the volume whenever it changes."
+        )]
+        follow: bool,
+    },
+    #[command(
+        about = "Lists the audio outputs, or moves the current player's audio to another one."
+    )]
+    Sink {
+        #[command(subcommand)]
+        command: SinkCommand,
+    },
+    #[command(
+        about = "Lists or edits the current player's queue, for players with an MPRIS track list."
+    )]
+    Queue {
+        #[command(subcommand)]
+        command: QueueCommand,
     },
-    #[structopt(about = "Prints or sets the position of the current player.")]
+    #[command(
+        about = "Pauses the players that are playing after a while, through the daemon. Without a duration, shows the timer.",
+        args_conflicts_with_subcommands = true
+    )]
+    Sleep {
+        #[arg(
+            value_name = "DURATION",
+            value_parser = parse_interval,
+            help = "How long until the players are paused, e.g. `30m` or `1h30m`. Replaces any timer already set."
+        )]
+        duration: Option<Duration>,
+        #[arg(
+            long = "fade",
+            value_name = "DURATION",
+            value_parser = parse_interval,
+            help = "Fade the volume out over this long, ending when the timer goes off."
+        )]
+        fade: Option<Duration>,
+        #[arg(long = "quit", help = "Quit the players instead of only pausing them.")]
+        quit: bool,
+        #[command(subcommand)]
+        command: Option<SleepCommand>,
+    },
+    #[command(
+        about = "Runs a command at a time of day through the daemon, e.g. `schedule 07:30 --player spotify play`. Without a time, lists what is scheduled.",
+        args_conflicts_with_subcommands = true
+    )]
+    Schedule {
+        #[arg(
+            value_name = "TIME",
+            value_parser = schedule::parse_time,
+            help = "When to run the command, such as `07:30`: today, or tomorrow if that has passed."
+        )]
+        time: Option<(u32, u32)>,
+        #[arg(
+            long = "volume-ramp",
+            value_name = "FROM..TO/DURATION",
+            value_parser = schedule::parse_ramp,
+            help = "Start the player the command acts on at one volume and raise it to another, e.g. `0.1..0.6/5m`."
+        )]
+        volume_ramp: Option<Ramp>,
+        #[arg(
+            value_name = "COMMAND",
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "The command to run, with its options. Defaults to `play`."
+        )]
+        command: Vec<String>,
+        #[command(subcommand)]
+        action: Option<ScheduleCommand>,
+    },
+    #[command(about = "Prints or sets the position of the current player.")]
     Position {
-        #[structopt(name = "VALUE", help = "The position to set the current player to.")]
+        #[arg(
+            value_name = "VALUE",
+            allow_hyphen_values = true,
+            help = "The position to set the current player to, e.g. `90`, `1m30s` or `37%` of the track length."
+        )]
         value: Option<String>,
-        #[structopt(
-            short = "f",
+        #[arg(
+            short = 'f',
             long = "format",
-            help = "The format to use when printing the position."
+            help = "The template to print the position with, e.g. `{position}/{length}`. Templates in playerctl's syntax, such as `{{ position }}`, are passed on to it."
         )]
         format: Option<String>,
+        #[arg(
+            short = 'F',
+            long = "follow",
+            help = "Whether to block and keep printing the position while the player is playing."
+        )]
+        follow: bool,
+        #[arg(
+            short = 'i',
+            long = "interval",
+            default_value = "1",
+            value_parser = parse_interval,
+            help = "How often to print the position with --follow, e.g. `1`, `0.5` or `500ms`."
+        )]
+        interval: Duration,
+        #[arg(
+            long = "raw",
+            conflicts_with_all = ["value", "format", "percent"],
+            help = "Print it in seconds, e.g. `83.500000`, instead of like `1:23`."
+        )]
+        raw: bool,
+        #[arg(
+            long = "percent",
+            conflicts_with_all = ["value", "format"],
+            help = "Print how far the track has played in percent of its length instead, e.g. `37.21`. To seek to a percentage, give one as the value, e.g. `37%`."
+        )]
+        percent: bool,
+    },
+    #[command(about = "Seeks the current player, e.g. `+10s`, `-1m30s` or `50%`.")]
+    Seek {
+        #[arg(
+            value_name = "OFFSET",
+            allow_hyphen_values = true,
+            help = "A duration such as `90`, `1m30s` or a percentage of the track length. With a leading `+` or `-` it is relative to the current position."
+        )]
+        offset: String,
     },
-    #[structopt(about = "Prints the status of the current player.")]
+    #[command(
+        about = "Seeks the current player to where its track was left off, as remembered by the daemon (see `[bookmarks]` in the config)."
+    )]
+    Resume,
+    #[command(about = "Prints the status of the current player.")]
     Status {
-        #[structopt(
-            short = "f",
+        #[arg(
+            short = 'f',
             long = "format",
-            help = "The format to use when printing the status."
+            help = "The template to print the status with, e.g. `{status_icon} {status}`. Templates in playerctl's syntax, such as `{{ status }}`, are passed on to it."
         )]
         format: Option<String>,
-        #[structopt(
-            short = "F",
+        #[arg(
+            short = 'F',
             long = "follow",
             help = "Whether to follow the status of the player."
         )]
         follow: bool,
     },
-    #[structopt(about = "Prints the metadata of the current player.")]
+    #[command(about = "Prints the metadata of the current player.")]
     Metadata {
-        #[structopt(
-            name = "KEY",
+        #[arg(
+            value_name = "KEY",
             help = "If the key is set, only the value with the key is printed."
         )]
         key: Option<String>,
-        #[structopt(
-            short = "f",
+        #[arg(
+            short = 'f',
             long = "format",
-            help = "The format to use when printing the metadata."
+            help = "The template to print the metadata with, e.g. `{?artist}{artist} - {/}{title}`. Templates in playerctl's syntax, such as `{{ metadata }}`, are passed on to it."
         )]
         format: Option<String>,
-        #[structopt(
-            short = "F",
+        #[arg(
+            short = 'F',
             long = "follow",
             help = "Whether to follow the status of the player."
         )]
         follow: bool,
     },
-    #[structopt(about = "Prints the current player.")]
+    #[command(about = "Prints the title of the current track.")]
+    Title,
+    #[command(about = "Prints the artists of the current track.")]
+    Artist,
+    #[command(about = "Prints the album of the current track.")]
+    Album,
+    #[command(about = "Prints the length of the current track, e.g. `3:45`.")]
+    Length {
+        #[arg(long = "raw", help = "Print it in seconds instead, e.g. `225.000000`.")]
+        raw: bool,
+    },
+    #[command(about = "Caches the cover art of the current track and prints its location.")]
+    Art {
+        #[arg(
+            short = 's',
+            long = "size",
+            help = "Scale the image down to fit a square of this many pixels."
+        )]
+        size: Option<u32>,
+        #[arg(long = "path", help = "Print a plain path instead of a file:// URI.")]
+        path: bool,
+        #[arg(
+            long = "colors",
+            conflicts_with_all = ["size", "path"],
+            help = "Print the dominant and accent colors of the cover as hex values instead, for theming."
+        )]
+        colors: bool,
+    },
+    #[command(
+        about = "Prints the lyrics of the current track, from an .lrc file next to it or from lrclib.net."
+    )]
+    Lyrics {
+        #[arg(
+            short = 'F',
+            long = "follow",
+            help = "Print the line being sung whenever it changes, for synced lyrics."
+        )]
+        follow: bool,
+    },
+    #[command(about = "Prints the current player.")]
     Player,
+    #[command(about = "Prints the status, track and position of the current player in one line.")]
+    NowPlaying {
+        #[arg(
+            short = 'f',
+            long = "format",
+            help = "The template to print, e.g. `{status_icon} {artist} - {title} ({position}/{length})`."
+        )]
+        format: Option<String>,
+        #[arg(
+            short = 'F',
+            long = "follow",
+            help = "Print again whenever anything changes."
+        )]
+        follow: bool,
+        #[arg(
+            long = "tick",
+            value_name = "INTERVAL",
+            value_parser = parse_tick,
+            requires = "follow",
+            help = "With --follow, also print this often while the player is playing, e.g. `1`, `0.5` or `500ms`, for the elapsed time. Defaults to every second when the template shows the position."
+        )]
+        tick: Option<Duration>,
+        #[arg(
+            long = "scroll",
+            value_name = "WIDTH[,SPEED]",
+            value_parser = scroll::parse,
+            requires = "follow",
+            help = "With --follow, show lines wider than WIDTH columns scrolling through a window that wide, moving SPEED characters per second (2 by default), for narrow bar modules."
+        )]
+        scroll: Option<scroll::Scroll>,
+        #[arg(
+            long = "scroll-separator",
+            value_name = "TEXT",
+            default_value = " | ",
+            requires = "scroll",
+            help = "What is shown between the end of a scrolling line and its start."
+        )]
+        scroll_separator: String,
+    },
+    #[command(about = "Prints how far the current track has played as a bar, like `▰▰▰▱▱▱▱▱`.")]
+    Progress {
+        #[arg(
+            short = 'w',
+            long = "width",
+            default_value_t = 20,
+            help = "How many characters wide the bar is."
+        )]
+        width: usize,
+        #[arg(
+            short = 'F',
+            long = "follow",
+            help = "Print the bar again whenever it changes, checking every second while playing."
+        )]
+        follow: bool,
+    },
+    #[command(about = "Prints Waybar custom module JSON for the current player as it changes.")]
+    Waybar,
+    #[command(
+        about = "Prints a line for a polybar module in tail mode as the current player changes, with click actions."
+    )]
+    Polybar,
+    #[command(
+        about = "Runs the action for the button clicked on an i3blocks or i3status-rust block, then prints the block's text."
+    )]
+    I3blocks {
+        #[arg(
+            long = "click",
+            value_name = "BUTTON",
+            value_parser = clap::value_parser!(u8).range(1..=5),
+            help = "The button clicked, from 1 (left) to 5 (scrolling down). Defaults to `$BLOCK_BUTTON`, as set by i3blocks."
+        )]
+        click: Option<u8>,
+    },
+    #[command(
+        about = "Prints a compact JSON object with the current player's status, track, position, volume and cover art whenever they change, e.g. for eww."
+    )]
+    Listen,
+    #[command(
+        about = "Sends a desktop notification whenever the current player starts a new track."
+    )]
+    Notify,
+    #[command(
+        about = "Runs in the background, serving other invocations over a Unix socket for lower latency."
+    )]
+    Daemon,
+    #[command(about = "Lists the most recently played tracks from the listening history.")]
+    History {
+        #[arg(
+            short = 'n',
+            long = "limit",
+            default_value_t = 20,
+            help = "How many tracks to list."
+        )]
+        limit: usize,
+    },
+    #[command(
+        about = "Summarizes the listening history: top artists and tracks, listening time and players."
+    )]
+    Stats {
+        #[arg(
+            long = "period",
+            value_parser = ["day", "week", "month", "year", "all"],
+            ignore_case = true,
+            default_value = "week",
+            help = "How far back to look."
+        )]
+        period: String,
+        #[arg(
+            short = 'n',
+            long = "limit",
+            default_value_t = 10,
+            help = "How many artists and tracks to list."
+        )]
+        limit: usize,
+    },
+    #[command(
+        about = "Serves a REST API and a WebSocket pushing state changes, for control over the network."
+    )]
+    Serve {
+        #[arg(
+            long = "bind",
+            value_name = "ADDRESS",
+            help = "The address to listen on (default: `serve.bind` from the config, or 127.0.0.1:8080)."
+        )]
+        bind: Option<String>,
+        #[arg(
+            long = "token",
+            help = "A bearer token every request has to carry (default: `serve.token` from the config)."
+        )]
+        token: Option<String>,
+    },
+    #[command(
+        about = "Shows the players in an interactive terminal interface, with keys to switch between and control them."
+    )]
+    Tui,
+    #[command(
+        about = "Shows a system tray icon for the current player, with a menu to control it and switch players."
+    )]
+    Tray,
+    #[command(about = "Manages scrobbling to Last.fm from the daemon.")]
+    Scrobble {
+        #[command(subcommand)]
+        command: ScrobbleCommand,
+    },
+    #[command(
+        about = "Prints a line for every player event: appearing, vanishing, switches, status, track, seek and volume changes."
+    )]
+    Events,
+    #[command(
+        about = "Waits until the current player (or --player) is in the given state, then exits. Without conditions, waits for the player to appear."
+    )]
+    WaitFor {
+        #[arg(
+            short = 's',
+            long = "status",
+            value_parser = parse_status,
+            help = "Wait for this playback status: Playing, Paused or Stopped."
+        )]
+        status: Option<PlaybackStatus>,
+        #[arg(
+            short = 'm',
+            long = "metadata",
+            value_name = "KEY=VALUE",
+            value_parser = parse_condition,
+            help = "Wait for a metadata field to have a value, e.g. `xesam:title=Intro`. May be repeated."
+        )]
+        metadata: Vec<(String, String)>,
+        #[arg(
+            short = 't',
+            long = "timeout",
+            value_parser = parse_interval,
+            help = "Give up after this long (e.g. `30s` or `2m`), exiting with code 9."
+        )]
+        timeout: Option<Duration>,
+    },
+    #[command(
+        about = "Checks the session bus, playerctl, the state directory, the daemon and the players, and suggests fixes for what is wrong."
+    )]
+    Doctor,
+    #[command(about = "Runs the daemon as a systemd user service, started at login.")]
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommand,
+    },
+    #[command(about = "Prints, checks or edits the config file.")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    #[command(about = "Prints a shell completion script.")]
+    Completions {
+        #[arg(
+            value_name = "SHELL",
+            ignore_case = true,
+            help = "The shell to generate completions for."
+        )]
+        shell: Shell,
+    },
 }
 
-fn main() -> Result<(), Error> {
-    let cache_path = get_cache_path()?;
+#[derive(Debug, Clone, Subcommand)]
+enum ServiceCommand {
+    #[command(
+        about = "Writes systemd user units for the daemon and its socket, then enables and starts them. Commands sent before the daemon is up start it through the socket."
+    )]
+    Install {
+        #[arg(
+            long = "notify",
+            help = "Also install a unit for `multiplayerctl notify`, to announce new tracks."
+        )]
+        notify: bool,
+    },
+    #[command(about = "Stops, disables and removes the units written by `install`.")]
+    Uninstall,
+    #[command(about = "Starts the daemon through systemd.")]
+    Start,
+    #[command(about = "Stops the daemon, and its socket, until the next start or login.")]
+    Stop,
+    #[command(
+        about = "Shows whether the units are installed, enabled and active, and whether the daemon answers. Fails if it does not."
+    )]
+    Status,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ConfigCommand {
+    #[command(about = "Prints the path of the config file in use, whether or not it exists.")]
+    Path,
+    #[command(
+        about = "Prints the settings in effect, with the defaults filled in and MULTIPLAYERCTL_* variables, --ignore, --ignore-instance and --backend applied. Passwords and tokens are hidden."
+    )]
+    Show,
+    #[command(
+        about = "Checks the config file for syntax errors, unknown keys and settings that cannot work, such as malformed glob patterns."
+    )]
+    Validate,
+    #[command(
+        about = "Opens the config file in $VISUAL or $EDITOR, creating it from a commented template if missing, and validates it afterwards."
+    )]
+    Edit,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ScrobbleCommand {
+    #[command(
+        about = "Authorizes multiplayerctl to scrobble to your Last.fm account and saves the session for the daemon."
+    )]
+    Auth,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum SinkCommand {
+    #[command(
+        about = "Lists the audio outputs, marking the ones the current player plays on with `*`."
+    )]
+    List,
+    #[command(about = "Moves the current player's audio to another output.")]
+    Set {
+        #[arg(
+            value_name = "SINK",
+            help = "The output to move to: its name or index as in `sink list`, or a part of its name or description that only it has, e.g. `headphones`."
+        )]
+        sink: String,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum QueueCommand {
+    #[command(about = "Lists the tracks in the queue, marking the current one with `*`.")]
+    List,
+    #[command(about = "Adds a track to the end of the queue.")]
+    Add {
+        #[arg(
+            value_name = "URI",
+            help = "The track to add, e.g. `file:///music/song.flac`."
+        )]
+        uri: String,
+        #[arg(long = "next", help = "Add it after the current track instead.")]
+        next: bool,
+    },
+    #[command(about = "Removes a track from the queue.")]
+    Remove {
+        #[arg(
+            value_name = "INDEX",
+            help = "The track to remove, counting from 1 in the order of `queue list`."
+        )]
+        index: usize,
+    },
+    #[command(about = "Skips to a track in the queue.")]
+    Goto {
+        #[arg(
+            value_name = "INDEX",
+            help = "The track to skip to, counting from 1 in the order of `queue list`."
+        )]
+        index: usize,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum SleepCommand {
+    #[command(about = "Shows how long until the sleep timer goes off.")]
+    Status,
+    #[command(about = "Cancels the sleep timer.")]
+    Cancel,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum ScheduleCommand {
+    #[command(about = "Lists what is scheduled.")]
+    List,
+    #[command(about = "Cancels a scheduled command, or all of them.")]
+    Cancel {
+        #[arg(value_name = "ID", help = "The id shown by `schedule list`.")]
+        id: Option<u32>,
+    },
+}
+
+fn main() {
+    if let Err(why) = run() {
+        let message = why.to_string();
+
+        // Commands run elsewhere have already printed their errors.
+        if !message.is_empty() {
+            eprintln!("{}", message);
+        }
+
+        process::exit(why.exit_code());
+    }
+}
+
+/// Logs to standard error as `RUST_LOG` says, or else as verbose as
+/// `verbose`. Returns whether anything is logged.
+fn init_logging(verbose: u8) -> bool {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => match verbose {
+            0 => return false,
+            1 => EnvFilter::new("multiplayerctl=debug"),
+            _ => EnvFilter::new("debug,multiplayerctl=trace"),
+        },
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
+
+    true
+}
+
+fn run() -> Result<()> {
+    let mut opt = Opt::parse().resolve_player();
+
+    let logging = init_logging(opt.verbose);
+
+    if let Args::Completions { shell } = opt.command {
+        return completions::generate(shell);
+    }
+
+    // Before anything can fail, so that it can say why.
+    if let Args::Doctor = opt.command {
+        return doctor::run(&opt, Config::load(opt.config.as_deref()));
+    }
+
+    // Also before loading the config, which may be what is to be fixed.
+    if let Args::Config { command } = &opt.command {
+        return configure::run(&opt, command);
+    }
+
+    if let Args::Service { command } = &opt.command {
+        return systemd::run(&opt, command);
+    }
+
+    let mut config = Config::load(opt.config.as_deref())?;
+
+    let host = opt.host.as_ref().or(config.remote.host.as_ref());
+
+    if let Some(host) = host.filter(|h| !h.is_empty()) {
+        let args = remote::without_host(std::env::args().skip(1));
+
+        return remote::run(host, &config.remote, &args);
+    }
+
+    if opt.once && (!follows(&opt.command) || matches!(opt.command, Args::Notify)) {
+        return Err(Error::InvalidArgument(String::from(
+            "--once only applies to commands that print changes, such as `status --follow`, `waybar` or `events`.",
+        )));
+    }
+
+    if (opt.debounce.is_some() || opt.dedupe)
+        && (!follows(&opt.command) || matches!(opt.command, Args::Notify | Args::Events))
+    {
+        return Err(Error::InvalidArgument(String::from(
+            "--debounce and --dedupe only apply to --follow and `waybar`.",
+        )));
+    }
+
+    if opt.json {
+        if has_format(&opt.command) {
+            return Err(Error::InvalidArgument(String::from(
+                "--json cannot be combined with --format.",
+            )));
+        }
+    } else {
+        apply_default_formats(&mut opt.command, &config);
+    }
+
+    apply_overrides(&opt, &mut config);
+
+    // The daemon has its own settings, so only plain invocations are forwarded to it.
+    let has_overrides = opt.backend.is_some()
+        || opt.config.is_some()
+        || !opt.ignore.is_empty()
+        || opt.ignore_instance
+        || multiplayerctl::config::has_env_overrides();
+
+    template::set_color(colors(&opt));
+
+    // The daemon would log elsewhere, if at all.
+    if is_oneshot(&opt.command) && !has_overrides && !logging {
+        let args = with_color(std::env::args().skip(1), colors(&opt));
+
+        if let Ok(response) = ipc::send(&args) {
+            print!("{}", response?);
+
+            return Ok(());
+        }
+
+        if let Args::Sleep {
+            duration: Some(_), ..
+        }
+        | Args::Schedule { time: Some(_), .. } = opt.command
+        {
+            eprintln!("The daemon is not running, and nothing happens until it is.");
+        }
+    }
+
+    // Before the backend starts threads of its own, which must leave the
+    // signals to the one waiting for them.
+    if follows(&opt.command) {
+        shutdown::install()?;
+    }
+
+    let manager = PlayerManager::new(backend(&opt, &config))?.with_config(config);
+
+    let manager = match &opt.player {
+        Some(p) => manager.targeting(p),
+        None => manager,
+    };
+
+    let follow_options = follow::Options {
+        once: opt.once,
+        debounce: opt.debounce,
+        dedupe: opt.dedupe,
+    };
+
+    if let Args::Daemon = opt.command {
+        return daemon::run(manager.in_memory(), &opt)
+            .map_err(|why| Error::Other(format!("Failed to run daemon: {}", why)));
+    }
+
+    if let Args::History { limit } = opt.command {
+        return history(&manager, limit, opt.json);
+    }
+
+    if let Args::Stats { period, limit } = opt.command {
+        return stats(&manager, &period, limit, opt.json);
+    }
+
+    if let Args::Scrobble { command } = opt.command {
+        return scrobble(&manager, command);
+    }
+
+    if let Args::Serve { bind, token } = opt.command {
+        return serve(manager, bind, token);
+    }
+
+    if let Args::Tui = opt.command {
+        return tui(manager, !has_overrides);
+    }
+
+    if let Args::Tray = opt.command {
+        return tray(manager, !has_overrides);
+    }
+
+    if let Args::I3blocks { click } = opt.command {
+        return i3blocks::run(&manager, click, !has_overrides);
+    }
+
+    // Both are about players yet to come, so there need not be any now.
+    if let Args::Events = opt.command {
+        return events::run(manager, follow_options, opt.json);
+    }
+
+    if let Args::WaitFor {
+        status,
+        metadata,
+        timeout,
+    } = opt.command
+    {
+        return wait::run(manager, status, metadata, timeout);
+    }
+
+    if needs_player(&opt.command) {
+        manager.init()?;
+    }
+
+    if is_oneshot(&opt.command) {
+        print!("{}", execute(&manager, opt.command, opt.json)?);
+
+        return Ok(());
+    }
+
+    match opt.command {
+        Args::Volume {
+            value: None,
+            format: Some(format),
+            follow,
+        }
+        | Args::Status {
+            format: Some(format),
+            follow,
+        }
+        | Args::Metadata {
+            key: None,
+            format: Some(format),
+            follow,
+        } if !template::is_playerctl(&format) => {
+            print_template(manager, &format, follow.then_some(follow_options))
+        }
+        Args::Position {
+            value: None,
+            format: Some(format),
+            follow: false,
+            ..
+        } if !template::is_playerctl(&format) => print_template(manager, &format, None),
+        Args::Volume {
+            value,
+            format,
+            follow,
+        } => volume(
+            &manager,
+            &value,
+            &format,
+            follow.then_some(follow_options),
+            opt.json,
+        ),
+        Args::Position {
+            value,
+            format,
+            follow: false,
+            ..
+        } => position(&manager, &value, &format),
+        Args::Position {
+            format: Some(_), ..
+        } => Err(Error::InvalidArgument(String::from(
+            "--format cannot be combined with position --follow.",
+        ))),
+        Args::Position {
+            interval,
+            percent,
+            raw,
+            ..
+        } => follow::run(
+            manager,
+            follow_options,
+            |manager| playing(manager).then_some(interval),
+            |manager| current_position(manager, percent, raw, opt.json),
+        ),
+        Args::Status { format, follow } => status(
+            &manager,
+            &format,
+            follow.then_some(follow_options),
+            opt.json,
+        ),
+        Args::Metadata {
+            key,
+            format,
+            follow,
+        } => metadata(
+            &manager,
+            &key,
+            &format,
+            follow.then_some(follow_options),
+            opt.json,
+        ),
+        Args::Waybar => waybar::run(manager, follow_options),
+        Args::Polybar => polybar::run(manager, follow_options),
+        Args::Listen => listen::run(manager, follow_options),
+        Args::Notify => notify::run(manager),
+        Args::NowPlaying {
+            format,
+            tick,
+            scroll,
+            scroll_separator,
+            ..
+        } => {
+            // Unless asked to, only tick when the position is shown, as it is
+            // the only field that changes without the player announcing it.
+            let tick = tick.or(
+                shows_position(format.as_deref().unwrap_or(NOW_PLAYING_FORMAT))
+                    .then_some(Duration::from_secs(1)),
+            );
+
+            // Escape codes would be scrolled through like any other text.
+            if scroll.is_some() {
+                template::set_color(false);
+            }
+
+            // The line last rendered, and since when its track has been on,
+            // so that scrolling starts over with a new track but not every
+            // time the position changes.
+            let shown = RefCell::new((String::new(), None, Instant::now()));
+
+            follow::run(
+                manager,
+                follow_options,
+                |manager| {
+                    // Paused, the position stands still, and so does the
+                    // ticker. Scrolling goes on regardless.
+                    let tick = tick.filter(|_| playing(manager));
+
+                    let shown = shown.borrow();
+
+                    match scroll.filter(|s| s.scrolls(&shown.0)) {
+                        Some(scroll) => {
+                            let step = scroll.next_step(shown.2.elapsed());
+
+                            Some(tick.map_or(step, |t| t.min(step)))
+                        }
+                        None => tick,
+                    }
+                },
+                |manager| {
+                    let line = now_playing(manager, &format);
+
+                    let scroll = match scroll {
+                        Some(s) => s,
+                        None => return line,
+                    };
+
+                    let track = manager
+                        .current()
+                        .ok()
+                        .map(|p| (p.name().to_string(), p.metadata().unwrap_or_default()));
+
+                    let mut shown = shown.borrow_mut();
+
+                    if shown.1 != track {
+                        shown.1 = track;
+                        shown.2 = Instant::now();
+                    }
+
+                    shown.0 = line;
+
+                    scroll.window(&shown.0, &scroll_separator, shown.2.elapsed())
+                },
+            )
+        }
+        Args::Progress { width, .. } => {
+            print_template(manager, &progress_format(width), Some(follow_options))
+        }
+        Args::Lyrics { .. } => follow_lyrics(manager, follow_options, opt.json),
+        Args::Play {
+            targets,
+            fade: Some(duration),
+        } => for_targets(&manager, &targets, |p| fade::play(p, duration)).map(|_| ()),
+        Args::Pause {
+            targets,
+            fade: Some(duration),
+        } => for_targets(&manager, &targets, |p| fade::pause(p, duration)).map(|_| ()),
+        Args::Switch { local_only, .. } => {
+            let mut players = manager.players()?;
+
+            if local_only || manager.config().switch.local_only {
+                players.retain(|p| !manager.is_remote(p));
+            }
+
+            let chosen = picker::pick(&players, &manager.config().switch.picker)?;
+
+            manager.switch_to(&chosen)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// The backend named by `--backend` or the config, with the other sources
+/// of players in the config.
+fn backend(opt: &Opt, config: &Config) -> Backend {
+    let backend = match opt.backend.as_ref().or(config.backend.as_ref()) {
+        Some(b) => Backend::new(b),
+        None => Backend::new("mpris"),
+    }
+    .with_timeout(config.playerctl.limit());
+
+    let backend = match &config.mpd {
+        Some(mpd) => backend.with_source(Arc::new(Mpd::new(mpd.clone()))),
+        None => backend,
+    };
+
+    match &config.snapcast {
+        Some(snapcast) => backend.with_source(Arc::new(Snapcast::new(snapcast.clone()))),
+        None => backend,
+    }
+}
+
+/// Applies the options that add to the config, which take precedence over it.
+fn apply_overrides(opt: &Opt, config: &mut Config) {
+    config.ignore.extend(opt.ignore.iter().cloned());
+    config.group_instances |= opt.ignore_instance;
+}
+
+/// Fills in the format strings from the config for queries run without `--format`.
+fn apply_default_formats(command: &mut Args, config: &Config) {
+    let (format, default) = match command {
+        Args::Volume {
+            value: None,
+            format,
+            ..
+        } => (format, &config.formats.volume),
+        // The configured format only stands in for the plain position.
+        Args::Position {
+            value: None,
+            format,
+            follow: false,
+            percent: false,
+            raw: false,
+            ..
+        } => (format, &config.formats.position),
+        Args::Status { format, .. } => (format, &config.formats.status),
+        Args::Metadata { format, .. } => (format, &config.formats.metadata),
+        Args::NowPlaying { format, .. } => (format, &config.formats.now_playing),
+        _ => return,
+    };
+
+    if format.is_none() {
+        *format = default.clone();
+    }
+}
+
+fn has_format(command: &Args) -> bool {
+    match command {
+        Args::List { format, .. }
+        | Args::Volume { format, .. }
+        | Args::Position { format, .. }
+        | Args::Status { format, .. }
+        | Args::Metadata { format, .. } => format.is_some(),
+        _ => false,
+    }
+}
+
+/// A file in the state directory kept per session, like the current player,
+/// unless `shared_state` is set.
+fn session_file(manager: &PlayerManager, name: &str) -> PathBuf {
+    match session_key() {
+        Some(key) if !manager.config().shared_state => {
+            manager.state_path().join(format!("{}-{}", name, key))
+        }
+        _ => manager.state_path().join(name),
+    }
+}
+
+/// Whether `--color` has templates color the output.
+fn colors(opt: &Opt) -> bool {
+    match opt.color.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => {
+            io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        }
+    }
+}
+
+/// The command line with `--color` replaced by what it comes to here, for
+/// the daemon, which cannot tell where the output goes.
+fn with_color(mut args: impl Iterator<Item = String>, color: bool) -> Vec<String> {
+    let mut rest = vec![format!(
+        "--color={}",
+        match color {
+            true => "always",
+            false => "never",
+        }
+    )];
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            rest.push(arg);
+            rest.extend(args.by_ref());
+        } else if arg == "--color" {
+            args.next();
+        } else if !arg.starts_with("--color=") {
+            rest.push(arg);
+        }
+    }
+
+    rest
+}
+
+/// Whether a command acts on players, so that there must be a current one.
+fn needs_player(command: &Args) -> bool {
+    !matches!(command, Args::Sleep { .. } | Args::Schedule { .. })
+}
+
+/// Whether a command keeps printing or acting on changes until it is
+/// stopped, like `status --follow` or `waybar`.
+fn follows(command: &Args) -> bool {
+    match command {
+        Args::Volume { follow, .. }
+        | Args::Position { follow, .. }
+        | Args::Status { follow, .. }
+        | Args::Metadata { follow, .. }
+        | Args::NowPlaying { follow, .. }
+        | Args::Progress { follow, .. }
+        | Args::Lyrics { follow } => *follow,
+        Args::Waybar | Args::Polybar | Args::Listen | Args::Notify | Args::Events => true,
+        _ => false,
+    }
+}
+
+/// Whether a command produces all of its output at once without interaction.
+/// Those can be served by the daemon; formatting and following are left to
+/// the command itself.
+fn is_oneshot(command: &Args) -> bool {
+    match command {
+        Args::Volume {
+            value,
+            format,
+            follow,
+        } => value.is_some() || (format.is_none() && !follow),
+        Args::Position {
+            value,
+            format,
+            follow,
+            ..
+        } => value.is_some() || (format.is_none() && !follow),
+        Args::Status { format, follow } | Args::Metadata { format, follow, .. } => {
+            format.is_none() && !follow
+        }
+        Args::Switch { interactive, .. } => !interactive,
+        Args::NowPlaying { follow, .. } => !follow,
+        Args::Progress { follow, .. } => !follow,
+        Args::Lyrics { follow } => !follow,
+        // Fading takes seconds, better spent by the CLI than by the daemon.
+        Args::Play { fade, .. } | Args::Pause { fade, .. } => fade.is_none(),
+        Args::Daemon
+        | Args::Waybar
+        | Args::Polybar
+        | Args::I3blocks { .. }
+        | Args::Listen
+        | Args::Notify
+        | Args::Events
+        | Args::History { .. }
+        | Args::Stats { .. }
+        | Args::Scrobble { .. }
+        | Args::Serve { .. }
+        | Args::Tui
+        | Args::Tray
+        | Args::WaitFor { .. }
+        | Args::Doctor
+        | Args::Config { .. }
+        | Args::Service { .. }
+        | Args::Completions { .. } => false,
+        _ => true,
+    }
+}
+
+/// Runs a one-shot command and returns what it would print.
+///
+/// If the current player turns out to have exited, a new one is selected and
+/// the command is retried on it. So is the next player if the current one
+/// stalls and `playerctl.skip_stalled` is set.
+fn execute(manager: &PlayerManager, command: Args, json: bool) -> Result<String> {
+    match execute_once(manager, command.clone(), json) {
+        Err(Error::UnknownPlayer(player)) if manager.current_name().as_ref() == Ok(&player) => {
+            manager.init()?;
+
+            execute_once(manager, command, json)
+        }
+        Err(why @ Error::Stalled { .. }) if skips_stalled(manager, &why) => {
+            eprintln!("{}; switching to the next player.", why);
+
+            manager.switch_next()?;
+
+            execute_once(manager, command, json)
+        }
+        result => result,
+    }
+}
+
+/// Whether `playerctl.skip_stalled` moves on from the player that stalled,
+/// which has to be the current one rather than one asked for by name.
+fn skips_stalled(manager: &PlayerManager, why: &Error) -> bool {
+    match why {
+        Error::Stalled { player, .. } => {
+            manager.config().playerctl.skip_stalled
+                && manager.target().is_none()
+                && manager.current_name().as_ref() == Ok(player)
+        }
+        _ => false,
+    }
+}
+
+fn execute_once(manager: &PlayerManager, command: Args, json: bool) -> Result<String> {
+    match command {
+        Args::List {
+            status,
+            format,
+            numbered,
+        } => list_players(manager, status, &format, numbered, json),
+        Args::Toggle => manager.current()?.toggle().map(|_| String::new()),
+        Args::Play { targets, .. } => for_targets(manager, &targets, |p| p.play()),
+        Args::Pause { targets, .. } => for_targets(manager, &targets, |p| p.pause()),
+        Args::Stop { targets } => for_targets(manager, &targets, |p| p.stop()),
+        Args::Open { uri } => manager.current()?.open(&uri).map(|_| String::new()),
+        Args::Raise => manager.current()?.raise().map(|_| String::new()),
+        Args::QuitPlayer => manager.current()?.quit().map(|_| String::new()),
+        Args::Switch {
+            player,
+            number,
+            back,
+            playing,
+            index,
+            local_only,
+            ..
+        } => {
+            let local = local_only.then(|| manager.local_only());
+
+            switch(
+                local.as_ref().unwrap_or(manager),
+                player,
+                back,
+                playing,
+                index.or(number),
+            )
+            .map(|_| String::new())
+        }
+        Args::Next => manager.current()?.next().map(|_| String::new()),
+        Args::Previous => manager.current()?.previous().map(|_| String::new()),
+        Args::Mute { state } => mute(manager, &state).map(|_| String::new()),
+        Args::Loop { status } => loop_status(manager, &status),
+        Args::Shuffle { state } => shuffle(manager, &state),
+        Args::Volume { value, .. } => get_or_set_volume(manager, &value, json),
+        Args::Sink { command } => sink(manager, command, json),
+        Args::Queue { command } => queue(manager, command, json),
+        Args::Sleep {
+            duration,
+            fade,
+            quit,
+            command,
+        } => sleep::command(manager, duration, fade, quit, command, json),
+        Args::Schedule {
+            time,
+            volume_ramp,
+            command,
+            action,
+        } => schedule::command(manager, time, volume_ramp, command, action, json),
+        Args::Position {
+            value,
+            percent,
+            raw,
+            ..
+        } => get_or_set_position(manager, &value, percent, raw, json),
+        Args::Seek { offset } => seek(manager, &offset).map(|_| String::new()),
+        Args::Resume => bookmarks::resume(manager),
+        Args::Status { .. } => get_status(manager, json),
+        Args::Metadata { key, .. } => get_metadata(manager, &key, json),
+        Args::Title => get_metadata_field(manager, "title", false, json),
+        Args::Artist => get_metadata_field(manager, "artist", false, json),
+        Args::Album => get_metadata_field(manager, "album", false, json),
+        Args::Length { raw } => get_metadata_field(manager, "length", raw, json),
+        Args::Art { size, path, colors } => match colors {
+            true => cover_colors(manager, json),
+            false => cover_art(manager, size, path, json),
+        },
+        Args::Lyrics { .. } => print_lyrics(manager, json),
+        Args::NowPlaying { format, .. } => Ok(format!("{}\n", now_playing(manager, &format))),
+        Args::Progress { width, .. } => Ok(format!(
+            "{}\n",
+            render_current(manager, &progress_format(width))
+        )),
+        Args::Player => {
+            let player = manager.current_name()?;
+            let player_name = manager.display_name(&player);
+
+            match json {
+                true => Ok(json_line(
+                    json!({ "player": player, "player_name": player_name }),
+                )),
+                false => Ok(player_name),
+            }
+        }
+        Args::Daemon
+        | Args::Waybar
+        | Args::Polybar
+        | Args::I3blocks { .. }
+        | Args::Listen
+        | Args::Notify
+        | Args::Events
+        | Args::History { .. }
+        | Args::Stats { .. }
+        | Args::Scrobble { .. }
+        | Args::Serve { .. }
+        | Args::Tui
+        | Args::Tray
+        | Args::WaitFor { .. }
+        | Args::Doctor
+        | Args::Config { .. }
+        | Args::Service { .. }
+        | Args::Completions { .. } => Err(Error::InvalidArgument(String::from(
+            "This command cannot be run from here.",
+        ))),
+    }
+}
+
+fn list_players(
+    manager: &PlayerManager,
+    status: bool,
+    format: &Option<String>,
+    numbered: bool,
+    json: bool,
+) -> Result<String> {
+    let players = manager.players()?;
+    let mut output = String::new();
+
+    // The numbers are right-aligned, to keep the names in line.
+    let number_width = players.len().to_string().len();
+    let number = |i: usize| match numbered {
+        true => format!("{:>number_width$}  ", i + 1),
+        false => String::new(),
+    };
+
+    if !status && format.is_none() && !json {
+        for (i, p) in players.iter().enumerate() {
+            output.push_str(&number(i));
+            output.push_str(&manager.display_name(p));
+            output.push('\n');
+        }
+
+        return Ok(output);
+    }
+
+    let current_player = manager.current_name().unwrap_or_default();
+    let name_width = players
+        .iter()
+        .map(|p| manager.display_name(p).chars().count())
+        .max()
+        .unwrap_or(0);
+
+    if json {
+        let entries: Vec<Value> = players
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let player = manager.player(name);
+                let metadata = player.metadata().unwrap_or_default();
+
+                json!({
+                    "index": i + 1,
+                    "name": name,
+                    "player_name": manager.display_name(name),
+                    "current": *name == current_player,
+                    "status": player.status().ok().map(|s| s.to_string()),
+                    "volume": player.volume().ok(),
+                    "title": metadata.title(),
+                    "artist": metadata.artist(),
+                    "album": metadata.album(),
+                })
+            })
+            .collect();
+
+        return Ok(json_line(Value::Array(entries)));
+    }
+
+    for (i, name) in players.iter().enumerate() {
+        let mut fields = player_fields(manager, &manager.player(name));
+
+        fields.push((
+            "current",
+            String::from(if *name == current_player { "*" } else { "" }),
+        ));
+
+        match format {
+            Some(f) => output.push_str(&render_styled(f, &fields)),
+            None => {
+                let field = |key| {
+                    fields
+                        .iter()
+                        .find(|(k, _)| *k == key)
+                        .map_or("", |(_, v)| v.as_str())
+                };
+
+                output.push_str(&format!(
+                    "{}{:1} {:<name_width$}  {:<7}  {:>4}  {}",
+                    number(i),
+                    field("current"),
+                    manager.display_name(name),
+                    field("status"),
+                    field("volume"),
+                    field("title")
+                ));
+            }
+        }
+
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+fn json_line(value: Value) -> String {
+    format!("{}\n", value)
+}
+
+fn playing(manager: &PlayerManager) -> bool {
+    manager
+        .current()
+        .and_then(|p| p.status())
+        .is_ok_and(|s| s == PlaybackStatus::Playing)
+}
+
+/// Renders the current position for `position --follow`.
+fn current_position(manager: &PlayerManager, percent: bool, raw: bool, json: bool) -> String {
+    let player = match manager.current_or_init() {
+        Ok(p) => p,
+        Err(_) => return String::new(),
+    };
+
+    if percent {
+        let percent = position_percent(&player).ok();
+
+        return match json {
+            true => json!({ "player": player.name(), "percent": percent }).to_string(),
+            false => percent.map_or(String::new(), |p| format!("{:.2}", p)),
+        };
+    }
+
+    let position = player.position().ok();
+
+    if json {
+        let position = position.map(|p| p as f64 / 1_000_000.0);
+
+        return json!({ "player": player.name(), "position": position }).to_string();
+    }
+
+    match raw {
+        true => position.map_or(String::new(), format_seconds),
+        false => position.map_or(String::new(), format_duration),
+    }
+}
+
+const NOW_PLAYING_FORMAT: &str = "{status_icon} {artist} - {title} ({position}/{length})";
+
+fn now_playing(manager: &PlayerManager, format: &Option<String>) -> String {
+    render_current(manager, format.as_deref().unwrap_or(NOW_PLAYING_FORMAT))
+}
+
+/// `format` rendered for the current player, or nothing without one.
+fn render_current(manager: &PlayerManager, format: &str) -> String {
+    match manager.current_or_init() {
+        Ok(player) => render_styled(format, &player_fields(manager, &player)),
+        Err(_) => String::new(),
+    }
+}
+
+/// The template `progress` prints.
+fn progress_format(width: usize) -> String {
+    format!("{{progress_bar:{}}}", width)
+}
+
+/// Whether `format` shows the position, which changes all the time while
+/// playing without the player announcing it.
+fn shows_position(format: &str) -> bool {
+    format.contains("{position") || format.contains("{progress")
+}
+
+/// Prints `format` rendered for the current player, and again whenever it
+/// changes if following.
+fn print_template(
+    manager: PlayerManager,
+    format: &str,
+    follow: Option<follow::Options>,
+) -> Result<()> {
+    let options = match follow {
+        Some(options) => options,
+        None => {
+            let player = manager.current()?;
+
+            println!(
+                "{}",
+                render_styled(format, &player_fields(&manager, &player))
+            );
 
-    match init_if_empty_player(&cache_path) {
-        Ok(_) => (),
-        Err(why) => {
-            println!("{}", why);
             return Ok(());
         }
+    };
+
+    // The position changes without the player announcing it.
+    let tick = shows_position(format).then_some(Duration::from_secs(1));
+
+    follow::run(
+        manager,
+        options,
+        |manager| tick.filter(|_| playing(manager)),
+        |manager| render_current(manager, format),
+    )
+}
+
+/// The fields available to `--format` templates describing a player.
+fn player_fields(manager: &PlayerManager, player: &Player) -> Vec<(&'static str, String)> {
+    let metadata = player.metadata().unwrap_or_default();
+    let position = player.position().ok();
+    let players = manager.players().unwrap_or_default();
+
+    let index = players
+        .iter()
+        .position(|p| p == player.name())
+        .map_or(String::from("-"), |i| (i + 1).to_string());
+
+    let volume = player.volume();
+
+    vec![
+        ("name", player.name().to_string()),
+        ("player", player.name().to_string()),
+        ("player_index", index),
+        ("player_count", players.len().to_string()),
+        ("player_name", manager.display_name(player.name())),
+        (
+            "status",
+            player.status().map_or(String::from("-"), |s| s.to_string()),
+        ),
+        ("title", metadata.title().unwrap_or_default().to_string()),
+        ("artist", metadata.artist().unwrap_or_default()),
+        ("album", metadata.album().unwrap_or_default().to_string()),
+        (
+            "volume",
+            volume
+                .as_ref()
+                .map_or(String::from("-"), |v| format!("{:.2}", v)),
+        ),
+        (
+            "volume_percent",
+            volume.map_or(String::from("-"), |v| format!("{:.0}", v * 100.0)),
+        ),
+        (
+            "status_icon",
+            manager
+                .config()
+                .status_icon(player.status().ok())
+                .to_string(),
+        ),
+        (
+            "player_icon",
+            manager.config().player_icon(player.name()).to_string(),
+        ),
+        (
+            "position",
+            position.map_or(String::from("-"), format_duration),
+        ),
+        (
+            "length",
+            metadata.length().map_or(String::from("-"), format_duration),
+        ),
+        (
+            "progress",
+            progress(position, metadata.length())
+                .map_or(String::from("-"), |p| format!("{:.0}", p)),
+        ),
+    ]
+}
+
+/// How far into a track `position` is, in percent of its `length`.
+fn progress(position: Option<i64>, length: Option<i64>) -> Option<f64> {
+    match (position, length) {
+        (Some(position), Some(length)) if length > 0 => {
+            Some((position as f64 / length as f64 * 100.0).clamp(0.0, 100.0))
+        }
+        _ => None,
     }
+}
 
-    let args = Args::from_args();
+/// Formats microseconds as `m:ss`, or `h:mm:ss` for an hour or more.
+fn format_duration(micros: i64) -> String {
+    let seconds = micros.max(0) / 1_000_000;
+
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
 
-    signal_hook::flag::register(
-        signal_hook::consts::SIGUSR1,
-        Arc::clone(&CHANGE_SIGNAL_HANDLER),
-    )?;
+/// Formats microseconds as seconds, e.g. `83.500000`, as playerctl does.
+fn format_seconds(micros: i64) -> String {
+    format!("{:.6}", micros as f64 / 1_000_000.0)
+}
 
-    match args {
-        Args::List => list_players(),
-        Args::Toggle => toggle(&cache_path),
-        Args::Play => play(&cache_path),
-        Args::Pause => pause(&cache_path),
-        Args::Switch { player, next, back } => match switch(&cache_path, player, next, back) {
-            Ok(()) => (),
-            Err(why) => println!("Failed to switch player: {}", why),
-        },
-        Args::Next => next(&cache_path),
-        Args::Previous => previous(&cache_path),
-        Args::Volume { value, format } => volume(&cache_path, &value, &format),
-        Args::Position { value, format } => position(&cache_path, &value, &format),
-        Args::Status { format, follow } => status(&cache_path, &format, follow),
-        Args::Metadata {
-            key,
-            format,
-            follow,
-        } => metadata(&cache_path, &key, &format, follow),
-        Args::Player => player(&cache_path),
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM` in local time.
+fn format_timestamp(timestamp: i64) -> String {
+    let time = timestamp as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return timestamp.to_string();
     }
 
-    Ok(())
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min
+    )
+}
+
+/// Runs `command` on the current player, or on all (other) players.
+fn for_targets<F>(manager: &PlayerManager, targets: &Targets, command: F) -> Result<String>
+where
+    F: Fn(&Player) -> Result<()> + Sync,
+{
+    if !targets.all && !targets.others {
+        return command(&manager.current()?).map(|_| String::new());
+    }
+
+    let current_player = manager.current_name().ok();
+
+    let players: Vec<String> = manager
+        .players()?
+        .into_iter()
+        .filter(|name| !targets.others || Some(name) != current_player.as_ref())
+        .collect();
+
+    // All at once, so that fading them takes no longer than fading one.
+    let results: Vec<Result<()>> = thread::scope(|scope| {
+        let handles: Vec<_> = players
+            .iter()
+            .map(|name| scope.spawn(|| command(&manager.player(name))))
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut result = Ok(());
+
+    for outcome in results {
+        match outcome {
+            // The player exited in the meantime, so there is nothing to do.
+            Err(Error::UnknownPlayer(_)) => (),
+            Err(why) if result.is_ok() => result = Err(why),
+            _ => (),
+        }
+    }
+
+    result.map(|_| String::new())
+}
+
+fn switch(
+    manager: &PlayerManager,
+    player: Option<String>,
+    previous: bool,
+    playing: bool,
+    index: Option<usize>,
+) -> Result<()> {
+    match (player, index) {
+        (Some(p), _) => manager.switch_to(&p),
+        (None, Some(i)) => manager.switch_to_index(i),
+        (None, None) if playing => manager.switch_to_playing(),
+        (None, None) if previous => manager.switch_previous(),
+        (None, None) => manager.switch_next(),
+    }
 }
 
-fn get_cache_path() -> Result<PathBuf, Error> {
-    let xdg_cache = env::var_os("XDG_CACHE_HOME");
+fn mute(manager: &PlayerManager, state: &str) -> Result<()> {
+    let player = manager.current()?;
 
-    let home = env::var("HOME").expect("No $HOME defined!");
-    let cache_base = match xdg_cache {
-        Some(v) => v
-            .to_str()
-            .expect("$XDG_CACHE_HOME is not valid unicode!")
-            .into(),
-        None => format!("{}/.cache", home),
+    let mute = match state.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => !manager.is_muted(&player)?,
     };
 
-    let cache_path = format!("{}/multiplayerctl", cache_base);
+    if mute {
+        manager.mute(&player)
+    } else {
+        manager.unmute(&player)
+    }
+}
+
+fn loop_status(manager: &PlayerManager, status: &Option<String>) -> Result<String> {
+    let player = manager.current()?;
 
-    let cache_path = PathBuf::from(cache_path);
+    let status = match status {
+        Some(s) if s.eq_ignore_ascii_case("cycle") => player.loop_status()?.cycle(),
+        Some(s) => s.parse().map_err(Error::InvalidArgument)?,
+        None => return Ok(format!("{}\n", player.loop_status()?)),
+    };
 
-    create_dir_all(&cache_path)?;
+    player.set_loop_status(status)?;
 
-    Ok(cache_path)
+    Ok(String::new())
 }
 
-fn init_if_empty_player(cache_path: &PathBuf) -> Result<(), String> {
-    let mut file_path = cache_path.to_owned();
-    file_path.push("currentplayer");
+fn shuffle(manager: &PlayerManager, state: &Option<String>) -> Result<String> {
+    let player = manager.current()?;
 
-    let mut current_player = String::new();
+    let shuffle = match state.as_deref().map(str::to_lowercase).as_deref() {
+        Some("on") => true,
+        Some("off") => false,
+        Some(_) => !player.shuffle()?,
+        None => {
+            let current = if player.shuffle()? { "On" } else { "Off" };
 
-    if file_path.exists() {
-        match File::open(&file_path) {
-            Ok(mut f) => match f.read_to_string(&mut current_player) {
-                Ok(_) => (),
-                Err(why) => return Err(format!("Failed to read cache file: {}", why)),
-            },
-            Err(why) => return Err(format!("Cannot open cache file: {}", why)),
+            return Ok(format!("{}\n", current));
         }
-    }
+    };
+
+    player.set_shuffle(shuffle)?;
+
+    Ok(String::new())
+}
+
+fn sink(manager: &PlayerManager, command: SinkCommand, json: bool) -> Result<String> {
+    let sinks = pulse::sinks()?;
+
+    let sink = match command {
+        SinkCommand::List => return list_sinks(manager, &sinks, json),
+        SinkCommand::Set { sink } => sink,
+    };
+
+    let matches = |s: &pulse::Sink| {
+        let wanted = sink.to_lowercase();
 
-    let all_players_output = Command::new("playerctl")
-        .arg("-l")
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?")
-        .stdout;
+        s.name.to_lowercase().contains(&wanted)
+            || s.description
+                .as_ref()
+                .is_some_and(|d| d.to_lowercase().contains(&wanted))
+    };
+
+    let exact = sinks
+        .iter()
+        .find(|s| s.name == sink || s.index.to_string() == sink);
 
-    let all_players_str = match String::from_utf8(all_players_output) {
-        Ok(v) => v,
-        Err(why) => return Err(format!("Failed to get player list: {}", &why)),
+    let found = match exact {
+        Some(s) => s,
+        None => {
+            let candidates: Vec<&pulse::Sink> = sinks.iter().filter(|s| matches(s)).collect();
+
+            match candidates[..] {
+                [s] => s,
+                [] => {
+                    return Err(Error::InvalidArgument(format!(
+                        "No audio output matches {}",
+                        sink
+                    )))
+                }
+                _ => {
+                    let names: Vec<&str> = candidates.iter().map(|s| s.name.as_str()).collect();
+
+                    return Err(Error::InvalidArgument(format!(
+                        "Several audio outputs match {}: {}",
+                        sink,
+                        names.join(", ")
+                    )));
+                }
+            }
+        }
     };
 
-    let mut all_player_lines = all_players_str.lines();
+    manager.current()?.move_to_sink(&found.name)?;
+
+    Ok(String::new())
+}
 
-    let mut file = match File::create(&file_path) {
-        Ok(f) => f,
-        Err(why) => return Err(format!("Failed to create cache file: {}", why)),
+fn queue(manager: &PlayerManager, command: QueueCommand, json: bool) -> Result<String> {
+    let player = manager.current()?;
+    let tracks = player.tracks()?;
+    let current = player
+        .metadata()
+        .ok()
+        .and_then(|m| m.track_id().map(String::from));
+
+    let track_at = |index: usize| {
+        index
+            .checked_sub(1)
+            .and_then(|i| tracks.get(i))
+            .and_then(Metadata::track_id)
+            .ok_or_else(|| {
+                Error::InvalidArgument(format!("There is no track {} in the queue", index))
+            })
     };
 
-    let mut player_valid = false;
+    match command {
+        QueueCommand::List => return Ok(list_queue(&tracks, current.as_deref(), json)),
+        QueueCommand::Add { uri, next } => {
+            let after = match next {
+                true => current.as_deref(),
+                false => tracks.last().and_then(Metadata::track_id),
+            };
 
-    for l in all_player_lines.clone() {
-        if l == current_player {
-            player_valid = true;
+            player.add_track(&uri, after)?;
         }
+        QueueCommand::Remove { index } => player.remove_track(track_at(index)?)?,
+        QueueCommand::Goto { index } => player.go_to(track_at(index)?)?,
     }
 
-    if !player_valid {
-        current_player = String::new();
+    Ok(String::new())
+}
+
+fn list_queue(tracks: &[Metadata], current: Option<&str>, json: bool) -> String {
+    let is_current = |track: &Metadata| track.track_id().is_some() && track.track_id() == current;
+
+    if json {
+        let entries: Vec<Value> = tracks
+            .iter()
+            .enumerate()
+            .map(|(i, track)| {
+                json!({
+                    "index": i + 1,
+                    "trackid": track.track_id(),
+                    "title": track.title(),
+                    "artist": track.artist(),
+                    "album": track.album(),
+                    "length": track.length_secs(),
+                    "url": track.url(),
+                    "current": is_current(track),
+                })
+            })
+            .collect();
+
+        return json_line(Value::Array(entries));
     }
 
-    if current_player.is_empty() {
-        match all_player_lines.next() {
-            Some(v) => current_player = v.to_string(),
-            None => return Err(String::from("No players found!")),
-        }
+    let mut output = String::new();
+
+    for (i, track) in tracks.iter().enumerate() {
+        let name = match (track.artist(), track.title()) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title.to_string(),
+            _ => track
+                .url()
+                .or(track.track_id())
+                .unwrap_or_default()
+                .to_string(),
+        };
+
+        let line = format!(
+            "{:1} {:>3}  {}  {}",
+            if is_current(track) { "*" } else { "" },
+            i + 1,
+            name,
+            track.length().map(format_duration).unwrap_or_default(),
+        );
+
+        output.push_str(line.trim_end());
+        output.push('\n');
     }
 
-    file.write_all(current_player.as_bytes())
-        .expect("Failed to write cache file.");
+    output
+}
 
-    Ok(())
+fn list_sinks(manager: &PlayerManager, sinks: &[pulse::Sink], json: bool) -> Result<String> {
+    // Nothing is marked without a current player or while it plays nothing.
+    let playing_on: Vec<u32> = manager
+        .current()
+        .and_then(|p| p.sink_inputs())
+        .unwrap_or_default()
+        .iter()
+        .map(|input| input.sink)
+        .collect();
+
+    if json {
+        let entries: Vec<Value> = sinks
+            .iter()
+            .map(|s| {
+                json!({
+                    "index": s.index,
+                    "name": s.name,
+                    "description": s.description,
+                    "current": playing_on.contains(&s.index),
+                })
+            })
+            .collect();
+
+        return Ok(json_line(Value::Array(entries)));
+    }
+
+    let name_width = sinks
+        .iter()
+        .map(|s| s.name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+
+    for s in sinks {
+        let line = format!(
+            "{:1} {:>3}  {:<name_width$}  {}",
+            if playing_on.contains(&s.index) {
+                "*"
+            } else {
+                ""
+            },
+            s.index,
+            s.name,
+            s.description.as_deref().unwrap_or(""),
+        );
+
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Splits a `+`/`-` sign off either end of `value`, accepting both the
+/// playerctl syntax (`0.1+`) and the usual one (`+0.1`).
+fn split_sign(value: &str) -> (&str, Option<char>) {
+    match (value.chars().next(), value.chars().last()) {
+        (Some(c), _) if c == '+' || c == '-' => (&value[1..], Some(c)),
+        (_, Some(c)) if c == '+' || c == '-' => (&value[..value.len() - 1], Some(c)),
+        _ => (value, None),
+    }
+}
+
+fn parse_number(value: &str, number: &str) -> Result<f64> {
+    match number.parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        Ok(_) => Err(Error::InvalidArgument(format!(
+            "Invalid value '{}': not a finite number",
+            value
+        ))),
+        Err(why) => Err(Error::InvalidArgument(format!(
+            "Invalid value '{}': {}",
+            value, why
+        ))),
+    }
 }
 
-fn get_current_player(cache_path: &PathBuf) -> String {
-    let mut file_path = cache_path.to_owned();
-    file_path.push("currentplayer");
+fn get_or_set_volume(
+    manager: &PlayerManager,
+    value: &Option<String>,
+    json: bool,
+) -> Result<String> {
+    let player = manager.current()?;
+
+    match value {
+        Some(v) => {
+            let (number, sign) = split_sign(v);
+
+            let amount = match number.strip_suffix('%') {
+                Some(percent) => parse_number(v, percent)? / 100.0,
+                None => parse_number(v, number)?,
+            };
+
+            let target = match sign {
+                Some('+') => player.volume()? + amount,
+                Some(_) => player.volume()? - amount,
+                None => amount,
+            };
+
+            player.set_volume(target.clamp(0.0, manager.config().volume.limit()))?;
 
-    let mut current_player = String::new();
+            Ok(String::new())
+        }
+        None if json => Ok(json_line(
+            json!({ "player": player.name(), "volume": player.volume()? }),
+        )),
+        None => Ok(format!("{:.6}\n", player.volume()?)),
+    }
+}
+
+fn seek(manager: &PlayerManager, offset: &str) -> Result<()> {
+    let player = manager.current()?;
+    let (amount, sign) = split_sign(offset);
 
-    match File::open(&file_path) {
-        Ok(mut f) => match f.read_to_string(&mut current_player) {
-            Ok(_) => (),
-            Err(why) => panic!("Failed to read cache file: {}", why),
+    let micros = match amount.strip_suffix('%') {
+        Some(percent) => {
+            (parse_number(offset, percent)? / 100.0 * track_length(&player)? as f64) as i64
+        }
+        None => match parse_duration(amount) {
+            Some(seconds) => (seconds * 1_000_000.0) as i64,
+            None => {
+                return Err(Error::InvalidArgument(format!(
+                    "Invalid offset '{}'",
+                    offset
+                )))
+            }
         },
-        Err(why) => panic!("Cannot open cache file: {}", why),
+    };
+
+    match sign {
+        Some('+') => player.seek(micros),
+        Some(_) => player.seek(-micros),
+        None => player.set_position(micros),
     }
+}
+
+#[cfg(feature = "history")]
+fn history(manager: &PlayerManager, limit: usize, json: bool) -> Result<()> {
+    print!("{}", listens::print(manager, limit, json)?);
+
+    Ok(())
+}
 
-    current_player
+#[cfg(not(feature = "history"))]
+fn history(_manager: &PlayerManager, _limit: usize, _json: bool) -> Result<()> {
+    Err(Error::InvalidArgument(String::from(
+        "multiplayerctl was built without the `history` feature.",
+    )))
 }
 
-fn list_players() {
-    let output = Command::new("playerctl")
-        .arg("-l")
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?");
+#[cfg(feature = "history")]
+fn stats(manager: &PlayerManager, period: &str, limit: usize, json: bool) -> Result<()> {
+    print!("{}", listens::stats(manager, period, limit, json)?);
 
-    print!("{}", String::from_utf8(output.stdout).unwrap());
-    eprint!("{}", String::from_utf8(output.stderr).unwrap());
+    Ok(())
 }
 
-fn toggle(cache_path: &PathBuf) {
-    let current_player = get_current_player(cache_path);
+#[cfg(feature = "scrobble")]
+fn scrobble(manager: &PlayerManager, command: ScrobbleCommand) -> Result<()> {
+    match command {
+        ScrobbleCommand::Auth => scrobble::auth(manager),
+    }
+}
 
-    Command::new("playerctl")
-        .arg(format!("--player={}", current_player))
-        .arg("play-pause")
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?");
+#[cfg(not(feature = "scrobble"))]
+fn scrobble(_manager: &PlayerManager, _command: ScrobbleCommand) -> Result<()> {
+    Err(Error::InvalidArgument(String::from(
+        "multiplayerctl was built without the `scrobble` feature.",
+    )))
 }
 
-fn play(cache_path: &PathBuf) {
-    let current_player = get_current_player(cache_path);
+#[cfg(feature = "serve")]
+fn serve(manager: PlayerManager, bind: Option<String>, token: Option<String>) -> Result<()> {
+    let config = &manager.config().serve;
+    let bind = bind.unwrap_or_else(|| config.bind.clone());
+    let token = token.or_else(|| config.token.clone());
 
-    Command::new("playerctl")
-        .arg(format!("--player={}", current_player))
-        .arg("play")
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?");
+    serve::run(manager, &bind, token)
 }
 
-fn pause(cache_path: &PathBuf) {
-    let current_player = get_current_player(cache_path);
+#[cfg(not(feature = "serve"))]
+fn serve(_manager: PlayerManager, _bind: Option<String>, _token: Option<String>) -> Result<()> {
+    Err(Error::InvalidArgument(String::from(
+        "multiplayerctl was built without the `serve` feature.",
+    )))
+}
 
-    Command::new("playerctl")
-        .arg(format!("--player={}", current_player))
-        .arg("pause")
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?");
+#[cfg(feature = "tui")]
+fn tui(manager: PlayerManager, forward: bool) -> Result<()> {
+    tui::run(manager, forward)
 }
 
-fn switch(
-    cache_path: &PathBuf,
-    player: Option<String>,
-    _next: bool,
-    previous: bool,
-) -> Result<(), String> {
-    let mut file_path = cache_path.to_owned();
-    file_path.push("currentplayer");
+#[cfg(not(feature = "tui"))]
+fn tui(_manager: PlayerManager, _forward: bool) -> Result<()> {
+    Err(Error::InvalidArgument(String::from(
+        "multiplayerctl was built without the `tui` feature.",
+    )))
+}
 
-    let mut current_player = String::new();
+#[cfg(feature = "tray")]
+fn tray(manager: PlayerManager, forward: bool) -> Result<()> {
+    tray::run(manager, forward)
+}
 
-    if file_path.exists() {
-        match File::open(&file_path) {
-            Ok(mut f) => match f.read_to_string(&mut current_player) {
-                Ok(_) => (),
-                Err(why) => return Err(format!("Failed to read cache file: {}", why)),
-            },
-            Err(why) => return Err(format!("Cannot open cache file: {}", why)),
-        }
+#[cfg(not(feature = "tray"))]
+fn tray(_manager: PlayerManager, _forward: bool) -> Result<()> {
+    Err(Error::InvalidArgument(String::from(
+        "multiplayerctl was built without the `tray` feature.",
+    )))
+}
+
+#[cfg(not(feature = "history"))]
+fn stats(_manager: &PlayerManager, _period: &str, _limit: usize, _json: bool) -> Result<()> {
+    Err(Error::InvalidArgument(String::from(
+        "multiplayerctl was built without the `history` feature.",
+    )))
+}
+
+fn parse_status(value: &str) -> std::result::Result<PlaybackStatus, String> {
+    match value.to_lowercase().as_str() {
+        "playing" => Ok(PlaybackStatus::Playing),
+        "paused" => Ok(PlaybackStatus::Paused),
+        "stopped" => Ok(PlaybackStatus::Stopped),
+        _ => Err(format!("'{}' is not Playing, Paused or Stopped", value)),
     }
+}
 
-    let all_players_output = Command::new("playerctl")
-        .arg("-l")
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?")
-        .stdout;
+fn parse_condition(value: &str) -> std::result::Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, expected)) if !key.is_empty() => Ok((key.to_string(), expected.to_string())),
+        _ => Err(format!("'{}' is not of the form KEY=VALUE", value)),
+    }
+}
 
-    let all_players_str = match String::from_utf8(all_players_output) {
-        Ok(v) => v,
-        Err(why) => return Err(format!("Failed to get player list: {}", &why)),
-    };
+/// Parses a positive interval for `--interval`, in the same syntax as `seek`.
+fn parse_interval(value: &str) -> std::result::Result<Duration, String> {
+    parse_duration(value)
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .filter(|interval| !interval.is_zero())
+        .ok_or_else(|| format!("'{}' is not a positive duration", value))
+}
 
-    let mut all_player_lines = all_players_str.lines();
+/// The shortest `--tick`, as anything faster only redraws bars for nothing.
+const MIN_TICK: Duration = Duration::from_millis(100);
 
-    let line_count = all_player_lines.clone().count();
+/// Parses `--tick` like `--interval`, but no shorter than [`MIN_TICK`].
+fn parse_tick(value: &str) -> std::result::Result<Duration, String> {
+    let tick = parse_interval(value)?;
 
-    match player {
-        Some(p) => {
-            for l in all_player_lines.clone() {
-                if l == p {
-                    current_player = p;
-                    break;
-                }
-            }
-        }
-        None => {
-            for (i, l) in all_player_lines.clone().enumerate() {
-                if l == current_player {
-                    if previous {
-                        current_player = all_player_lines
-                            .nth((i + line_count - 1) % line_count)
-                            .expect("Cannot get indexed player.")
-                            .into();
-                    } else {
-                        current_player = all_player_lines
-                            .nth((i + 1) % line_count)
-                            .expect("Cannot get indexed player.")
-                            .into();
-                    }
-                    break;
-                }
-            }
-        }
+    match tick < MIN_TICK {
+        true => Err(format!("'{}' is shorter than {:?}", value, MIN_TICK)),
+        false => Ok(tick),
     }
+}
 
-    let mut file = match File::create(&file_path) {
-        Ok(f) => f,
-        Err(why) => return Err(format!("Failed to create cache file: {}", why)),
-    };
+/// Parses `--debounce`, in milliseconds unless another unit is given.
+fn parse_debounce(value: &str) -> std::result::Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(millis) => Ok(Duration::from_millis(millis)),
+        Err(_) => parse_interval(value),
+    }
+}
+
+/// Parses a duration like `90`, `1.5`, `1m30s`, `1h2m`, `01:30` or
+/// `1:02:03` into seconds. Minutes and seconds after a colon have to be
+/// below 60, and `inf` or `NaN` are not durations.
+fn parse_duration(value: &str) -> Option<f64> {
+    // Durations are never negative, not even in part, as in `1m-30s`. The
+    // sign of `seek` is taken off before.
+    if value.contains(['+', '-']) {
+        return None;
+    }
 
-    if current_player.is_empty() {
-        current_player = all_player_lines.next().expect("No players found!").into();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Some(seconds).filter(|s| s.is_finite());
     }
 
-    file.write_all(current_player.as_bytes())
-        .expect("Failed to write cache file.");
+    if value.contains(':') {
+        let parts: Vec<&str> = value.split(':').collect();
 
-    // send SIGUSR1 to all running instances of multiplayerctl
-    let mut pids = Vec::new();
+        if parts.len() > 3 {
+            return None;
+        }
 
-    for entry in std::fs::read_dir("/proc").expect("Failed to read /proc") {
-        let entry = entry.expect("Failed to read /proc entry");
-        let path = entry.path();
+        return parts
+            .into_iter()
+            .enumerate()
+            .try_fold(0.0, |seconds, (i, part)| {
+                let part: f64 = part
+                    .parse()
+                    .ok()
+                    .filter(|p: &f64| *p >= 0.0 && (i == 0 || *p < 60.0))?;
+
+                Some(seconds * 60.0 + part)
+            })
+            .filter(|s| s.is_finite());
+    }
 
-        if path.is_dir() {
-            let file_name = path.file_name().unwrap().to_str().unwrap();
+    let mut seconds = 0.0;
+    let mut rest = value;
 
-            if file_name.parse::<u32>().is_ok() {
-                let mut file = match File::open(path.join("comm")) {
-                    Ok(f) => f,
-                    Err(_) => continue,
-                };
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| c.is_alphabetic())?;
+        let number: f64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
 
-                let mut contents = String::new();
+        let unit_length = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
 
-                match file.read_to_string(&mut contents) {
-                    Ok(_) => (),
-                    Err(_) => continue,
-                }
+        seconds += number
+            * match &rest[..unit_length] {
+                "h" => 3600.0,
+                "m" => 60.0,
+                "s" => 1.0,
+                "ms" => 0.001,
+                _ => return None,
+            };
 
-                if contents.contains("multiplayerctl") {
-                    pids.push(file_name.parse::<u32>().unwrap());
-                }
-            }
-        }
+        rest = &rest[unit_length..];
     }
 
-    for pid in pids {
-        unsafe {
-            libc::kill(pid as i32, libc::SIGUSR1);
-        }
+    Some(seconds).filter(|s| s.is_finite())
+}
+
+fn get_or_set_position(
+    manager: &PlayerManager,
+    value: &Option<String>,
+    percent: bool,
+    raw: bool,
+    json: bool,
+) -> Result<String> {
+    // Setting the position is seeking without a sign.
+    if let Some(v) = value {
+        return seek(manager, v).map(|_| String::new());
     }
 
-    Ok(())
+    let player = manager.current()?;
+
+    match (percent, json) {
+        (true, true) => Ok(json_line(json!({
+            "player": player.name(),
+            "percent": position_percent(&player)?,
+        }))),
+        (true, false) => Ok(format!("{:.2}\n", position_percent(&player)?)),
+        (false, true) => Ok(json_line(json!({
+            "player": player.name(),
+            "position": player.position()? as f64 / 1_000_000.0,
+        }))),
+        (false, false) => match raw {
+            true => Ok(format!("{}\n", format_seconds(player.position()?))),
+            false => Ok(format!("{}\n", format_duration(player.position()?))),
+        },
+    }
 }
 
-fn next(cache_path: &PathBuf) {
-    let current_player = get_current_player(cache_path);
+/// How far `player` is into its track, in percent of the track's length.
+fn position_percent(player: &Player) -> Result<f64> {
+    let length = track_length(player)?;
 
-    let output = Command::new("playerctl")
-        .arg(format!("--player={}", current_player))
-        .arg("next")
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?");
+    Ok(progress(Some(player.position()?), Some(length)).unwrap_or_default())
+}
 
-    print!("{}", String::from_utf8(output.stdout).unwrap());
-    eprint!("{}", String::from_utf8(output.stderr).unwrap());
+/// The length of `player`'s track, which positions in percent are of.
+fn track_length(player: &Player) -> Result<i64> {
+    match player.metadata()?.length() {
+        Some(length) if length > 0 => Ok(length),
+        _ => Err(Error::player_command(
+            player.name(),
+            "The current track has no known length.",
+        )),
+    }
 }
 
-fn previous(cache_path: &PathBuf) {
-    let current_player = get_current_player(cache_path);
+fn get_status(manager: &PlayerManager, json: bool) -> Result<String> {
+    let player = manager.current()?;
+    let status = player.status()?;
 
-    let output = Command::new("playerctl")
-        .arg(format!("--player={}", current_player))
-        .arg("previous")
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?");
+    if json {
+        return Ok(json_line(
+            json!({ "player": player.name(), "status": status.to_string() }),
+        ));
+    }
 
-    print!("{}", String::from_utf8(output.stdout).unwrap());
-    eprint!("{}", String::from_utf8(output.stderr).unwrap());
+    Ok(format!("{}\n", status))
 }
 
-fn volume(cache_path: &PathBuf, value: &Option<String>, format: &Option<String>) {
-    let current_player = get_current_player(cache_path);
+fn get_metadata(manager: &PlayerManager, key: &Option<String>, json: bool) -> Result<String> {
+    let player = manager.current()?;
+    let metadata = player.metadata()?;
+
+    if json {
+        return Ok(json_line(match key {
+            Some(k) => json!({
+                "player": player.name(),
+                "metadata": { k.as_str(): metadata.get(k) },
+            }),
+            None => json!({
+                "player": player.name(),
+                "trackid": metadata.track_id(),
+                "title": metadata.title(),
+                "artists": metadata.artists(),
+                "album": metadata.album(),
+                "album_artists": metadata.album_artists(),
+                "track_number": metadata.track_number(),
+                "length": metadata.length_secs(),
+                "art_url": metadata.art_url(),
+                "url": metadata.url(),
+                "metadata": metadata,
+            }),
+        }));
+    }
 
-    let mut args: Vec<String> = vec![format!("--player={}", current_player), "volume".to_string()];
+    match key {
+        Some(k) => Ok(match metadata.get(k) {
+            Some(v) => format!("{}\n", v),
+            None => String::new(),
+        }),
+        None => {
+            let mut output = String::new();
 
-    match value {
-        Some(v) => {
-            args.push(v.to_string());
-        }
-        None => (),
-    }
+            for (k, v) in metadata.iter() {
+                output.push_str(&format!("{} {:<25} {}\n", player.name(), k, v));
+            }
 
-    match format {
-        Some(f) => {
-            args.push(format!("--format={}", f));
+            Ok(output)
         }
-        None => (),
     }
+}
+
+/// Prints one of the `title`, `artist`, `album` or `length` shortcuts, the
+/// length in seconds if `raw`. Fails with [`Error::MissingMetadata`] and
+/// prints nothing if the player does not report the field; with `--json` it
+/// is printed as `null` instead.
+fn get_metadata_field(
+    manager: &PlayerManager,
+    field: &str,
+    raw: bool,
+    json: bool,
+) -> Result<String> {
+    let player = manager.current()?;
+    let metadata = player.metadata()?;
+
+    let (text, value) = match field {
+        "title" => (metadata.title().map(String::from), json!(metadata.title())),
+        "artist" => (metadata.artist(), json!(metadata.artists())),
+        "album" => (metadata.album().map(String::from), json!(metadata.album())),
+        "length" => (
+            metadata.length().map(|length| match raw {
+                true => format_seconds(length),
+                false => format_duration(length),
+            }),
+            json!(metadata.length_secs()),
+        ),
+        _ => unreachable!(),
+    };
 
-    let output = Command::new("playerctl")
-        .args(args)
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?");
+    if json {
+        return Ok(json_line(json!({ "player": player.name(), field: value })));
+    }
 
-    print!("{}", String::from_utf8(output.stdout).unwrap());
-    eprint!("{}", String::from_utf8(output.stderr).unwrap());
+    match text {
+        Some(t) => Ok(format!("{}\n", t)),
+        None => Err(Error::MissingMetadata {
+            player: player.name().to_string(),
+            field: field.to_string(),
+        }),
+    }
 }
 
-fn position(cache_path: &PathBuf, value: &Option<String>, format: &Option<String>) {
-    let current_player = get_current_player(cache_path);
+fn cover_art(manager: &PlayerManager, size: Option<u32>, path: bool, json: bool) -> Result<String> {
+    let player = manager.current()?;
+    let file = art::fetch(
+        manager.cache_path(),
+        player.name(),
+        &player.metadata()?,
+        size,
+        manager.config().art.musicbrainz,
+    )?;
+    let uri = format!("file://{}", file.display());
 
-    let mut args: Vec<String> = vec![
-        format!("--player={}", current_player),
-        "position".to_string(),
-    ];
+    if json {
+        return Ok(json_line(
+            json!({ "player": player.name(), "path": file, "uri": uri }),
+        ));
+    }
 
-    match value {
-        Some(v) => {
-            args.push(v.to_string());
-        }
-        None => (),
+    Ok(match path {
+        true => format!("{}\n", file.display()),
+        false => format!("{}\n", uri),
+    })
+}
+
+fn cover_colors(manager: &PlayerManager, json: bool) -> Result<String> {
+    let player = manager.current()?;
+    let file = art::fetch(
+        manager.cache_path(),
+        player.name(),
+        &player.metadata()?,
+        None,
+        manager.config().art.musicbrainz,
+    )?;
+    let colors = art::colors(&file)?;
+
+    if json {
+        return Ok(json_line(json!({
+            "player": player.name(),
+            "dominant": art::hex(colors.dominant),
+            "accent": art::hex(colors.accent),
+        })));
     }
 
-    match format {
-        Some(f) => {
-            args.push(format!("--format={}", f));
-        }
-        None => (),
+    Ok(format!(
+        "dominant {}\naccent {}\n",
+        art::hex(colors.dominant),
+        art::hex(colors.accent)
+    ))
+}
+
+fn print_lyrics(manager: &PlayerManager, json: bool) -> Result<String> {
+    let player = manager.current()?;
+    let metadata = player.metadata()?;
+    let lyrics = lyrics::fetch(manager.cache_path(), player.name(), &metadata)?;
+
+    if json {
+        let lines: Vec<Value> = lyrics
+            .synced
+            .iter()
+            .map(|(time, line)| json!({ "time": *time as f64 / 1_000_000.0, "line": line }))
+            .collect();
+
+        return Ok(json_line(json!({
+            "player": player.name(),
+            "title": metadata.title(),
+            "artist": metadata.artist(),
+            "lyrics": lyrics.text(),
+            "synced": lines,
+            "instrumental": lyrics.instrumental,
+        })));
     }
 
-    let output = Command::new("playerctl")
-        .args(args)
-        .output()
-        .expect("Failed to execute playerctl. Are you sure it is installed?");
+    Ok(format!("{}\n", lyrics.text()))
+}
+
+/// Prints the line of the current track being sung whenever it changes, for
+/// `lyrics --follow`.
+fn follow_lyrics(manager: PlayerManager, options: follow::Options, json: bool) -> Result<()> {
+    // The lyrics of the last track shown, looked up once per track.
+    let fetched: RefCell<Option<(String, Option<Lyrics>)>> = RefCell::new(None);
+    let next_line = Cell::new(None);
+
+    follow::run(
+        manager,
+        options,
+        |_| next_line.get(),
+        |manager| {
+            next_line.set(None);
+
+            let player = match manager.current_or_init() {
+                Ok(p) => p,
+                Err(_) => return String::new(),
+            };
+
+            let metadata = player.metadata().unwrap_or_default();
+            let key = format!(
+                "{} {:?} {:?} {:?}",
+                player.name(),
+                metadata.track_id(),
+                metadata.title(),
+                metadata.artists()
+            );
+
+            let mut fetched = fetched.borrow_mut();
+
+            if !matches!(&*fetched, Some((k, _)) if *k == key) {
+                let lyrics = lyrics::fetch(manager.cache_path(), player.name(), &metadata);
+
+                if let Err(why) = &lyrics {
+                    eprintln!("{}", why);
+                }
+
+                *fetched = Some((key, lyrics.ok()));
+            }
+
+            let lyrics = fetched.as_ref().and_then(|(_, l)| l.as_ref());
+            let position = player.position().unwrap_or_default();
+
+            // Woken up for the next line only while playing; pausing and
+            // seeking are announced by the player.
+            if playing(manager) {
+                next_line.set(
+                    lyrics
+                        .and_then(|l| l.next_line_at(position))
+                        .map(|at| Duration::from_micros((at - position) as u64)),
+                );
+            }
+
+            let line = lyrics.and_then(|l| l.line_at(position)).unwrap_or_default();
 
-    print!("{}", String::from_utf8(output.stdout).unwrap());
-    eprint!("{}", String::from_utf8(output.stderr).unwrap());
+            match json {
+                true => json!({ "player": player.name(), "line": line }).to_string(),
+                false => line.to_string(),
+            }
+        },
+    )
 }
 
-fn status(cache_path: &PathBuf, format: &Option<String>, follow: bool) {
-    let current_player = get_current_player(cache_path);
+fn volume(
+    manager: &PlayerManager,
+    value: &Option<String>,
+    format: &Option<String>,
+    follow: Option<follow::Options>,
+    json: bool,
+) -> Result<()> {
+    let current_player = manager.current_name()?;
 
-    let mut args: Vec<String> = vec![format!("--player={}", current_player), "status".to_string()];
+    let mut args: Vec<String> = vec!["volume".to_string()];
 
-    match format {
-        Some(f) => {
-            args.push(format!("--format={}", f));
-        }
-        None => (),
+    if let Some(v) = value {
+        args.push(v.to_string());
     }
 
-    if !follow {
-        let output = Command::new("playerctl")
-            .args(args)
-            .output()
-            .expect("Failed to execute playerctl. Are you sure it is installed?");
+    if json {
+        args.push(String::from("--format={{volume}}"));
+    } else if let Some(f) = format {
+        args.push(format!("--format={}", f));
+    }
 
-        print!("{}", String::from_utf8(output.stdout).unwrap());
-        eprint!("{}", String::from_utf8(output.stderr).unwrap());
-    } else {
-        let mut child = Command::new("playerctl")
-            .args(args)
-            .stdout(Stdio::piped())
-            .spawn()
-            .expect("Failed to execute playerctl. Are you sure it is installed?");
+    let options = match follow {
+        Some(options) => options,
+        None => return passthrough(manager, &current_player, &args),
+    };
 
-        let stdout = child.stdout.as_mut().expect("Failed to get stdout.");
+    follow_playerctl(manager, options, args, |player, line| {
+        if json {
+            let volume = line.trim().parse::<f64>().ok();
 
-        let mut reader = BufReader::new(stdout);
+            print!(
+                "{}",
+                json_line(json!({ "player": player, "volume": volume }))
+            );
+        } else {
+            println!("{}", line);
+        }
 
-        let mut line = String::new();
+        Ok(())
+    })
+}
 
-        loop {
-            line.clear();
+fn position(
+    manager: &PlayerManager,
+    value: &Option<String>,
+    format: &Option<String>,
+) -> Result<()> {
+    let current_player = manager.current_name()?;
 
-            reader.read_line(&mut line).expect("Failed to read line.");
+    let mut args: Vec<String> = vec!["position".to_string()];
 
-            print!("{}", line);
-        }
+    if let Some(v) = value {
+        args.push(v.to_string());
     }
+
+    if let Some(f) = format {
+        args.push(format!("--format={}", f));
+    }
+
+    passthrough(manager, &current_player, &args)
 }
 
-fn metadata(cache_path: &PathBuf, key: &Option<String>, format: &Option<String>, follow: bool) {
-    let current_player = get_current_player(cache_path);
+/// Runs a playerctl command on a player and prints its output.
+fn passthrough(manager: &PlayerManager, player: &str, args: &[String]) -> Result<()> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
 
-    let mut args: Vec<String> = vec![
-        format!("--player={}", current_player),
-        "metadata".to_string(),
-    ];
+    print!(
+        "{}",
+        playerctl::query(player, &args, manager.backend().timeout())?
+    );
 
-    match key {
-        Some(k) => {
+    Ok(())
+}
+
+fn status(
+    manager: &PlayerManager,
+    format: &Option<String>,
+    follow: Option<follow::Options>,
+    json: bool,
+) -> Result<()> {
+    let current_player = manager.current_name()?;
+
+    let mut args: Vec<String> = vec!["status".to_string()];
+
+    if json {
+        args.push(String::from("--format={{status}}"));
+    } else if let Some(f) = format {
+        args.push(format!("--format={}", f));
+    }
+
+    let options = match follow {
+        Some(options) => options,
+        None => return passthrough(manager, &current_player, &args),
+    };
+
+    follow_playerctl(manager, options, args, |player, line| {
+        if json {
+            print!(
+                "{}",
+                json_line(json!({ "player": player, "status": line.trim() }))
+            );
+        } else {
+            println!("{}", line);
+        }
+
+        Ok(())
+    })
+}
+
+fn metadata(
+    manager: &PlayerManager,
+    key: &Option<String>,
+    format: &Option<String>,
+    follow: Option<follow::Options>,
+    json: bool,
+) -> Result<()> {
+    let current_player = manager.current_name()?;
+
+    let mut args: Vec<String> = vec!["metadata".to_string()];
+
+    if json {
+        // Only used to find out when the track changes; the metadata itself
+        // is read from the backend.
+        args.push(String::from(
+            "--format={{mpris:trackid}} {{xesam:title}} {{xesam:artist}} {{xesam:album}}",
+        ));
+    } else {
+        if let Some(k) = key {
             args.push(k.to_string());
         }
-        None => (),
-    }
 
-    match format {
-        Some(f) => {
+        if let Some(f) = format {
             args.push(format!("--format={}", f));
         }
-        None => (),
     }
 
-    if !follow {
-        let output = Command::new("playerctl")
-            .args(args)
-            .output()
-            .expect("Failed to execute playerctl. Are you sure it is installed?");
+    let options = match follow {
+        Some(options) => options,
+        None => return passthrough(manager, &current_player, &args),
+    };
+
+    follow_playerctl(manager, options, args, |_, line| {
+        if json {
+            print!("{}", get_metadata(manager, key, true)?);
+        } else {
+            println!("{}", line);
+        }
 
-        print!("{}", String::from_utf8(output.stdout).unwrap());
-        eprint!("{}", String::from_utf8(output.stderr).unwrap());
-    } else {
-        args.push("--follow".to_string());
+        Ok(())
+    })
+}
+
+/// Runs `playerctl <args> --follow` against the current player, calling
+/// `on_line` with the player and each line it prints. playerctl is restarted
+/// for the new player whenever the current player is switched, and killed
+/// and reaped when following ends, whether by an error or a closed stdout.
+fn follow_playerctl<F>(
+    manager: &PlayerManager,
+    options: follow::Options,
+    mut args: Vec<String>,
+    mut on_line: F,
+) -> Result<()>
+where
+    F: FnMut(&str, &str) -> Result<()>,
+{
+    args.push(String::from("--follow"));
+
+    let watcher = manager.watch_current()?;
+
+    // With --once, playerctl's first line only tells how things are.
+    let mut skip = options.once;
+    // The player and line printed last, for --dedupe.
+    let mut last: Option<(String, String)> = None;
+
+    // Prints a line, and tells whether that was the last one.
+    let mut emit = |last: &mut Option<(String, String)>,
+                    player: &str,
+                    line: String|
+     -> Result<ControlFlow<()>> {
+        let line = (player.to_string(), line);
+
+        if options.dedupe && last.as_ref() == Some(&line) {
+            return Ok(ControlFlow::Continue(()));
+        }
+
+        on_line(&line.0, &line.1)?;
+        io::stdout().flush()?;
+
+        *last = Some(line);
+
+        match options.once {
+            true => Ok(ControlFlow::Break(())),
+            false => Ok(ControlFlow::Continue(())),
+        }
+    };
+
+    loop {
+        let current_player = manager.current_name()?;
+
+        let mut child = playerctl::spawn(
+            std::iter::once(format!("--player={}", current_player)).chain(args.iter().cloned()),
+        )?;
+
+        let stdout = child.stdout.as_mut().expect("Child stdout is piped.");
+
+        let mut buf = [0u8; 1024];
+        let mut pending: Vec<u8> = Vec::new();
+        let mut switched = false;
+        // The latest line, held back until --debounce has passed without
+        // another one, and when that is.
+        let mut held: Option<(String, Instant)> = None;
 
         loop {
-            let current_player = get_current_player(cache_path);
-            args.iter_mut().for_each(|arg| {
-                if arg.starts_with("--player=") {
-                    *arg = format!("--player={}", current_player);
-                }
-            });
-            let mut child = Command::new("playerctl")
-                .args(args.clone())
-                .stdout(Stdio::piped())
-                .spawn()
-                .expect("Failed to execute playerctl. Are you sure it is installed?");
-
-            let stdout = child.stdout.as_mut().expect("Failed to get stdout.");
-
-            let fd = stdout.as_raw_fd();
-            let flags = unsafe { libc::fcntl(fd, libc::F_GETFL, 0) };
-            if flags == -1 {
-                panic!("Failed to get file descriptor flags.");
+            let timeout = held
+                .as_ref()
+                .map(|(_, due)| due.saturating_duration_since(Instant::now()));
+
+            let [output, changed] =
+                wait_readable_for([stdout.as_raw_fd(), watcher.as_raw_fd()], timeout)?;
+
+            if changed && watcher.read_change()? {
+                switched = true;
+                break;
             }
 
-            let flags = flags | libc::O_NONBLOCK;
+            if !output {
+                if let Some((line, _)) = held.take_if(|(_, due)| Instant::now() >= *due) {
+                    if emit(&mut last, &current_player, line)?.is_break() {
+                        return Ok(());
+                    }
+                }
 
-            let res = unsafe { libc::fcntl(fd, libc::F_SETFL, flags) };
-            if res == -1 {
-                panic!("Failed to set non-blocking mode.");
+                continue;
             }
 
-            let mut buf = [0u8; 1024];
+            let n = stdout.read(&mut buf)?;
 
-            loop {
-                if CHANGE_SIGNAL_HANDLER.load(Ordering::Relaxed) {
-                    child.kill().expect("Failed to kill child process.");
-                    CHANGE_SIGNAL_HANDLER.store(false, Ordering::Relaxed);
-                    break;
-                }
+            if n == 0 {
+                break;
+            }
 
-                let n = unsafe {
-                    libc::read(
-                        fd,
-                        buf.as_mut_ptr() as *mut libc::c_void,
-                        buf.len() as libc::size_t,
-                    )
-                };
+            pending.extend_from_slice(&buf[..n]);
+
+            while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line[..end]).into_owned();
 
-                let n = if n >= 0 {
-                    n as usize
-                } else if n == -1 && get_errno() == libc::EAGAIN || get_errno() == libc::EWOULDBLOCK
-                {
-                    std::thread::sleep(std::time::Duration::from_millis(100));
+                if skip {
+                    skip = false;
+                    last = Some((current_player.clone(), line));
                     continue;
-                } else {
-                    panic!("Failed to read from stdout.");
-                };
+                }
+
+                match options.debounce {
+                    Some(debounce) => held = Some((line, Instant::now() + debounce)),
+                    None => {
+                        if emit(&mut last, &current_player, line)?.is_break() {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
 
-                let s =
-                    String::from_utf8(buf[0..n].to_vec()).expect("Failed to convert to string.");
+        // Killed and reaped before the next one starts.
+        drop(child);
 
-                print!("{}", s);
+        // A line still held back is outdated by a switch, but otherwise how
+        // things were left.
+        if let Some((line, _)) = held.filter(|_| !switched) {
+            if emit(&mut last, &current_player, line)?.is_break() {
+                return Ok(());
             }
         }
+
+        // playerctl ended by itself, e.g. because it failed. Waiting a moment
+        // keeps one that fails right away from being restarted over and over.
+        if !switched {
+            thread::sleep(Duration::from_secs(1));
+        }
     }
 }
 
-fn get_errno() -> i32 {
-    unsafe { *libc::__errno_location() }
+/// Blocks until at least one of `fds` is readable or closed, and returns
+/// which ones are.
+fn wait_readable<const N: usize>(fds: [RawFd; N]) -> Result<[bool; N]> {
+    wait_readable_for(fds, None)
 }
 
-fn player(cache_path: &PathBuf) {
-    let current_player = get_current_player(cache_path);
+/// Like [`wait_readable`], but gives up after `timeout`, when none are.
+fn wait_readable_for<const N: usize>(
+    fds: [RawFd; N],
+    timeout: Option<Duration>,
+) -> Result<[bool; N]> {
+    let mut pollfds = fds.map(|fd| libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    });
+
+    // Rounded up, so that what is left of a millisecond is not spun away.
+    let timeout = match timeout {
+        Some(t) => t.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int,
+        None => -1,
+    };
+
+    loop {
+        let n = unsafe { libc::poll(pollfds.as_mut_ptr(), N as libc::nfds_t, timeout) };
+
+        if n >= 0 {
+            return Ok(pollfds.map(|p| p.revents & (libc::POLLIN | libc::POLLHUP) != 0));
+        }
+
+        let why = io::Error::last_os_error();
 
-    print!("{}", current_player);
+        if why.kind() != io::ErrorKind::Interrupted {
+            return Err(why.into());
+        }
+    }
 }
@@ -0,0 +1,815 @@
+use std::env;
+use std::fs::{self, create_dir_all, File};
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use tracing::{debug, trace};
+
+use crate::backend::Backend;
+use crate::config::{matches_pattern, Config};
+use crate::error::{Error, Result};
+use crate::hash::stable_hash;
+use crate::player::{Capability, PlaybackStatus, Player};
+use crate::watch::FileWatcher;
+
+/// The prefix KDE Connect gives the players it proxies from other devices,
+/// followed by an opaque id.
+const KDECONNECT_PREFIX: &str = "kdeconnect.";
+
+/// Keeps track of which player is "current" and hands out handles to players.
+///
+/// The current player is persisted in a file so that it is shared between
+/// invocations of the CLI and any program embedding this library.
+pub struct PlayerManager {
+    backend: Backend,
+    cache_path: PathBuf,
+    state_path: PathBuf,
+    /// Shared with the managers derived from this one, so that
+    /// [`set_config`](Self::set_config) reaches them too.
+    config: Arc<RwLock<Arc<Config>>>,
+    memory: Option<Arc<Mutex<Option<String>>>>,
+    target: Option<String>,
+}
+
+impl PlayerManager {
+    /// Creates a manager storing its state in the default state directory and
+    /// cached files such as cover art in the default cache directory.
+    pub fn new(backend: Backend) -> Result<Self> {
+        let cache_path = default_cache_path()?;
+        let state_path = default_state_path()?;
+
+        Ok(Self::with_cache_path(backend, cache_path).with_state_path(state_path))
+    }
+
+    /// Creates a manager keeping both its state and cached files in
+    /// `cache_path`.
+    pub fn with_cache_path(backend: Backend, cache_path: PathBuf) -> Self {
+        Self {
+            backend,
+            state_path: cache_path.clone(),
+            cache_path,
+            config: Arc::default(),
+            memory: None,
+            target: None,
+        }
+    }
+
+    /// Keeps the current player and other state in `state_path` instead.
+    pub fn with_state_path(mut self, state_path: PathBuf) -> Self {
+        self.state_path = state_path;
+        self
+    }
+
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = Arc::new(RwLock::new(Arc::new(config)));
+        self
+    }
+
+    /// Keeps the current player in memory instead of re-reading the cache file
+    /// on every access. Only appropriate when this manager is the sole writer
+    /// of the current player, as is the case for the daemon.
+    pub fn in_memory(mut self) -> Self {
+        self.memory = Some(Arc::new(Mutex::new(None)));
+        self
+    }
+
+    /// Returns a manager that treats `player` as the current player, leaving
+    /// the stored current player alone. Used for one-off `--player` commands.
+    pub fn targeting(&self, player: &str) -> PlayerManager {
+        let player = self.resolve(player);
+
+        Self {
+            backend: self.backend.clone(),
+            cache_path: self.cache_path.clone(),
+            state_path: self.state_path.clone(),
+            config: Arc::clone(&self.config),
+            memory: None,
+            target: Some(player),
+        }
+    }
+
+    /// Returns a manager that leaves players on other devices out when
+    /// switching, as with `switch.local_only`. Used for `switch --local-only`.
+    pub fn local_only(&self) -> PlayerManager {
+        let mut config = Config::clone(&self.config());
+        config.switch.local_only = true;
+
+        Self {
+            backend: self.backend.clone(),
+            cache_path: self.cache_path.clone(),
+            state_path: self.state_path.clone(),
+            config: Arc::new(RwLock::new(Arc::new(config))),
+            memory: self.memory.clone(),
+            target: self.target.clone(),
+        }
+    }
+
+    /// The player given to [`PlayerManager::targeting`], if any.
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    pub fn cache_path(&self) -> &Path {
+        &self.cache_path
+    }
+
+    pub fn state_path(&self) -> &Path {
+        &self.state_path
+    }
+
+    /// The config as it is now. Hold on to it rather than calling this
+    /// again for settings that belong together, as it may be replaced at any
+    /// time through [`set_config`](Self::set_config).
+    pub fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config.read().unwrap())
+    }
+
+    /// Replaces the config of this manager and of those derived from it,
+    /// e.g. when the daemon reloads the config file. The current player is
+    /// kept.
+    pub fn set_config(&self, config: Config) {
+        *self.config.write().unwrap() = Arc::new(config);
+    }
+
+    /// Lists the available players, leaving out ignored ones. With
+    /// `group_instances`, each application is listed once, without its
+    /// instance suffix.
+    pub fn players(&self) -> Result<Vec<String>> {
+        let instances = self.instances()?;
+
+        if !self.config().group_instances {
+            return Ok(instances);
+        }
+
+        let mut players: Vec<String> = Vec::new();
+
+        for name in instances.iter().map(|p| instance_group(p)) {
+            if !players.iter().any(|p| p == name) {
+                players.push(name.to_string());
+            }
+        }
+
+        Ok(players)
+    }
+
+    /// Lists every player instance, leaving out ignored ones.
+    fn instances(&self) -> Result<Vec<String>> {
+        let mut players = self.backend.list_players()?;
+
+        players.retain(|p| {
+            let ignored = self.config().is_ignored(p);
+
+            if ignored {
+                trace!("Ignoring {}", p);
+            }
+
+            !ignored
+        });
+
+        if !self.config().kdeconnect.exclude.is_empty() {
+            players.retain(|p| {
+                self.device_name(p)
+                    .is_none_or(|name| !self.config().kdeconnect.excludes(&name))
+            });
+        }
+
+        Ok(players)
+    }
+
+    /// Picks the instance behind the player `name`: with `group_instances`,
+    /// one that is playing if there is one, or else the first.
+    fn instance(&self, name: &str) -> String {
+        if !self.config().group_instances {
+            return name.to_string();
+        }
+
+        let instances: Vec<String> = self
+            .instances()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| instance_group(p) == name)
+            .collect();
+
+        if instances.iter().any(|p| p == name) {
+            return name.to_string();
+        }
+
+        instances
+            .iter()
+            .find(|p| {
+                Player::new(&self.backend, p.to_string()).status() == Ok(PlaybackStatus::Playing)
+            })
+            .or(instances.first())
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Returns the alias of `player` from the config, or else the name a
+    /// player on another device reports, or else its name.
+    pub fn display_name(&self, player: &str) -> String {
+        match self.config().alias(player) {
+            Some(alias) => alias.to_string(),
+            None => self
+                .device_name(player)
+                .unwrap_or_else(|| player.to_string()),
+        }
+    }
+
+    /// Whether `player` is on another device, proxied by KDE Connect.
+    pub fn is_remote(&self, player: &str) -> bool {
+        player.starts_with(KDECONNECT_PREFIX)
+    }
+
+    /// The name a player on another device is shown as. KDE Connect names
+    /// it after the device, as its bus name says nothing.
+    fn device_name(&self, player: &str) -> Option<String> {
+        if !self.is_remote(player) {
+            return None;
+        }
+
+        self.backend.identity(player).ok()
+    }
+
+    /// Turns an alias given by the user back into the name of the player it
+    /// stands for, and an instance into its application with
+    /// `group_instances`. Other names are returned unchanged.
+    fn resolve(&self, name: &str) -> String {
+        if self.config().group_instances && instance_group(name) != name {
+            return instance_group(name).to_string();
+        }
+
+        let config = self.config();
+
+        let is_alias = config
+            .aliases
+            .values()
+            .any(|a| a.eq_ignore_ascii_case(name));
+
+        if !is_alias {
+            return name.to_string();
+        }
+
+        let players = self.players().unwrap_or_default();
+
+        if players.iter().any(|p| p == name) {
+            return name.to_string();
+        }
+
+        players
+            .into_iter()
+            .find(|p| {
+                config
+                    .alias(p)
+                    .is_some_and(|a| a.eq_ignore_ascii_case(name))
+            })
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    pub fn player(&self, name: &str) -> Player<'_> {
+        Player::new(&self.backend, self.instance(name))
+    }
+
+    /// Returns the current player, selecting one first if none is stored yet.
+    pub fn current(&self) -> Result<Player<'_>> {
+        let name = self.current_name()?;
+
+        Ok(self.player(&name))
+    }
+
+    /// Returns the name stored as the current player without validating it.
+    pub fn current_name(&self) -> Result<String> {
+        if let Some(target) = &self.target {
+            return Ok(target.clone());
+        }
+
+        if let Some(memory) = &self.memory {
+            if let Some(current_player) = memory.lock().unwrap().as_ref() {
+                return Ok(current_player.clone());
+            }
+        }
+
+        let mut current_player = String::new();
+
+        let file_path = self.stored_player_file();
+
+        match File::open(&file_path) {
+            Ok(mut f) => match f.read_to_string(&mut current_player) {
+                Ok(_) => (),
+                Err(why) => {
+                    return Err(Error::Other(format!("Failed to read state file: {}", why)))
+                }
+            },
+            Err(why) => return Err(Error::Other(format!("Cannot open state file: {}", why))),
+        }
+
+        debug!(
+            "State file {} names {:?}",
+            file_path.display(),
+            current_player
+        );
+
+        Ok(current_player)
+    }
+
+    /// Makes sure the stored current player still exists, picking the
+    /// highest-priority available player otherwise.
+    pub fn init(&self) -> Result<()> {
+        if let Some(target) = &self.target {
+            let known = self.backend.list_players()?.iter().any(|p| {
+                p == target || self.config().group_instances && instance_group(p) == target
+            });
+
+            return match known {
+                true => Ok(()),
+                false => Err(Error::UnknownPlayer(target.clone())),
+            };
+        }
+
+        let _lock = self.lock_state()?;
+
+        let mut current_player = String::new();
+
+        if self.stored_player_file().exists() {
+            current_player = self.current_name()?;
+        }
+
+        let all_players = self.players()?;
+
+        if all_players.contains(&current_player) {
+            debug!("Keeping {}, which is still available", current_player);
+
+            // Rewriting an unchanged file would wake up everyone watching it.
+            if let Some(memory) = &self.memory {
+                *memory.lock().unwrap() = Some(current_player);
+            }
+
+            return Ok(());
+        }
+
+        debug!(
+            "{:?} is not among {:?}, picking another player",
+            current_player, all_players
+        );
+
+        match self.preferred_player(&all_players) {
+            Some(v) => self.store_current(v),
+            None => Err(Error::NoPlayers),
+        }
+    }
+
+    /// The current player, after [`init`](Self::init) has picked a new one
+    /// if the stored one has gone away. Fails while there are no players.
+    pub fn current_or_init(&self) -> Result<Player<'_>> {
+        self.init()?;
+        self.current()
+    }
+
+    /// Picks the available player matching the earliest entry of the
+    /// configured priority list, or the first available player if none match.
+    /// Pinned players on other devices come first.
+    fn preferred_player<'p>(&self, all_players: &'p [String]) -> Option<&'p String> {
+        let pinned = all_players.iter().find(|p| {
+            !self.config().kdeconnect.pin.is_empty()
+                && self
+                    .device_name(p)
+                    .is_some_and(|name| self.config().kdeconnect.pins(&name))
+        });
+
+        if let Some(player) = pinned {
+            debug!("Preferring {}, as its device is pinned", player);
+
+            return Some(player);
+        }
+
+        let config = self.config();
+
+        let prioritized = config.priority.iter().find_map(|wanted| {
+            all_players
+                .iter()
+                .find(|p| matches_pattern(wanted, p))
+                .map(|p| (wanted, p))
+        });
+
+        if let Some((wanted, player)) = prioritized {
+            debug!("Preferring {}, as it matches priority {:?}", player, wanted);
+
+            return Some(player);
+        }
+
+        let first = all_players.first();
+
+        if let Some(player) = first {
+            debug!(
+                "Preferring {}, as no player matches the priority list",
+                player
+            );
+        }
+
+        first
+    }
+
+    /// Makes `player` the current player. It may also be given by its alias.
+    pub fn switch_to(&self, player: &str) -> Result<()> {
+        let player = self.resolve(player);
+        let _lock = self.lock_state()?;
+
+        if !self.players()?.contains(&player) {
+            return Err(Error::UnknownPlayer(player));
+        }
+
+        self.store_current(&player)
+    }
+
+    /// Switches to a player that is currently playing.
+    ///
+    /// MPRIS does not tell when playback started, so players other than the
+    /// current one are preferred (they are most likely what just started), and
+    /// among those the one with the lowest position.
+    pub fn switch_to_playing(&self) -> Result<()> {
+        let _lock = self.lock_state()?;
+
+        let current_player = self.current_name().unwrap_or_default();
+
+        let target = self
+            .players()?
+            .into_iter()
+            .filter(|name| !(self.config().switch.local_only && self.is_remote(name)))
+            .filter_map(|name| {
+                let player = self.player(&name);
+
+                match player.status() {
+                    Ok(PlaybackStatus::Playing) => {
+                        let position = player.position().unwrap_or(i64::MAX);
+
+                        debug!("{} is playing, at {} µs", name, position);
+
+                        Some((name != current_player, position, name))
+                    }
+                    _ => None,
+                }
+            })
+            .min_by_key(|(other, position, _)| (!other, *position))
+            .map(|(_, _, name)| name);
+
+        match target {
+            Some(t) => {
+                debug!(
+                    "Picking {}, preferring players other than {:?} and then the lowest position",
+                    t, current_player
+                );
+
+                self.store_current(&t)
+            }
+            None => Err(Error::Other(String::from("No player is playing."))),
+        }
+    }
+
+    /// Switches to the `index`th player, counting from 1 in the order of
+    /// [`players`](Self::players).
+    pub fn switch_to_index(&self, index: usize) -> Result<()> {
+        let _lock = self.lock_state()?;
+
+        let all_players = self.players()?;
+
+        match index.checked_sub(1).and_then(|i| all_players.get(i)) {
+            Some(target) => self.store_current(target),
+            None => Err(Error::InvalidArgument(format!(
+                "There is no player number {} ({} available).",
+                index,
+                all_players.len()
+            ))),
+        }
+    }
+
+    pub fn switch_next(&self) -> Result<()> {
+        self.switch_by(1)
+    }
+
+    pub fn switch_previous(&self) -> Result<()> {
+        self.switch_by(-1)
+    }
+
+    fn switch_by(&self, step: isize) -> Result<()> {
+        let _lock = self.lock_state()?;
+
+        let mut all_players = self.players()?;
+
+        let current_player = if self.stored_player_file().exists() {
+            self.current_name()?
+        } else {
+            String::new()
+        };
+
+        // The current player stays in, as the starting point.
+        all_players.retain(|p| *p == current_player || self.is_switchable(p));
+
+        debug!(
+            "Stepping {} from {:?} through {:?}",
+            step, current_player, all_players
+        );
+
+        let line_count = all_players.len() as isize;
+
+        let target = match all_players.iter().position(|l| *l == current_player) {
+            Some(i) if self.config().switch.wrap => {
+                &all_players[(i as isize + step).rem_euclid(line_count) as usize]
+            }
+            Some(i) => &all_players[(i as isize + step).clamp(0, line_count - 1) as usize],
+            None => self
+                .preferred_player(&all_players)
+                .ok_or(Error::NoPlayers)?,
+        };
+
+        self.store_current(target)
+    }
+
+    /// Whether cycling may land on `player`, see `switch.skip_uncontrollable`,
+    /// `switch.skip_stopped` and `switch.local_only`.
+    fn is_switchable(&self, name: &str) -> bool {
+        let config = &self.config().switch;
+
+        if config.local_only && self.is_remote(name) {
+            debug!("Skipping {}, as it is on another device", name);
+
+            return false;
+        }
+
+        let player = self.player(name);
+
+        if config.skip_uncontrollable && !player.can(Capability::Control) {
+            debug!("Skipping {}, as it cannot be controlled", name);
+
+            return false;
+        }
+
+        if config.skip_stopped
+            && !matches!(
+                player.status(),
+                Ok(PlaybackStatus::Playing | PlaybackStatus::Paused)
+            )
+        {
+            debug!("Skipping {}, as it is stopped", name);
+
+            return false;
+        }
+
+        true
+    }
+
+    /// Whether `player` was muted through [`mute`](Self::mute) and has not
+    /// been turned up since.
+    pub fn is_muted(&self, player: &Player) -> Result<bool> {
+        match player.muted() {
+            Err(Error::Unsupported { .. }) => (),
+            muted => return muted,
+        }
+
+        Ok(self.muted_volume_file(player.name()).exists() && player.volume()? == 0.0)
+    }
+
+    /// Mutes `player`, by itself if it can, or else by setting its volume to
+    /// zero and remembering the previous volume so that
+    /// [`unmute`](Self::unmute) can restore it.
+    pub fn mute(&self, player: &Player) -> Result<()> {
+        match player.set_muted(true) {
+            Err(Error::Unsupported { .. }) => (),
+            muted => return muted,
+        }
+
+        if self.is_muted(player)? {
+            return Ok(());
+        }
+
+        let volume = player.volume()?;
+        let file_path = self.muted_volume_file(player.name());
+
+        let written = file_path
+            .parent()
+            .map_or(Ok(()), create_dir_all)
+            .and_then(|_| fs::write(&file_path, volume.to_string()));
+
+        if let Err(why) = written {
+            return Err(Error::Other(format!(
+                "Failed to write muted volume: {}",
+                why
+            )));
+        }
+
+        player.set_volume(0.0)
+    }
+
+    /// Restores the volume `player` had before it was muted. Does nothing if
+    /// it is not muted.
+    pub fn unmute(&self, player: &Player) -> Result<()> {
+        match player.set_muted(false) {
+            Err(Error::Unsupported { .. }) => (),
+            unmuted => return unmuted,
+        }
+
+        if !self.is_muted(player)? {
+            return Ok(());
+        }
+
+        let file_path = self.muted_volume_file(player.name());
+
+        let volume = match fs::read_to_string(&file_path) {
+            Ok(v) => v.trim().parse::<f64>().unwrap_or(1.0),
+            Err(why) => {
+                return Err(Error::Other(format!(
+                    "Failed to read muted volume: {}",
+                    why
+                )))
+            }
+        };
+
+        player.set_volume(volume)?;
+
+        let _ = fs::remove_file(file_path);
+
+        Ok(())
+    }
+
+    fn muted_volume_file(&self, player: &str) -> PathBuf {
+        self.state_path.join("muted").join(player)
+    }
+
+    /// Starts watching for the current player to be changed, by this or any
+    /// other process sharing the state directory.
+    pub fn watch_current(&self) -> Result<FileWatcher> {
+        FileWatcher::new(&self.current_player_file())
+    }
+
+    fn current_player_file(&self) -> PathBuf {
+        match session_key() {
+            Some(key) if !self.config().shared_state => {
+                self.state_path.join(format!("currentplayer-{}", key))
+            }
+            _ => self.state_path.join("currentplayer"),
+        }
+    }
+
+    /// The file the current player is read from: that of the session or, until
+    /// the session has stored one of its own, the one shared by all sessions,
+    /// which is also where older versions kept it.
+    fn stored_player_file(&self) -> PathBuf {
+        let file_path = self.current_player_file();
+        let shared = self.state_path.join("currentplayer");
+
+        match file_path.exists() || !shared.exists() {
+            true => file_path,
+            false => shared,
+        }
+    }
+
+    /// Takes an exclusive lock on the state directory, held until the returned
+    /// file is dropped, so that concurrent invocations reading and then
+    /// rewriting the current player don't undo each other's switches.
+    fn lock_state(&self) -> Result<File> {
+        let lock_error = |why| Error::Other(format!("Failed to lock state directory: {}", why));
+
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.state_path.join("lock"))
+            .map_err(lock_error)?;
+
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                return Ok(file);
+            }
+
+            let why = std::io::Error::last_os_error();
+
+            if why.kind() != std::io::ErrorKind::Interrupted {
+                return Err(lock_error(why));
+            }
+        }
+    }
+
+    /// Replaces the current player file through a rename, so readers never
+    /// see a truncated or half-written name.
+    fn store_current(&self, player: &str) -> Result<()> {
+        debug!("Switching to {}", player);
+
+        if let Some(memory) = &self.memory {
+            *memory.lock().unwrap() = Some(player.to_string());
+        }
+
+        let file_path = self.current_player_file();
+        let temporary = self
+            .state_path
+            .join(format!(".currentplayer.{}.part", std::process::id()));
+
+        let written = File::create(&temporary)
+            .and_then(|mut f| f.write_all(player.as_bytes()))
+            .and_then(|_| fs::rename(&temporary, &file_path));
+
+        if let Err(why) = written {
+            let _ = fs::remove_file(&temporary);
+
+            return Err(Error::Other(format!("Failed to write state file: {}", why)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns `$MULTIPLAYERCTL_CACHE_DIR`, or else `$XDG_CACHE_HOME/multiplayerctl`
+/// (or `~/.cache/multiplayerctl`), creating it if needed.
+pub fn default_cache_path() -> Result<PathBuf> {
+    let cache_path = match env::var_os("MULTIPLAYERCTL_CACHE_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => xdg_dir("XDG_CACHE_HOME", ".cache")?.join("multiplayerctl"),
+    };
+
+    match create_dir_all(&cache_path) {
+        Ok(_) => Ok(cache_path),
+        Err(why) => Err(Error::Other(format!(
+            "Failed to create cache directory: {}",
+            why
+        ))),
+    }
+}
+
+/// Returns `$MULTIPLAYERCTL_STATE_DIR`, or else `$XDG_STATE_HOME/multiplayerctl`
+/// (or `~/.local/state/multiplayerctl`), creating it if needed.
+///
+/// Older versions kept their state in the cache directory, where cache
+/// cleaners would wipe it. Anything left there is moved over the first time.
+pub fn default_state_path() -> Result<PathBuf> {
+    let chosen = env::var_os("MULTIPLAYERCTL_STATE_DIR");
+
+    let state_path = match &chosen {
+        Some(dir) => PathBuf::from(dir),
+        None => xdg_dir("XDG_STATE_HOME", ".local/state")?.join("multiplayerctl"),
+    };
+
+    if let Err(why) = create_dir_all(&state_path) {
+        return Err(Error::Other(format!(
+            "Failed to create state directory: {}",
+            why
+        )));
+    }
+
+    // Only the default location can have a predecessor.
+    if chosen.is_some() {
+        return Ok(state_path);
+    }
+
+    if let Ok(cache_path) = xdg_dir("XDG_CACHE_HOME", ".cache").map(|p| p.join("multiplayerctl")) {
+        for name in ["currentplayer", "muted"] {
+            let (old, new) = (cache_path.join(name), state_path.join(name));
+
+            if old.exists() && !new.exists() {
+                let _ = fs::rename(old, new);
+            }
+        }
+    }
+
+    Ok(state_path)
+}
+
+/// Returns `$XDG_RUNTIME_DIR/multiplayerctl`, the directory for files that
+/// only make sense while the session lasts, such as the daemon's socket. Does
+/// not create it.
+pub fn default_runtime_path() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("multiplayerctl"))
+}
+
+/// Strips the instance suffix browsers and some players add to tell their
+/// windows apart, e.g. `firefox.instance_1_23` becomes `firefox`.
+fn instance_group(player: &str) -> &str {
+    player
+        .split_once(".instance")
+        .map_or(player, |(application, _)| application)
+}
+
+/// Identifies the session this process belongs to: the D-Bus session bus it
+/// talks to or, failing that, the login session. Sessions with a bus of their
+/// own see different players, so they get their own current player.
+pub fn session_key() -> Option<String> {
+    let session = env::var_os("DBUS_SESSION_BUS_ADDRESS")
+        .or_else(|| env::var_os("XDG_SESSION_ID"))
+        .filter(|s| !s.is_empty())?;
+
+    Some(format!("{:016x}", stable_hash([session.as_bytes()])))
+}
+
+/// Returns the directory in `var`, or `fallback` relative to `$HOME`.
+fn xdg_dir(var: &str, fallback: &str) -> Result<PathBuf> {
+    match env::var_os(var) {
+        Some(v) => Ok(PathBuf::from(v)),
+        None => match env::var_os("HOME") {
+            Some(home) => Ok(PathBuf::from(home).join(fallback)),
+            None => Err(Error::Other(String::from("No $HOME defined!"))),
+        },
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use evdev::{Device, EventSummary, KeyCode};
+use multiplayerctl::{PlayerManager, Result};
+
+use crate::daemon;
+
+/// How often `/dev/input` is looked at for new devices, such as a headset
+/// that just connected.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Key values in events; repeats (`2`) and releases (`0`) are ignored.
+const PRESSED: i32 = 1;
+
+/// The keys handled, with the command each one runs.
+const KEYS: &[(KeyCode, &str)] = &[
+    (KeyCode::KEY_PLAYPAUSE, "toggle"),
+    (KeyCode::KEY_PLAYCD, "play"),
+    (KeyCode::KEY_PLAY, "play"),
+    (KeyCode::KEY_PAUSECD, "pause"),
+    (KeyCode::KEY_STOPCD, "stop"),
+    (KeyCode::KEY_NEXTSONG, "next"),
+    (KeyCode::KEY_PREVIOUSSONG, "previous"),
+];
+
+/// Grabs input devices with media keys and runs the matching command on the
+/// current player whenever one is pressed. Never returns.
+///
+/// Only devices without letter keys are taken, such as the separate
+/// "Consumer Control" device most keyboards and headsets have for their
+/// media keys, since grabbing a keyboard would take all typing with it.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let grabbed: Arc<Mutex<HashSet<PathBuf>>> = Arc::default();
+    let mut warned = false;
+
+    loop {
+        for (path, device) in evdev::enumerate() {
+            if !has_media_keys(&device) || grabbed.lock().unwrap().contains(&path) {
+                continue;
+            }
+
+            grabbed.lock().unwrap().insert(path.clone());
+
+            let manager = Arc::clone(&manager);
+            let grabbed = Arc::clone(&grabbed);
+
+            thread::spawn(move || match listen(&manager, device) {
+                // Gone; it may be back later.
+                Ok(()) => {
+                    grabbed.lock().unwrap().remove(&path);
+                }
+                // Taken by someone else, which is not retried.
+                Err(why) => eprintln!("Failed to grab {}: {}", path.display(), why),
+            });
+        }
+
+        if !warned && grabbed.lock().unwrap().is_empty() {
+            eprintln!(
+                "No media keys found. Reading them needs access to /dev/input, usually by being in the `input` group."
+            );
+            warned = true;
+        }
+
+        thread::sleep(RESCAN_INTERVAL);
+    }
+}
+
+fn has_media_keys(device: &Device) -> bool {
+    let keys = match device.supported_keys() {
+        Some(k) => k,
+        None => return false,
+    };
+
+    !keys.contains(KeyCode::KEY_A) && KEYS.iter().any(|(key, _)| keys.contains(*key))
+}
+
+/// Runs commands for the keys pressed on `device` until it goes away.
+/// Fails only if the device cannot be grabbed.
+fn listen(manager: &PlayerManager, mut device: Device) -> io::Result<()> {
+    device.grab()?;
+
+    loop {
+        let events = match device.fetch_events() {
+            Ok(e) => e,
+            Err(_) => return Ok(()),
+        };
+
+        for event in events {
+            let command = match event.destructure() {
+                EventSummary::Key(_, key, PRESSED) => KEYS.iter().find(|(k, _)| *k == key),
+                _ => None,
+            };
+
+            if let Some((_, command)) = command {
+                if let Err(why) = daemon::handle(manager, vec![command.to_string()]) {
+                    eprintln!("{}", why);
+                }
+            }
+        }
+    }
+}
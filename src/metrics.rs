@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use clap::CommandFactory;
+use multiplayerctl::{Error, PlaybackStatus, PlayerManager, Result};
+use tiny_http::{Header, Response, Server};
+
+use crate::{follow, Opt};
+
+/// Upper bounds of the command latency histogram buckets, in seconds.
+const BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+const STATUSES: &[PlaybackStatus] = &[
+    PlaybackStatus::Playing,
+    PlaybackStatus::Paused,
+    PlaybackStatus::Stopped,
+];
+
+/// What the daemon counts for `/metrics`.
+pub struct Metrics {
+    /// The subcommand names, to tell commands from their arguments.
+    commands: Vec<String>,
+    counters: Mutex<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    switches: u64,
+    track_changes: BTreeMap<String, u64>,
+    latencies: BTreeMap<String, Latency>,
+}
+
+#[derive(Default)]
+struct Latency {
+    /// How many commands took at most the matching entry of [`BUCKETS`].
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+    errors: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            commands: Opt::command()
+                .get_subcommands()
+                .map(|c| c.get_name().to_string())
+                .collect(),
+            counters: Mutex::default(),
+        }
+    }
+
+    /// Runs the command given by `args` and records how long it took.
+    pub fn time<T, E>(
+        &self,
+        args: &[String],
+        run: impl FnOnce() -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        let started = Instant::now();
+        let result = run();
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let command = args
+            .iter()
+            .find(|a| self.commands.contains(a))
+            .cloned()
+            .unwrap_or_else(|| String::from("unknown"));
+
+        let mut counters = self.counters.lock().unwrap();
+        let latency = counters.latencies.entry(command).or_default();
+
+        latency.buckets.resize(BUCKETS.len(), 0);
+
+        for (count, bound) in latency.buckets.iter_mut().zip(BUCKETS) {
+            if elapsed <= *bound {
+                *count += 1;
+            }
+        }
+
+        latency.count += 1;
+        latency.sum += elapsed;
+
+        if result.is_err() {
+            latency.errors += 1;
+        }
+
+        result
+    }
+
+    /// The metrics in the Prometheus text format.
+    fn render(&self, manager: &PlayerManager) -> String {
+        let players = manager.players().unwrap_or_default();
+        let current = manager.current_name().ok();
+        let counters = self.counters.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out, "players", "gauge", "Number of players.");
+        let _ = writeln!(out, "multiplayerctl_players {}", players.len());
+
+        header(
+            &mut out,
+            "player_status",
+            "gauge",
+            "1 for the playback status each player is in.",
+        );
+
+        for name in &players {
+            let status = manager.player(name).status().ok();
+
+            for s in STATUSES {
+                let _ = writeln!(
+                    out,
+                    "multiplayerctl_player_status{{player=\"{}\",status=\"{}\"}} {}",
+                    escape(name),
+                    s,
+                    u8::from(status == Some(*s))
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "current_player",
+            "gauge",
+            "1 for the current player.",
+        );
+
+        for name in &players {
+            let _ = writeln!(
+                out,
+                "multiplayerctl_current_player{{player=\"{}\"}} {}",
+                escape(name),
+                u8::from(current.as_deref() == Some(name))
+            );
+        }
+
+        header(
+            &mut out,
+            "track_changes_total",
+            "counter",
+            "Tracks started by each player.",
+        );
+
+        for (name, count) in &counters.track_changes {
+            let _ = writeln!(
+                out,
+                "multiplayerctl_track_changes_total{{player=\"{}\"}} {}",
+                escape(name),
+                count
+            );
+        }
+
+        header(
+            &mut out,
+            "switches_total",
+            "counter",
+            "Changes of the current player.",
+        );
+        let _ = writeln!(out, "multiplayerctl_switches_total {}", counters.switches);
+
+        header(
+            &mut out,
+            "command_duration_seconds",
+            "histogram",
+            "How long commands sent to the daemon took.",
+        );
+
+        for (command, latency) in &counters.latencies {
+            for (count, bound) in latency.buckets.iter().zip(BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "multiplayerctl_command_duration_seconds_bucket{{command=\"{}\",le=\"{}\"}} {}",
+                    command, bound, count
+                );
+            }
+
+            let _ = writeln!(
+                out,
+                "multiplayerctl_command_duration_seconds_bucket{{command=\"{}\",le=\"+Inf\"}} {}",
+                command, latency.count
+            );
+            let _ = writeln!(
+                out,
+                "multiplayerctl_command_duration_seconds_sum{{command=\"{}\"}} {}",
+                command, latency.sum
+            );
+            let _ = writeln!(
+                out,
+                "multiplayerctl_command_duration_seconds_count{{command=\"{}\"}} {}",
+                command, latency.count
+            );
+        }
+
+        header(
+            &mut out,
+            "command_errors_total",
+            "counter",
+            "Commands sent to the daemon that failed.",
+        );
+
+        for (command, latency) in &counters.latencies {
+            let _ = writeln!(
+                out,
+                "multiplayerctl_command_errors_total{{command=\"{}\"}} {}",
+                command, latency.errors
+            );
+        }
+
+        out
+    }
+}
+
+/// Serves `/metrics` at `bind`, counting track changes and switches, until
+/// watching the players fails.
+pub fn run(manager: Arc<PlayerManager>, metrics: Arc<Metrics>, bind: &str) -> Result<()> {
+    let server = Server::http(bind)
+        .map_err(|why| Error::Other(format!("Failed to serve metrics on {}: {}", bind, why)))?;
+
+    let scraped = Arc::clone(&manager);
+    let rendered = Arc::clone(&metrics);
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = match request.url() {
+                "/metrics" => Response::from_string(rendered.render(&scraped)).with_header(
+                    Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap(),
+                ),
+                _ => Response::from_string("Not found.").with_status_code(404),
+            };
+
+            let _ = request.respond(response);
+        }
+    });
+
+    let mut tracks: HashMap<String, String> = HashMap::new();
+    // Unknown until the first look.
+    let mut current: Option<Option<String>> = None;
+
+    follow::watch(
+        manager,
+        |_| None,
+        |manager| {
+            let mut counters = metrics.counters.lock().unwrap();
+
+            for name in manager.players().unwrap_or_default() {
+                let metadata = manager.player(&name).metadata().unwrap_or_default();
+
+                let track = match (metadata.track_id(), metadata.title()) {
+                    (None, None) => continue,
+                    _ => format!(
+                        "{:?} {:?} {:?}",
+                        metadata.track_id(),
+                        metadata.title(),
+                        metadata.artists()
+                    ),
+                };
+
+                if tracks.get(&name) != Some(&track) {
+                    *counters.track_changes.entry(name.clone()).or_default() += 1;
+                    tracks.insert(name, track);
+                }
+            }
+
+            let new_current = manager.current_name().ok();
+
+            if current.as_ref().is_some_and(|c| *c != new_current) {
+                counters.switches += 1;
+            }
+
+            current = Some(new_current);
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP multiplayerctl_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE multiplayerctl_{} {}", name, kind);
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,518 @@
+//! A [`PlayerBackend`] for [MPD](https://www.musicpd.org), spoken to over its
+//! own protocol, so that it is a player without an MPRIS bridge.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::PlayerBackend;
+use crate::config::MpdConfig;
+use crate::error::{Error, Result};
+use crate::player::{Change, LoopStatus, Metadata, MetadataValue, PlaybackStatus};
+
+/// How long to wait for the server, so that a missing one does not hold up
+/// listing the other players.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait before connecting again when the server went away.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The prefix of the track ids made up from MPD's song ids.
+const TRACK_ID_PREFIX: &str = "/org/musicpd/song/";
+
+/// An MPD server, listed as the single player named in its config.
+pub struct Mpd {
+    config: MpdConfig,
+}
+
+/// Both kinds of connection MPD listens on.
+trait Stream: BufRead + Send {
+    fn write_line(&mut self, line: &str) -> std::io::Result<()>;
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()>;
+}
+
+impl Stream for BufReader<TcpStream> {
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        writeln!(self.get_mut(), "{}", line)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.get_ref().set_read_timeout(timeout)
+    }
+}
+
+impl Stream for BufReader<UnixStream> {
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        writeln!(self.get_mut(), "{}", line)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.get_ref().set_read_timeout(timeout)
+    }
+}
+
+/// A connection to the server, over which commands are sent one at a time.
+struct Connection {
+    name: String,
+    stream: Box<dyn Stream>,
+}
+
+impl Connection {
+    /// Sends `command` and reads the `key: value` pairs of the answer, in
+    /// order, as some keys come more than once.
+    fn send(&mut self, command: &str) -> Result<Vec<(String, String)>> {
+        self.stream
+            .write_line(command)
+            .map_err(|why| Error::player_command(&self.name, why))?;
+
+        let mut pairs = Vec::new();
+
+        loop {
+            let mut line = String::new();
+
+            match self.stream.read_line(&mut line) {
+                Ok(0) => {
+                    return Err(Error::player_command(
+                        &self.name,
+                        "MPD closed the connection",
+                    ))
+                }
+                Ok(_) => (),
+                Err(why) => return Err(Error::player_command(&self.name, why)),
+            }
+
+            let line = line.trim_end();
+
+            if line == "OK" {
+                return Ok(pairs);
+            }
+
+            // e.g. `ACK [50@0] {play} song doesn't exist: "7"`
+            if let Some(ack) = line.strip_prefix("ACK ") {
+                let message = ack.split_once("} ").map_or(ack, |(_, message)| message);
+
+                return Err(Error::player_command(&self.name, message));
+            }
+
+            if let Some((key, value)) = line.split_once(": ") {
+                pairs.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+}
+
+impl Mpd {
+    pub fn new(config: MpdConfig) -> Self {
+        Self { config }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let unreachable = |why: std::io::Error| {
+            Error::UnknownPlayer(format!(
+                "{} ({}: {})",
+                self.config.name,
+                self.address(),
+                why
+            ))
+        };
+
+        let stream: Box<dyn Stream> = match self.config.host.starts_with('/') {
+            true => {
+                let stream = UnixStream::connect(&self.config.host).map_err(unreachable)?;
+
+                Box::new(BufReader::new(stream))
+            }
+            false => {
+                let address = (self.config.host.as_str(), self.config.port)
+                    .to_socket_addrs()
+                    .map_err(unreachable)?
+                    .next()
+                    .ok_or_else(|| {
+                        unreachable(std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            "no address",
+                        ))
+                    })?;
+
+                let stream =
+                    TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(unreachable)?;
+
+                Box::new(BufReader::new(stream))
+            }
+        };
+
+        stream
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(unreachable)?;
+
+        let mut connection = Connection {
+            name: self.config.name.clone(),
+            stream,
+        };
+
+        let mut greeting = String::new();
+
+        connection
+            .stream
+            .read_line(&mut greeting)
+            .map_err(unreachable)?;
+
+        if !greeting.starts_with("OK MPD") {
+            return Err(Error::player_command(
+                &self.config.name,
+                format!("{} is not an MPD server", self.address()),
+            ));
+        }
+
+        if let Some(password) = &self.config.password {
+            connection.send(&format!("password {}", quote(password)))?;
+        }
+
+        Ok(connection)
+    }
+
+    fn address(&self) -> String {
+        match self.config.host.starts_with('/') {
+            true => self.config.host.clone(),
+            false => format!("{}:{}", self.config.host, self.config.port),
+        }
+    }
+
+    /// Runs a single command on a new connection.
+    fn send(&self, player: &str, command: &str) -> Result<Vec<(String, String)>> {
+        self.check(player)?;
+        self.connect()?.send(command)
+    }
+
+    fn check(&self, player: &str) -> Result<()> {
+        match self.owns(player) {
+            true => Ok(()),
+            false => Err(Error::UnknownPlayer(player.to_string())),
+        }
+    }
+
+    /// The answer to `status`, where each key comes once.
+    fn status(&self, player: &str) -> Result<BTreeMap<String, String>> {
+        Ok(self.send(player, "status")?.into_iter().collect())
+    }
+
+    /// Turns the answer to `currentsong` into MPRIS metadata.
+    fn to_metadata(&self, pairs: Vec<(String, String)>) -> Metadata {
+        let mut values = BTreeMap::new();
+        let mut artists = Vec::new();
+        let mut album_artists = Vec::new();
+        let mut file = None;
+        let mut name = None;
+
+        for (key, value) in pairs {
+            match key.as_str() {
+                "file" => file = Some(value),
+                "Id" => {
+                    values.insert(
+                        String::from("mpris:trackid"),
+                        MetadataValue::Text(format!("{}{}", TRACK_ID_PREFIX, value)),
+                    );
+                }
+                "Title" => {
+                    values.insert(String::from("xesam:title"), MetadataValue::Text(value));
+                }
+                "Name" => name = Some(value),
+                "Artist" => artists.push(value),
+                "AlbumArtist" => album_artists.push(value),
+                "Album" => {
+                    values.insert(String::from("xesam:album"), MetadataValue::Text(value));
+                }
+                // Sometimes written as `3/12`.
+                "Track" => {
+                    if let Ok(number) = value.split('/').next().unwrap_or_default().parse() {
+                        values.insert(
+                            String::from("xesam:trackNumber"),
+                            MetadataValue::Integer(number),
+                        );
+                    }
+                }
+                "duration" => {
+                    if let Ok(seconds) = value.parse::<f64>() {
+                        values.insert(
+                            String::from("mpris:length"),
+                            MetadataValue::Integer((seconds * 1_000_000.0) as i64),
+                        );
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if !artists.is_empty() {
+            values.insert(String::from("xesam:artist"), MetadataValue::List(artists));
+        }
+
+        if !album_artists.is_empty() {
+            values.insert(
+                String::from("xesam:albumArtist"),
+                MetadataValue::List(album_artists),
+            );
+        }
+
+        if let Some(file) = &file {
+            // Streams are already URLs; songs are relative to the music
+            // directory.
+            let url = match (file.contains("://"), &self.config.music_directory) {
+                (true, _) => Some(file.clone()),
+                (false, Some(directory)) => Some(format!(
+                    "file://{}",
+                    percent_encode(&directory.join(file).to_string_lossy())
+                )),
+                (false, None) => None,
+            };
+
+            if let Some(url) = url {
+                values.insert(String::from("xesam:url"), MetadataValue::Text(url));
+            }
+        }
+
+        // Radio streams only have the station's name, and untagged files
+        // only their file name.
+        if !values.contains_key("xesam:title") {
+            let title = name.or_else(|| {
+                file.as_deref()
+                    .and_then(|f| f.rsplit('/').next())
+                    .map(String::from)
+            });
+
+            if let Some(title) = title {
+                values.insert(String::from("xesam:title"), MetadataValue::Text(title));
+            }
+        }
+
+        Metadata::new(values)
+    }
+
+    /// Waits for changes on `connection` until it fails.
+    fn idle(&self, connection: &mut Connection, f: &mut dyn FnMut(&str, Change)) -> Result<()> {
+        connection
+            .stream
+            .set_read_timeout(None)
+            .map_err(|why| Error::player_command(&self.config.name, why))?;
+
+        loop {
+            let changed = connection.send("idle player mixer options playlist")?;
+
+            if !changed.is_empty() {
+                f(&self.config.name, Change::Properties);
+            }
+        }
+    }
+}
+
+impl PlayerBackend for Mpd {
+    fn owns(&self, player: &str) -> bool {
+        player == self.config.name
+    }
+
+    fn list_players(&self) -> Result<Vec<String>> {
+        self.connect()?;
+
+        Ok(vec![self.config.name.clone()])
+    }
+
+    fn playback_status(&self, player: &str) -> Result<PlaybackStatus> {
+        let status = self.status(player)?;
+
+        Ok(match status.get("state").map(String::as_str) {
+            Some("play") => PlaybackStatus::Playing,
+            Some("pause") => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Stopped,
+        })
+    }
+
+    fn metadata(&self, player: &str) -> Result<Metadata> {
+        let song = self.send(player, "currentsong")?;
+
+        Ok(self.to_metadata(song))
+    }
+
+    fn player_command(&self, player: &str, command: &str) -> Result<()> {
+        let command = match command {
+            "play" => match self.playback_status(player)? {
+                PlaybackStatus::Paused => "pause 0",
+                _ => "play",
+            },
+            "pause" => "pause 1",
+            "play-pause" => match self.playback_status(player)? {
+                PlaybackStatus::Playing => "pause 1",
+                PlaybackStatus::Paused => "pause 0",
+                PlaybackStatus::Stopped => "play",
+            },
+            "next" => "next",
+            "previous" => "previous",
+            "stop" => "stop",
+            other => {
+                return Err(Error::InvalidArgument(format!(
+                    "Unknown player command: {}",
+                    other
+                )))
+            }
+        };
+
+        self.send(player, command).map(|_| ())
+    }
+
+    fn open_uri(&self, player: &str, uri: &str) -> Result<()> {
+        let mut connection = self.check(player).and_then(|_| self.connect())?;
+
+        let added = connection.send(&format!("addid {}", quote(uri)))?;
+
+        let id = added
+            .into_iter()
+            .find(|(key, _)| key == "Id")
+            .map(|(_, id)| id)
+            .ok_or_else(|| Error::player_command(player, "MPD did not add the track"))?;
+
+        connection.send(&format!("playid {}", id)).map(|_| ())
+    }
+
+    fn volume(&self, player: &str) -> Result<f64> {
+        let status = self.status(player)?;
+
+        // Without a mixer, MPD reports -1 or leaves it out.
+        match status.get("volume").and_then(|v| v.parse::<f64>().ok()) {
+            Some(volume) if volume >= 0.0 => Ok(volume / 100.0),
+            _ => Err(Error::Unsupported {
+                player: player.to_string(),
+                action: String::from("report its volume"),
+            }),
+        }
+    }
+
+    fn set_volume(&self, player: &str, volume: f64) -> Result<()> {
+        let volume = (volume * 100.0).round().clamp(0.0, 100.0);
+
+        self.send(player, &format!("setvol {}", volume)).map(|_| ())
+    }
+
+    fn loop_status(&self, player: &str) -> Result<LoopStatus> {
+        let status = self.status(player)?;
+        let on = |key: &str| status.get(key).map(String::as_str) == Some("1");
+
+        Ok(match (on("repeat"), on("single")) {
+            (true, true) => LoopStatus::Track,
+            (true, false) => LoopStatus::Playlist,
+            (false, _) => LoopStatus::None,
+        })
+    }
+
+    fn set_loop_status(&self, player: &str, status: LoopStatus) -> Result<()> {
+        let (repeat, single) = match status {
+            LoopStatus::None => (0, 0),
+            LoopStatus::Track => (1, 1),
+            LoopStatus::Playlist => (1, 0),
+        };
+
+        let mut connection = self.check(player).and_then(|_| self.connect())?;
+
+        connection.send(&format!("repeat {}", repeat))?;
+        connection.send(&format!("single {}", single)).map(|_| ())
+    }
+
+    fn shuffle(&self, player: &str) -> Result<bool> {
+        let status = self.status(player)?;
+
+        Ok(status.get("random").map(String::as_str) == Some("1"))
+    }
+
+    fn set_shuffle(&self, player: &str, shuffle: bool) -> Result<()> {
+        self.send(player, &format!("random {}", u8::from(shuffle)))
+            .map(|_| ())
+    }
+
+    fn position(&self, player: &str) -> Result<i64> {
+        let status = self.status(player)?;
+
+        status
+            .get("elapsed")
+            .and_then(|e| e.parse::<f64>().ok())
+            .map(|seconds| (seconds * 1_000_000.0) as i64)
+            .ok_or_else(|| Error::MissingMetadata {
+                player: player.to_string(),
+                field: String::from("position"),
+            })
+    }
+
+    fn set_position(&self, player: &str, position: i64) -> Result<()> {
+        let seconds = position.max(0) as f64 / 1_000_000.0;
+
+        self.send(player, &format!("seekcur {:.3}", seconds))
+            .map(|_| ())
+    }
+
+    fn seek(&self, player: &str, offset: i64) -> Result<()> {
+        let seconds = offset as f64 / 1_000_000.0;
+
+        self.send(player, &format!("seekcur {:+.3}", seconds))
+            .map(|_| ())
+    }
+
+    /// Idles on a connection of its own, connecting again while the server
+    /// is away. Never returns.
+    fn watch_changes(&self, f: &mut dyn FnMut(&str, Change)) -> Result<()> {
+        // Whether the server was there when last tried; `None` at first, so
+        // that a server already running does not count as appearing.
+        let mut reachable = None;
+
+        loop {
+            match self.connect() {
+                Ok(mut connection) => {
+                    if reachable == Some(false) {
+                        f(&self.config.name, Change::Appeared);
+                    }
+
+                    reachable = Some(true);
+
+                    let _ = self.idle(&mut connection, f);
+
+                    // Not straight away, in case MPD refuses to idle.
+                    thread::sleep(CONNECT_TIMEOUT);
+                }
+                Err(_) => {
+                    if reachable == Some(true) {
+                        f(&self.config.name, Change::Vanished);
+                    }
+
+                    reachable = Some(false);
+
+                    thread::sleep(RECONNECT_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+/// Quotes an argument, as MPD splits commands at spaces.
+fn quote(argument: &str) -> String {
+    format!(
+        "\"{}\"",
+        argument.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// Escapes what cannot appear in the path of a `file://` URL.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{proxy, Connection, MessageIterator, Proxy};
+use zbus::message::Type;
+use zbus::names::BusName;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{MatchRule, Message};
+
+use tracing::debug;
+
+use crate::player::{Change, MetadataValue};
+
+pub const BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+pub const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+pub const ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+pub const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+pub const TRACK_LIST_INTERFACE: &str = "org.mpris.MediaPlayer2.TrackList";
+/// The track id standing for no track, e.g. to add a track at the start of
+/// the track list.
+pub const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+/// The name of the player the daemon registers to mirror the current player.
+/// It is never listed, so that it does not end up controlling itself.
+pub const PROXY_PLAYER: &str = "multiplayerctl";
+
+/// Talks to MPRIS players directly over the session bus.
+#[derive(Clone)]
+pub struct Mpris {
+    connection: Connection,
+}
+
+impl Mpris {
+    pub fn new() -> zbus::Result<Self> {
+        Ok(Self {
+            connection: Connection::session()?,
+        })
+    }
+
+    /// Lists player names the same way `playerctl -l` does, i.e. with the
+    /// `org.mpris.MediaPlayer2.` prefix stripped.
+    pub fn list_players(&self) -> zbus::Result<Vec<String>> {
+        debug!("D-Bus org.freedesktop.DBus.ListNames");
+
+        let dbus = DBusProxy::new(&self.connection)?;
+
+        let mut players: Vec<String> = dbus
+            .list_names()?
+            .into_iter()
+            .filter_map(|name| name.strip_prefix(BUS_PREFIX).map(String::from))
+            .collect();
+
+        players.sort();
+
+        Ok(players)
+    }
+
+    /// Blocks forever, calling `f` with the player name and new status
+    /// whenever a player's `PlaybackStatus` changes.
+    pub fn watch_playback_status<F>(&self, mut f: F) -> zbus::Result<()>
+    where
+        F: FnMut(&str, &str),
+    {
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface("org.freedesktop.DBus.Properties")?
+            .member("PropertiesChanged")?
+            .path(OBJECT_PATH)?
+            .arg(0, PLAYER_INTERFACE)?
+            .build();
+
+        let dbus = DBusProxy::new(&self.connection)?;
+
+        for message in MessageIterator::for_match_rule(rule, &self.connection, None)? {
+            let message = message?;
+
+            let (_, changed, _) =
+                match message
+                    .body()
+                    .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+                {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+
+            let status = match changed
+                .get("PlaybackStatus")
+                .map(|v| String::try_from(v.clone()))
+            {
+                Some(Ok(s)) => s,
+                _ => continue,
+            };
+
+            if let Some(player) = self.signal_sender(&dbus, &message)? {
+                f(&player, &status);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Blocks forever, calling `f` with the player name and what happened
+    /// whenever one of the player's properties changes, it seeks, or it
+    /// appears or goes away.
+    pub fn watch_changes<F>(&self, mut f: F) -> zbus::Result<()>
+    where
+        F: FnMut(&str, Change),
+    {
+        let dbus = DBusProxy::new(&self.connection)?;
+        let messages = MessageIterator::from(&self.connection);
+
+        dbus.add_match_rule(
+            MatchRule::builder()
+                .msg_type(Type::Signal)
+                .interface("org.freedesktop.DBus.Properties")?
+                .member("PropertiesChanged")?
+                .path(OBJECT_PATH)?
+                .arg(0, PLAYER_INTERFACE)?
+                .build(),
+        )?;
+
+        dbus.add_match_rule(
+            MatchRule::builder()
+                .msg_type(Type::Signal)
+                .interface(PLAYER_INTERFACE)?
+                .member("Seeked")?
+                .path(OBJECT_PATH)?
+                .build(),
+        )?;
+
+        dbus.add_match_rule(
+            MatchRule::builder()
+                .msg_type(Type::Signal)
+                .sender("org.freedesktop.DBus")?
+                .member("NameOwnerChanged")?
+                .arg0ns(ROOT_INTERFACE)?
+                .build(),
+        )?;
+
+        for message in messages {
+            let message = message?;
+            let header = message.header();
+
+            let change = match header.member().map(|m| m.as_str()) {
+                Some("PropertiesChanged") => self
+                    .signal_sender(&dbus, &message)?
+                    .map(|player| (player, Change::Properties)),
+                Some("Seeked") => {
+                    let position = message.body().deserialize::<i64>().unwrap_or_default();
+
+                    self.signal_sender(&dbus, &message)?
+                        .map(|player| (player, Change::Seeked(position)))
+                }
+                Some("NameOwnerChanged") => message
+                    .body()
+                    .deserialize::<(String, String, String)>()
+                    .ok()
+                    .and_then(|(name, old_owner, new_owner)| {
+                        let change = match (old_owner.is_empty(), new_owner.is_empty()) {
+                            (true, _) => Change::Appeared,
+                            (_, true) => Change::Vanished,
+                            _ => Change::Properties,
+                        };
+
+                        name.strip_prefix(BUS_PREFIX)
+                            .map(|player| (player.to_string(), change))
+                    }),
+                _ => None,
+            };
+
+            if let Some((player, change)) = change {
+                f(&player, change);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the player that sent a signal. Signals come from the player's
+    /// unique name, so this looks for the well-known name it owns.
+    fn signal_sender(&self, dbus: &DBusProxy, message: &Message) -> zbus::Result<Option<String>> {
+        let header = message.header();
+
+        let sender = match header.sender() {
+            Some(s) => s.as_str(),
+            None => return Ok(None),
+        };
+
+        Ok(self.list_players()?.into_iter().find(|p| {
+            match BusName::try_from(format!("{}{}", BUS_PREFIX, p)) {
+                Ok(name) => dbus
+                    .get_name_owner(name)
+                    .is_ok_and(|owner| owner.as_str() == sender),
+                Err(_) => false,
+            }
+        }))
+    }
+
+    /// A proxy for `interface` on `player`, to call or access `member` on.
+    fn proxy(
+        &self,
+        player: &str,
+        interface: &'static str,
+        member: &str,
+    ) -> zbus::Result<Proxy<'static>> {
+        debug!(player, "D-Bus {}.{}", interface, member);
+
+        proxy::Builder::new(&self.connection)
+            .destination(format!("{}{}", BUS_PREFIX, player))?
+            .path(OBJECT_PATH)?
+            .interface(interface)?
+            .cache_properties(CacheProperties::No)
+            .build()
+    }
+
+    pub fn call(&self, player: &str, method: &str) -> zbus::Result<()> {
+        self.proxy(player, PLAYER_INTERFACE, method)?
+            .call::<_, _, ()>(method, &())
+    }
+
+    /// Calls a method of the root `org.mpris.MediaPlayer2` interface, such as `Raise`.
+    pub fn call_root(&self, player: &str, method: &str) -> zbus::Result<()> {
+        self.proxy(player, ROOT_INTERFACE, method)?
+            .call::<_, _, ()>(method, &())
+    }
+
+    pub fn open_uri(&self, player: &str, uri: &str) -> zbus::Result<()> {
+        self.proxy(player, PLAYER_INTERFACE, "OpenUri")?
+            .call::<_, _, ()>("OpenUri", &(uri,))
+    }
+
+    pub fn playback_status(&self, player: &str) -> zbus::Result<String> {
+        self.proxy(player, PLAYER_INTERFACE, "PlaybackStatus")?
+            .get_property("PlaybackStatus")
+    }
+
+    pub fn metadata(&self, player: &str) -> zbus::Result<HashMap<String, OwnedValue>> {
+        self.proxy(player, PLAYER_INTERFACE, "Metadata")?
+            .get_property("Metadata")
+    }
+
+    /// Reads one of the `Can*` properties, e.g. `CanSeek`.
+    pub fn capability(&self, player: &str, property: &str) -> zbus::Result<bool> {
+        self.proxy(player, PLAYER_INTERFACE, property)?
+            .get_property(property)
+    }
+
+    pub fn volume(&self, player: &str) -> zbus::Result<f64> {
+        self.proxy(player, PLAYER_INTERFACE, "Volume")?
+            .get_property("Volume")
+    }
+
+    /// The id of the process that owns the player's bus name.
+    pub fn process_id(&self, player: &str) -> zbus::Result<u32> {
+        let name = BusName::try_from(format!("{}{}", BUS_PREFIX, player))?;
+
+        Ok(DBusProxy::new(&self.connection)?.get_connection_unix_process_id(name)?)
+    }
+
+    pub fn set_volume(&self, player: &str, volume: f64) -> zbus::Result<()> {
+        self.proxy(player, PLAYER_INTERFACE, "Volume")?
+            .set_property("Volume", volume)?;
+
+        Ok(())
+    }
+
+    pub fn loop_status(&self, player: &str) -> zbus::Result<String> {
+        self.proxy(player, PLAYER_INTERFACE, "LoopStatus")?
+            .get_property("LoopStatus")
+    }
+
+    pub fn set_loop_status(&self, player: &str, status: &str) -> zbus::Result<()> {
+        self.proxy(player, PLAYER_INTERFACE, "LoopStatus")?
+            .set_property("LoopStatus", status)?;
+
+        Ok(())
+    }
+
+    pub fn shuffle(&self, player: &str) -> zbus::Result<bool> {
+        self.proxy(player, PLAYER_INTERFACE, "Shuffle")?
+            .get_property("Shuffle")
+    }
+
+    pub fn set_shuffle(&self, player: &str, shuffle: bool) -> zbus::Result<()> {
+        self.proxy(player, PLAYER_INTERFACE, "Shuffle")?
+            .set_property("Shuffle", shuffle)?;
+
+        Ok(())
+    }
+
+    /// Returns the playback position in microseconds.
+    pub fn position(&self, player: &str) -> zbus::Result<i64> {
+        self.proxy(player, PLAYER_INTERFACE, "Position")?
+            .get_property("Position")
+    }
+
+    /// Sets the absolute position in microseconds. MPRIS requires the current
+    /// track id for this, so it is read from the metadata first.
+    pub fn set_position(&self, player: &str, position: i64) -> zbus::Result<()> {
+        let metadata = self.metadata(player)?;
+
+        let track_id = match metadata.get("mpris:trackid") {
+            Some(v) => ObjectPath::try_from(v.try_clone()?)?,
+            None => {
+                return Err(zbus::Error::Failure(String::from(
+                    "Player did not report a track id.",
+                )))
+            }
+        };
+
+        self.proxy(player, PLAYER_INTERFACE, "SetPosition")?
+            .call::<_, _, ()>("SetPosition", &(track_id, position))
+    }
+
+    /// Seeks by a relative offset in microseconds.
+    pub fn seek(&self, player: &str, offset: i64) -> zbus::Result<()> {
+        self.proxy(player, PLAYER_INTERFACE, "Seek")?
+            .call::<_, _, ()>("Seek", &(offset,))
+    }
+
+    /// The player's `Identity`, the name it would like to be shown as.
+    pub fn identity(&self, player: &str) -> zbus::Result<String> {
+        self.proxy(player, ROOT_INTERFACE, "Identity")?
+            .get_property("Identity")
+    }
+
+    /// Whether the player implements the `TrackList` interface.
+    pub fn has_track_list(&self, player: &str) -> zbus::Result<bool> {
+        self.proxy(player, ROOT_INTERFACE, "HasTrackList")?
+            .get_property("HasTrackList")
+    }
+
+    /// Whether tracks can be added to and removed from the track list.
+    pub fn can_edit_tracks(&self, player: &str) -> zbus::Result<bool> {
+        self.proxy(player, TRACK_LIST_INTERFACE, "CanEditTracks")?
+            .get_property("CanEditTracks")
+    }
+
+    /// The ids of the tracks in the track list, in order.
+    pub fn tracks(&self, player: &str) -> zbus::Result<Vec<OwnedObjectPath>> {
+        self.proxy(player, TRACK_LIST_INTERFACE, "Tracks")?
+            .get_property("Tracks")
+    }
+
+    pub fn tracks_metadata(
+        &self,
+        player: &str,
+        tracks: &[OwnedObjectPath],
+    ) -> zbus::Result<Vec<HashMap<String, OwnedValue>>> {
+        self.proxy(player, TRACK_LIST_INTERFACE, "GetTracksMetadata")?
+            .call("GetTracksMetadata", &(tracks,))
+    }
+
+    /// Adds the track at `uri` after the track `after`, which may be
+    /// [`NO_TRACK`] to add it at the start.
+    pub fn add_track(&self, player: &str, uri: &str, after: &str) -> zbus::Result<()> {
+        self.proxy(player, TRACK_LIST_INTERFACE, "AddTrack")?
+            .call::<_, _, ()>("AddTrack", &(uri, ObjectPath::try_from(after)?, false))
+    }
+
+    pub fn remove_track(&self, player: &str, track: &str) -> zbus::Result<()> {
+        self.proxy(player, TRACK_LIST_INTERFACE, "RemoveTrack")?
+            .call::<_, _, ()>("RemoveTrack", &(ObjectPath::try_from(track)?,))
+    }
+
+    /// Skips to a track in the track list.
+    pub fn go_to(&self, player: &str, track: &str) -> zbus::Result<()> {
+        self.proxy(player, TRACK_LIST_INTERFACE, "GoTo")?
+            .call::<_, _, ()>("GoTo", &(ObjectPath::try_from(track)?,))
+    }
+}
+
+/// Converts a metadata variant into a [`MetadataValue`].
+pub fn metadata_value(value: &Value) -> MetadataValue {
+    match value {
+        Value::Str(s) => MetadataValue::Text(s.to_string()),
+        Value::ObjectPath(p) => MetadataValue::Text(p.to_string()),
+        Value::Bool(b) => MetadataValue::Bool(*b),
+        Value::U8(n) => MetadataValue::Integer(i64::from(*n)),
+        Value::I16(n) => MetadataValue::Integer(i64::from(*n)),
+        Value::U16(n) => MetadataValue::Integer(i64::from(*n)),
+        Value::I32(n) => MetadataValue::Integer(i64::from(*n)),
+        Value::U32(n) => MetadataValue::Integer(i64::from(*n)),
+        Value::I64(n) => MetadataValue::Integer(*n),
+        Value::U64(n) => match i64::try_from(*n) {
+            Ok(n) => MetadataValue::Integer(n),
+            Err(_) => MetadataValue::Text(n.to_string()),
+        },
+        Value::F64(n) => MetadataValue::Float(*n),
+        Value::Value(v) => metadata_value(v),
+        Value::Array(a) => {
+            MetadataValue::List(a.iter().map(|v| metadata_value(v).to_string()).collect())
+        }
+        other => MetadataValue::Text(format!("{:?}", other)),
+    }
+}
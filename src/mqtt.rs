@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::iter;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use multiplayerctl::config::MqttConfig;
+use multiplayerctl::{PlayerManager, Result};
+use rumqttc::{Client, Connection, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::{json, Map, Value};
+
+use crate::{daemon, follow};
+
+/// How long to wait before reconnecting to a broker that went away.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Publishes the current player to the `[mqtt]` broker, and runs commands
+/// sent to `<topic>/set/<command>`, until watching the players fails.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let config = match &manager.config().mqtt {
+        Some(c) => c.clone(),
+        None => return Ok(()),
+    };
+
+    let available = format!("{}/available", config.topic);
+
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(&available, "offline", QoS::AtLeastOnce, true));
+
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+
+    let (client, connection) = Client::new(options, 64);
+
+    let publisher = Publisher {
+        client,
+        published: Arc::new(Mutex::new(HashMap::new())),
+    };
+
+    publisher.publish(&available, String::from("online"));
+
+    let commands = publisher.clone();
+    let commanded = Arc::clone(&manager);
+    let broker = config.clone();
+
+    thread::spawn(move || serve(connection, &broker, &commands, &commanded));
+
+    let mut announced: Option<Vec<String>> = None;
+
+    follow::watch(
+        manager,
+        |_| None,
+        |manager| {
+            let players = manager.players().unwrap_or_default();
+
+            if config.discovery && announced.as_ref() != Some(&players) {
+                for (topic, payload) in discovery(&config, manager, &players) {
+                    publisher.publish(&topic, payload);
+                }
+
+                announced = Some(players.clone());
+            }
+
+            publisher.publish(
+                &format!("{}/players", config.topic),
+                json!(players).to_string(),
+            );
+
+            let fields = state(manager);
+
+            for (field, value) in &fields {
+                publisher.publish(
+                    &format!("{}/current/{}", config.topic, field),
+                    value.clone(),
+                );
+            }
+
+            let state: Map<String, Value> = fields
+                .into_iter()
+                .map(|(field, value)| (field.to_string(), json!(value)))
+                .collect();
+
+            publisher.publish(
+                &format!("{}/current/state", config.topic),
+                Value::Object(state).to_string(),
+            );
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+/// Publishes retained messages, skipping those the broker already has.
+#[derive(Clone)]
+struct Publisher {
+    client: Client,
+    /// The last payload published to each topic.
+    published: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Publisher {
+    fn publish(&self, topic: &str, payload: String) {
+        let mut published = self.published.lock().unwrap();
+
+        if published.get(topic) == Some(&payload) {
+            return;
+        }
+
+        // Never blocks; whatever does not make it is sent again on reconnecting.
+        let _ = self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, true, payload.clone());
+
+        published.insert(topic.to_string(), payload);
+    }
+
+    fn republish(&self) {
+        for (topic, payload) in self.published.lock().unwrap().iter() {
+            let _ = self
+                .client
+                .try_publish(topic, QoS::AtLeastOnce, true, payload.clone());
+        }
+    }
+}
+
+/// Keeps the connection to the broker up and runs the commands it delivers.
+fn serve(
+    mut connection: Connection,
+    config: &MqttConfig,
+    publisher: &Publisher,
+    manager: &PlayerManager,
+) {
+    let commands = format!("{}/set/", config.topic);
+    let mut last_error = None;
+
+    for event in connection.iter() {
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                last_error = None;
+
+                // A new session has no subscriptions, and a restarted broker
+                // no retained messages.
+                let _ = publisher
+                    .client
+                    .try_subscribe(format!("{}#", commands), QoS::AtLeastOnce);
+
+                publisher.republish();
+            }
+            Ok(Event::Incoming(Packet::Publish(message))) => {
+                let command = match message.topic.strip_prefix(&commands) {
+                    Some(c) => c,
+                    None => continue,
+                };
+
+                let payload = String::from_utf8_lossy(&message.payload);
+
+                if let Err(why) = daemon::handle(manager, arguments(command, &payload)) {
+                    eprintln!("MQTT command {} failed: {}", command, why);
+                }
+            }
+            Ok(_) => (),
+            Err(why) => {
+                let why = why.to_string();
+
+                // Reported once rather than on every attempt.
+                if last_error.as_ref() != Some(&why) {
+                    eprintln!(
+                        "MQTT broker {}:{} unavailable: {}",
+                        config.host, config.port, why
+                    );
+                }
+
+                last_error = Some(why);
+                thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// Turns a command topic and its payload into `multiplayerctl` arguments.
+/// `switch` with a player name switches to that player; other payloads are
+/// the command's arguments, e.g. `0.5` for `volume`.
+fn arguments(command: &str, payload: &str) -> Vec<String> {
+    let payload = payload.trim();
+
+    if command == "switch" && !payload.is_empty() && !payload.starts_with('-') {
+        return vec![
+            String::from("--player"),
+            payload.to_string(),
+            String::from("switch"),
+        ];
+    }
+
+    iter::once(command)
+        .chain(payload.split_whitespace())
+        .map(String::from)
+        .collect()
+}
+
+/// What is published under `<topic>/current/`; empty while there is no
+/// current player.
+fn state(manager: &PlayerManager) -> Vec<(&'static str, String)> {
+    let player = match manager.current_or_init() {
+        Ok(p) => p,
+        Err(_) => {
+            return [
+                "player",
+                "player_name",
+                "status",
+                "title",
+                "artist",
+                "album",
+                "art_url",
+                "length",
+                "volume",
+            ]
+            .into_iter()
+            .map(|field| (field, String::new()))
+            .collect()
+        }
+    };
+
+    let metadata = player.metadata().unwrap_or_default();
+
+    vec![
+        ("player", player.name().to_string()),
+        ("player_name", manager.display_name(player.name())),
+        (
+            "status",
+            player.status().map(|s| s.to_string()).unwrap_or_default(),
+        ),
+        ("title", metadata.title().unwrap_or_default().to_string()),
+        ("artist", metadata.artist().unwrap_or_default()),
+        ("album", metadata.album().unwrap_or_default().to_string()),
+        (
+            "art_url",
+            metadata.art_url().unwrap_or_default().to_string(),
+        ),
+        (
+            "length",
+            metadata
+                .length_secs()
+                .map(|l| l.to_string())
+                .unwrap_or_default(),
+        ),
+        (
+            "volume",
+            player
+                .volume()
+                .map(|v| format!("{:.2}", v))
+                .unwrap_or_default(),
+        ),
+    ]
+}
+
+/// Home Assistant discovery messages describing the player as one device.
+/// Home Assistant's MQTT integration has no media player entity, so it is
+/// made up of sensors, buttons, a volume slider and a player selector.
+fn discovery(
+    config: &MqttConfig,
+    manager: &PlayerManager,
+    players: &[String],
+) -> Vec<(String, String)> {
+    let topic = &config.topic;
+
+    let device = json!({
+        "identifiers": [config.client_id],
+        "name": "multiplayerctl",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+
+    let entity = |component: &str, object: &str, mut fields: Value| {
+        fields["unique_id"] = json!(format!("{}_{}", config.client_id, object));
+        fields["availability_topic"] = json!(format!("{}/available", topic));
+        fields["device"] = device.clone();
+
+        (
+            format!(
+                "{}/{}/{}/{}/config",
+                config.discovery_prefix, component, config.client_id, object
+            ),
+            fields.to_string(),
+        )
+    };
+
+    let button = |object: &str, name: &str, icon: &str| {
+        entity(
+            "button",
+            object,
+            json!({
+                "name": name,
+                "command_topic": format!("{}/set/{}", topic, object),
+                "icon": icon,
+            }),
+        )
+    };
+
+    let mut messages = vec![
+        entity(
+            "sensor",
+            "now_playing",
+            json!({
+                "name": "Now playing",
+                "state_topic": format!("{}/current/state", topic),
+                "value_template": "{{ value_json.artist }} - {{ value_json.title }}",
+                "json_attributes_topic": format!("{}/current/state", topic),
+                "icon": "mdi:music",
+            }),
+        ),
+        entity(
+            "sensor",
+            "status",
+            json!({
+                "name": "Status",
+                "state_topic": format!("{}/current/status", topic),
+                "icon": "mdi:play-pause",
+            }),
+        ),
+        button("toggle", "Play/pause", "mdi:play-pause"),
+        button("next", "Next", "mdi:skip-next"),
+        button("previous", "Previous", "mdi:skip-previous"),
+        entity(
+            "number",
+            "volume",
+            json!({
+                "name": "Volume",
+                "state_topic": format!("{}/current/volume", topic),
+                "command_topic": format!("{}/set/volume", topic),
+                "min": 0,
+                "max": manager.config().volume.limit(),
+                "step": 0.01,
+                "mode": "slider",
+                "icon": "mdi:volume-high",
+            }),
+        ),
+    ];
+
+    // A selector without options is invalid, so it goes away with the players.
+    let selector = entity(
+        "select",
+        "player",
+        json!({
+            "name": "Player",
+            "state_topic": format!("{}/current/player", topic),
+            "command_topic": format!("{}/set/switch", topic),
+            "options": players,
+        }),
+    );
+
+    messages.push(match players.is_empty() {
+        true => (selector.0, String::new()),
+        false => selector,
+    });
+
+    messages
+}
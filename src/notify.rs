@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use multiplayerctl::config::Urgency;
+use multiplayerctl::{art, Error, PlaybackStatus, PlayerManager, Result};
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+
+use crate::template::{render_fields, render_markup};
+use crate::{follow, player_fields};
+
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+/// Sends a desktop notification whenever the current player starts playing a
+/// track that has not been announced yet.
+pub fn run(manager: PlayerManager) -> Result<()> {
+    let connection = Connection::session()
+        .map_err(|why| Error::Other(format!("Failed to connect to the session bus: {}", why)))?;
+
+    let mut notifier = Notifier {
+        connection,
+        replaces_id: 0,
+        announced: HashMap::new(),
+    };
+
+    follow::watch(
+        Arc::new(manager),
+        |_| None,
+        |manager| {
+            notifier.update(manager);
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+struct Notifier {
+    connection: Connection,
+    /// The id of the last notification, which the next one replaces.
+    replaces_id: u32,
+    /// The last announced track, per player when duplicates are suppressed,
+    /// or under a single empty key otherwise.
+    announced: HashMap<String, String>,
+}
+
+impl Notifier {
+    fn update(&mut self, manager: &PlayerManager) {
+        let player = match manager.current_or_init() {
+            Ok(p) => p,
+            Err(_) => return,
+        };
+
+        if player.status() != Ok(PlaybackStatus::Playing) {
+            return;
+        }
+
+        let metadata = player.metadata().unwrap_or_default();
+        let config = &manager.config().notify;
+
+        let track = format!(
+            "{:?} {:?} {:?} {:?}",
+            metadata.track_id(),
+            metadata.title(),
+            metadata.artists(),
+            metadata.album()
+        );
+
+        let scope = match config.suppress_duplicates {
+            true => player.name().to_string(),
+            false => String::new(),
+        };
+
+        if self.announced.get(&scope) == Some(&track) {
+            return;
+        }
+
+        self.announced.insert(scope, track);
+
+        let fields = player_fields(manager, &player);
+
+        let image = art::fetch(
+            manager.cache_path(),
+            player.name(),
+            &metadata,
+            None,
+            manager.config().art.musicbrainz,
+        )
+        .map(|path| format!("file://{}", path.display()))
+        .ok();
+
+        let notification = Notification {
+            summary: render_fields(&config.summary, &fields),
+            body: render_markup(&config.body, &fields),
+            image,
+            urgency: config.urgency,
+            timeout: config.timeout,
+        };
+
+        match self.send(&notification) {
+            Ok(id) => self.replaces_id = id,
+            Err(why) => eprintln!("Failed to send notification: {}", why),
+        }
+    }
+
+    fn send(&self, notification: &Notification) -> zbus::Result<u32> {
+        let urgency: u8 = match notification.urgency {
+            Urgency::Low => 0,
+            Urgency::Normal => 1,
+            Urgency::Critical => 2,
+        };
+
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("urgency", Value::from(urgency));
+
+        let icon = match &notification.image {
+            Some(uri) => {
+                hints.insert("image-path", Value::from(uri.as_str()));
+                ""
+            }
+            None => "audio-x-generic",
+        };
+
+        let reply = self.connection.call_method(
+            Some(NOTIFICATIONS),
+            NOTIFICATIONS_PATH,
+            Some(NOTIFICATIONS),
+            "Notify",
+            &(
+                "multiplayerctl",
+                self.replaces_id,
+                icon,
+                notification.summary.as_str(),
+                notification.body.as_str(),
+                Vec::<&str>::new(),
+                hints,
+                notification.timeout,
+            ),
+        )?;
+
+        reply.body().deserialize()
+    }
+}
+
+struct Notification {
+    summary: String,
+    body: String,
+    /// A `file://` URI of the cover art.
+    image: Option<String>,
+    urgency: Urgency,
+    timeout: i32,
+}
@@ -0,0 +1,127 @@
+use std::io::{self, BufRead, Write};
+use std::process::{Command, Stdio};
+
+use multiplayerctl::{Error, Result};
+
+/// Lets the user choose one of `players`, either through the configured
+/// picker command or through a built-in prompt on the terminal.
+pub fn pick(players: &[String], picker: &Option<String>) -> Result<String> {
+    if players.is_empty() {
+        return Err(Error::NoPlayers);
+    }
+
+    match picker {
+        Some(command) => pick_external(players, command),
+        None => pick_builtin(players),
+    }
+}
+
+/// Runs a dmenu-style command that reads choices on stdin and prints the selection.
+fn pick_external(players: &[String], command: &str) -> Result<String> {
+    let mut child = match Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(why) => {
+            return Err(Error::Other(format!(
+                "Failed to run picker '{}': {}",
+                command, why
+            )))
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        for p in players {
+            writeln!(stdin, "{}", p)?;
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    let choice = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    if choice.is_empty() {
+        return Err(Error::Other(String::from("No player was picked.")));
+    }
+
+    Ok(choice)
+}
+
+/// Prompts on the terminal until the input identifies a single player, either
+/// by its number in the list or by a fuzzy match on its name.
+fn pick_builtin(players: &[String]) -> Result<String> {
+    let mut candidates: Vec<String> = players.to_vec();
+
+    loop {
+        for (i, p) in candidates.iter().enumerate() {
+            eprintln!("{:>3}  {}", i + 1, p);
+        }
+
+        eprint!("player> ");
+        io::stderr().flush()?;
+
+        let mut query = String::new();
+
+        if io::stdin().lock().read_line(&mut query)? == 0 {
+            return Err(Error::Other(String::from("No player was picked.")));
+        }
+
+        let query = query.trim();
+
+        if let Ok(n) = query.parse::<usize>() {
+            if n >= 1 && n <= candidates.len() {
+                return Ok(candidates[n - 1].clone());
+            }
+        }
+
+        let mut matches: Vec<(i64, String)> = candidates
+            .iter()
+            .filter_map(|p| fuzzy_score(query, p).map(|score| (score, p.clone())))
+            .collect();
+
+        matches.sort_by_key(|m| std::cmp::Reverse(m.0));
+
+        match matches.as_slice() {
+            [] => eprintln!("No player matches '{}'.", query),
+            [(_, p)] => return Ok(p.clone()),
+            [(best, p), (second, _), ..] if best > second => return Ok(p.clone()),
+            _ => candidates = matches.into_iter().map(|(_, p)| p).collect(),
+        }
+    }
+}
+
+/// Scores how well `query` fuzzily matches `candidate`, skim-style: every query
+/// character has to appear in order, with bonuses for consecutive characters
+/// and for matches at the start of a word. Returns `None` if it does not match.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for q in query.to_lowercase().chars() {
+        let offset = candidate[next..].iter().position(|c| *c == q)?;
+        let index = next + offset;
+
+        score += 1;
+
+        if previous_match == Some(index.wrapping_sub(1)) {
+            score += 5;
+        }
+
+        if index == 0 || !candidate[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        score -= offset as i64;
+
+        previous_match = Some(index);
+        next = index + 1;
+    }
+
+    Some(score)
+}
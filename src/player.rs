@@ -0,0 +1,475 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::backend::Backend;
+use crate::error::{Error, Result};
+use crate::pulse::{self, SinkInput};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+impl FromStr for PlaybackStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "Playing" => Ok(PlaybackStatus::Playing),
+            "Paused" => Ok(PlaybackStatus::Paused),
+            "Stopped" => Ok(PlaybackStatus::Stopped),
+            _ => Err(format!("Unknown playback status: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for PlaybackStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopStatus {
+    None,
+    Track,
+    Playlist,
+}
+
+impl LoopStatus {
+    /// The status following this one when cycling: None → Playlist → Track → None.
+    pub fn cycle(self) -> LoopStatus {
+        match self {
+            LoopStatus::None => LoopStatus::Playlist,
+            LoopStatus::Playlist => LoopStatus::Track,
+            LoopStatus::Track => LoopStatus::None,
+        }
+    }
+}
+
+impl FromStr for LoopStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(LoopStatus::None),
+            "track" => Ok(LoopStatus::Track),
+            "playlist" => Ok(LoopStatus::Playlist),
+            _ => Err(format!("Unknown loop status: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for LoopStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LoopStatus::None => "None",
+            LoopStatus::Track => "Track",
+            LoopStatus::Playlist => "Playlist",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// What happened to a player, as reported while watching for changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    /// Some of its properties (status, metadata, volume, ...) changed.
+    Properties,
+    /// It jumped to a new position, in microseconds.
+    Seeked(i64),
+    /// It started.
+    Appeared,
+    /// It went away.
+    Vanished,
+}
+
+/// Something a player may or may not support, as announced by its MPRIS
+/// `Can*` properties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Control,
+    Play,
+    Pause,
+    GoNext,
+    GoPrevious,
+    Seek,
+}
+
+impl Capability {
+    /// The MPRIS property announcing this capability.
+    pub fn property(self) -> &'static str {
+        match self {
+            Capability::Control => "CanControl",
+            Capability::Play => "CanPlay",
+            Capability::Pause => "CanPause",
+            Capability::GoNext => "CanGoNext",
+            Capability::GoPrevious => "CanGoPrevious",
+            Capability::Seek => "CanSeek",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Capability::Control => "be controlled",
+            Capability::Play => "play",
+            Capability::Pause => "pause",
+            Capability::GoNext => "go to the next track",
+            Capability::GoPrevious => "go to the previous track",
+            Capability::Seek => "seek",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// A single metadata value. MPRIS sends these as typed D-Bus variants; when
+/// they come from playerctl's text output, the type is taken from the key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum MetadataValue {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+impl MetadataValue {
+    /// Parses a value the way playerctl prints it, using the type the MPRIS
+    /// metadata spec gives `key`. Unknown keys are kept as text.
+    pub fn parse(key: &str, text: &str) -> MetadataValue {
+        let parsed = match key {
+            "mpris:length" | "xesam:trackNumber" | "xesam:discNumber" | "xesam:useCount"
+            | "xesam:audioBPM" => text.parse().ok().map(MetadataValue::Integer),
+            "xesam:autoRating" | "xesam:userRating" => text.parse().ok().map(MetadataValue::Float),
+            "xesam:artist" | "xesam:albumArtist" | "xesam:composer" | "xesam:lyricist"
+            | "xesam:genre" | "xesam:comment" => Some(MetadataValue::List(
+                text.split(", ")
+                    .filter(|v| !v.is_empty())
+                    .map(String::from)
+                    .collect(),
+            )),
+            _ => None,
+        };
+
+        parsed.unwrap_or_else(|| MetadataValue::Text(text.to_string()))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetadataValue::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            MetadataValue::Integer(n) => Some(*n),
+            MetadataValue::Text(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as a list, treating a single string as a list of one.
+    pub fn as_list(&self) -> Vec<&str> {
+        match self {
+            MetadataValue::List(v) => v.iter().map(String::as_str).collect(),
+            MetadataValue::Text(s) => vec![s.as_str()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataValue::Text(s) => write!(f, "{}", s),
+            MetadataValue::Integer(n) => write!(f, "{}", n),
+            MetadataValue::Float(n) => write!(f, "{}", n),
+            MetadataValue::Bool(b) => write!(f, "{}", b),
+            MetadataValue::List(v) => write!(f, "{}", v.join(", ")),
+        }
+    }
+}
+
+/// Track metadata as reported by the player, keyed by the MPRIS names
+/// (`xesam:title`, `mpris:length`, ...).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Metadata {
+    values: BTreeMap<String, MetadataValue>,
+}
+
+impl Metadata {
+    pub fn new(values: BTreeMap<String, MetadataValue>) -> Self {
+        Self { values }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.values.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &MetadataValue)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key)?.as_str().filter(|s| !s.is_empty())
+    }
+
+    fn get_list(&self, key: &str) -> Vec<&str> {
+        self.get(key)
+            .map(MetadataValue::as_list)
+            .unwrap_or_default()
+    }
+
+    /// The object path identifying the track within the player.
+    pub fn track_id(&self) -> Option<&str> {
+        self.get_str("mpris:trackid")
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.get_str("xesam:title")
+    }
+
+    pub fn artists(&self) -> Vec<&str> {
+        self.get_list("xesam:artist")
+    }
+
+    /// All artists joined by commas, or `None` if there are none.
+    pub fn artist(&self) -> Option<String> {
+        let artists = self.artists();
+
+        (!artists.is_empty()).then(|| artists.join(", "))
+    }
+
+    pub fn album(&self) -> Option<&str> {
+        self.get_str("xesam:album")
+    }
+
+    pub fn album_artists(&self) -> Vec<&str> {
+        self.get_list("xesam:albumArtist")
+    }
+
+    pub fn track_number(&self) -> Option<i64> {
+        self.get("xesam:trackNumber")?.as_i64()
+    }
+
+    /// The location of the cover art, usually a `file://` or `https://` URL.
+    pub fn art_url(&self) -> Option<&str> {
+        self.get_str("mpris:artUrl")
+    }
+
+    /// The location of the track itself.
+    pub fn url(&self) -> Option<&str> {
+        self.get_str("xesam:url")
+    }
+
+    /// The track length in microseconds.
+    pub fn length(&self) -> Option<i64> {
+        self.get("mpris:length")?.as_i64()
+    }
+
+    /// The track length in seconds.
+    pub fn length_secs(&self) -> Option<f64> {
+        self.length().map(|l| l as f64 / 1_000_000.0)
+    }
+}
+
+/// A handle to a single player, borrowed from a [`PlayerManager`](crate::PlayerManager).
+pub struct Player<'a> {
+    backend: &'a Backend,
+    name: String,
+}
+
+impl<'a> Player<'a> {
+    pub(crate) fn new(backend: &'a Backend, name: String) -> Self {
+        Self { backend, name }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the player supports `capability`. Players that don't say are
+    /// assumed to support it.
+    pub fn can(&self, capability: Capability) -> bool {
+        self.backend.can(&self.name, capability)
+    }
+
+    /// Fails with [`Error::Unsupported`] unless the player supports `capability`,
+    /// as players tend to silently ignore what they don't support.
+    fn require(&self, capability: Capability) -> Result<()> {
+        match self.can(capability) {
+            true => Ok(()),
+            false => Err(Error::Unsupported {
+                player: self.name.clone(),
+                action: capability.to_string(),
+            }),
+        }
+    }
+
+    pub fn play(&self) -> Result<()> {
+        self.require(Capability::Play)?;
+        self.backend.player_command(&self.name, "play")
+    }
+
+    pub fn pause(&self) -> Result<()> {
+        self.require(Capability::Pause)?;
+        self.backend.player_command(&self.name, "pause")
+    }
+
+    pub fn toggle(&self) -> Result<()> {
+        self.require(Capability::Pause)?;
+        self.backend.player_command(&self.name, "play-pause")
+    }
+
+    pub fn next(&self) -> Result<()> {
+        self.require(Capability::GoNext)?;
+        self.backend.player_command(&self.name, "next")
+    }
+
+    pub fn previous(&self) -> Result<()> {
+        self.require(Capability::GoPrevious)?;
+        self.backend.player_command(&self.name, "previous")
+    }
+
+    pub fn stop(&self) -> Result<()> {
+        self.require(Capability::Control)?;
+        self.backend.player_command(&self.name, "stop")
+    }
+
+    /// Asks the player to open and play a URI, e.g. a file path or stream URL.
+    pub fn open(&self, uri: &str) -> Result<()> {
+        self.backend.open_uri(&self.name, uri)
+    }
+
+    /// Brings the player's window to the front.
+    pub fn raise(&self) -> Result<()> {
+        self.backend.root_command(&self.name, "Raise")
+    }
+
+    /// Asks the player application to exit.
+    pub fn quit(&self) -> Result<()> {
+        self.backend.root_command(&self.name, "Quit")
+    }
+
+    pub fn status(&self) -> Result<PlaybackStatus> {
+        self.backend.playback_status(&self.name)
+    }
+
+    pub fn metadata(&self) -> Result<Metadata> {
+        self.backend.metadata(&self.name)
+    }
+
+    pub fn loop_status(&self) -> Result<LoopStatus> {
+        self.backend.loop_status(&self.name)
+    }
+
+    pub fn set_loop_status(&self, status: LoopStatus) -> Result<()> {
+        self.require(Capability::Control)?;
+        self.backend.set_loop_status(&self.name, status)
+    }
+
+    pub fn shuffle(&self) -> Result<bool> {
+        self.backend.shuffle(&self.name)
+    }
+
+    pub fn set_shuffle(&self, shuffle: bool) -> Result<()> {
+        self.require(Capability::Control)?;
+        self.backend.set_shuffle(&self.name, shuffle)
+    }
+
+    pub fn volume(&self) -> Result<f64> {
+        self.backend.volume(&self.name)
+    }
+
+    pub fn set_volume(&self, volume: f64) -> Result<()> {
+        self.require(Capability::Control)?;
+        self.backend.set_volume(&self.name, volume)
+    }
+
+    /// Whether the player is muted, for players that can be muted without
+    /// touching their volume; see [`PlayerManager::mute`](crate::PlayerManager::mute).
+    pub fn muted(&self) -> Result<bool> {
+        self.backend.muted(&self.name)
+    }
+
+    pub fn set_muted(&self, muted: bool) -> Result<()> {
+        self.require(Capability::Control)?;
+        self.backend.set_muted(&self.name, muted)
+    }
+
+    /// The player's audio streams in PulseAudio or PipeWire. Players that
+    /// are paused may have none.
+    pub fn sink_inputs(&self) -> Result<Vec<SinkInput>> {
+        self.backend.sink_inputs(&self.name)
+    }
+
+    /// Moves the player's audio to the sink with the given name or index.
+    pub fn move_to_sink(&self, sink: &str) -> Result<()> {
+        let inputs = self.sink_inputs()?;
+
+        if inputs.is_empty() {
+            return Err(Error::player_command(&self.name, "No audio stream found"));
+        }
+
+        inputs
+            .iter()
+            .try_for_each(|input| pulse::move_sink_input(input.index, sink))
+            .map_err(|why| Error::player_command(&self.name, why))
+    }
+
+    /// Returns the playback position in microseconds.
+    pub fn position(&self) -> Result<i64> {
+        self.backend.position(&self.name)
+    }
+
+    /// Sets the absolute playback position in microseconds.
+    pub fn set_position(&self, position: i64) -> Result<()> {
+        self.require(Capability::Seek)?;
+        self.backend.set_position(&self.name, position)
+    }
+
+    /// Seeks by a relative offset in microseconds.
+    pub fn seek(&self, offset: i64) -> Result<()> {
+        self.require(Capability::Seek)?;
+        self.backend.seek(&self.name, offset)
+    }
+
+    /// The tracks in the player's track list, such as its queue, for players
+    /// that have one.
+    pub fn tracks(&self) -> Result<Vec<Metadata>> {
+        self.backend.tracks(&self.name)
+    }
+
+    /// Adds the track at `uri` to the track list after the track with the id
+    /// `after`, or at the start with `None`.
+    pub fn add_track(&self, uri: &str, after: Option<&str>) -> Result<()> {
+        self.backend.add_track(&self.name, uri, after)
+    }
+
+    pub fn remove_track(&self, track_id: &str) -> Result<()> {
+        self.backend.remove_track(&self.name, track_id)
+    }
+
+    /// Skips to a track in the track list.
+    pub fn go_to(&self, track_id: &str) -> Result<()> {
+        self.backend.go_to(&self.name, track_id)
+    }
+}
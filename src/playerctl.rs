@@ -0,0 +1,166 @@
+use std::ffi::OsStr;
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::child::ChildGuard;
+use crate::error::{Error, Result};
+
+/// Runs playerctl with the given arguments and waits for it to finish.
+pub fn run<I, S>(args: I) -> Result<Output>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    run_within(args, None)
+}
+
+/// Runs playerctl like [`run`], but kills it if it has not finished after
+/// `timeout`, failing with [`Error::Timeout`]. Some players hang while they
+/// shut down, and playerctl with them.
+pub fn run_within<I, S>(args: I, timeout: Option<Duration>) -> Result<Output>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args: Vec<S> = args.into_iter().collect();
+
+    debug!(
+        "Running playerctl {:?}",
+        args.iter().map(|a| a.as_ref()).collect::<Vec<_>>()
+    );
+
+    let mut command = Command::new("playerctl");
+    command.args(&args);
+
+    let timeout = match timeout {
+        Some(t) => t,
+        None => return command.output().map_err(|_| Error::PlayerctlMissing),
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map(ChildGuard::new)
+        .map_err(|_| Error::PlayerctlMissing)?;
+
+    // Read while waiting, or playerctl would block on a full pipe.
+    let stdout = read_to_end(child.stdout.take());
+    let stderr = read_to_end(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        // Dropping the child kills it.
+        if Instant::now() >= deadline {
+            return Err(Error::Timeout(format!(
+                "playerctl {} did not finish within {:?} and was killed.",
+                args.iter()
+                    .map(|a| a.as_ref().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                timeout
+            )));
+        }
+
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn read_to_end(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+
+        buf
+    })
+}
+
+/// Starts playerctl with its stdout piped, e.g. for `--follow`. It is killed
+/// once the returned guard is dropped.
+pub fn spawn<I, S>(args: I) -> Result<ChildGuard>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args: Vec<S> = args.into_iter().collect();
+
+    debug!(
+        "Starting playerctl {:?}",
+        args.iter().map(|a| a.as_ref()).collect::<Vec<_>>()
+    );
+
+    Command::new("playerctl")
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map(ChildGuard::new)
+        .map_err(|_| Error::PlayerctlMissing)
+}
+
+pub fn list_players(timeout: Option<Duration>) -> Result<Vec<String>> {
+    let all_players_output = run_within(["-l"], timeout)?.stdout;
+
+    let all_players_str = match String::from_utf8(all_players_output) {
+        Ok(v) => v,
+        Err(why) => return Err(Error::Other(format!("Failed to get player list: {}", why))),
+    };
+
+    Ok(all_players_str.lines().map(String::from).collect())
+}
+
+/// Prints the output of a finished playerctl invocation.
+pub fn print_output(output: &Output) {
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+}
+
+/// Runs a playerctl command against a single player and returns its stdout,
+/// or its stderr as the error if playerctl reported a failure. Fails with
+/// [`Error::Stalled`] if the player does not answer within `timeout`.
+pub fn query(player: &str, args: &[&str], timeout: Option<Duration>) -> Result<String> {
+    let mut full_args = vec![format!("--player={}", player)];
+    full_args.extend(args.iter().map(|a| a.to_string()));
+
+    let output = match run_within(full_args, timeout) {
+        Ok(output) => output,
+        Err(Error::Timeout(_)) => {
+            return Err(Error::Stalled {
+                player: player.to_string(),
+                timeout: timeout.unwrap_or_default(),
+            })
+        }
+        Err(why) => return Err(why),
+    };
+
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+    // playerctl reports a --player that is not running like an empty player list.
+    if stderr.starts_with("No players found") {
+        Err(Error::UnknownPlayer(player.to_string()))
+    } else {
+        Err(Error::player_command(player, stderr))
+    }
+}
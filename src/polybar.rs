@@ -0,0 +1,69 @@
+use std::env;
+use std::time::Duration;
+
+use multiplayerctl::config::ClickActions;
+use multiplayerctl::{PlayerManager, Result};
+
+use crate::template::render_polybar;
+use crate::{follow, player_fields, playing, remote, shows_position};
+
+/// Prints a line for a polybar module in tail mode whenever the current
+/// player or anything shown about it changes, with the configured actions
+/// wrapped around it as click areas.
+pub fn run(manager: PlayerManager, options: follow::Options) -> Result<()> {
+    // The position changes without the player announcing it.
+    let tick = shows_position(&manager.config().polybar.text).then_some(Duration::from_secs(1));
+
+    // Run as this very binary, as polybar's PATH may not have it.
+    let executable = env::current_exe()
+        .map(|path| remote::quote(&path.to_string_lossy()))
+        .unwrap_or_else(|_| String::from("multiplayerctl"));
+
+    follow::run(
+        manager,
+        options,
+        |manager| tick.filter(|_| playing(manager)),
+        |manager| render(manager, &executable),
+    )
+}
+
+fn render(manager: &PlayerManager, executable: &str) -> String {
+    // Without a player, the empty line hides the module.
+    let player = match manager.current_or_init() {
+        Ok(p) => p,
+        Err(_) => return String::new(),
+    };
+
+    let config = manager.config();
+    let text = render_polybar(&config.polybar.text, &player_fields(manager, &player));
+
+    with_actions(&text, &config.polybar.actions, executable)
+}
+
+/// Wraps `text` in a click area for each button with an action.
+fn with_actions(text: &str, actions: &ClickActions, executable: &str) -> String {
+    let buttons = [
+        (1, &actions.left),
+        (2, &actions.middle),
+        (3, &actions.right),
+        (4, &actions.scroll_up),
+        (5, &actions.scroll_down),
+    ];
+
+    let mut opened = String::new();
+    let mut closed = String::new();
+
+    for (button, action) in buttons {
+        if action.trim().is_empty() {
+            continue;
+        }
+
+        // Colons end the command, unless escaped.
+        let command = format!("{} {}", executable, action.trim()).replace(':', "\\:");
+
+        opened.push_str(&format!("%{{A{}:{}:}}", button, command));
+        closed.push_str("%{A}");
+    }
+
+    format!("{}{}{}", opened, text, closed)
+}
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use multiplayerctl::mpris::{BUS_PREFIX, OBJECT_PATH, PLAYER_INTERFACE, PROXY_PLAYER};
+use multiplayerctl::{
+    Capability, Error, LoopStatus, MetadataValue, PlaybackStatus, Player, PlayerManager, Result,
+};
+use zbus::blocking::{connection, Connection};
+use zbus::fdo;
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+
+use crate::follow;
+
+/// Registers `org.mpris.MediaPlayer2.multiplayerctl`, a player that forwards
+/// every call to the current player, and keeps its properties in sync with
+/// whichever player is current. Blocks until watching the players fails.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let connection = connection::Builder::session()
+        .and_then(|b| b.name(format!("{}{}", BUS_PREFIX, PROXY_PLAYER)))
+        .and_then(|b| {
+            b.serve_at(
+                OBJECT_PATH,
+                Root {
+                    manager: Arc::clone(&manager),
+                },
+            )
+        })
+        .and_then(|b| {
+            b.serve_at(
+                OBJECT_PATH,
+                Proxy {
+                    manager: Arc::clone(&manager),
+                },
+            )
+        })
+        .and_then(|b| b.build())
+        .map_err(|why| Error::Other(format!("Failed to register the MPRIS proxy: {}", why)))?;
+
+    let mut last = HashMap::new();
+
+    follow::watch(
+        manager,
+        |_| None,
+        |manager| {
+            let properties = properties(manager);
+
+            let changed: HashMap<&str, &OwnedValue> = properties
+                .iter()
+                .filter(|(k, v)| last.get(*k) != Some(*v))
+                .map(|(k, v)| (*k, v))
+                .collect();
+
+            if !changed.is_empty() {
+                emit_properties_changed(&connection, changed);
+            }
+
+            last = properties;
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+fn emit_properties_changed(connection: &Connection, changed: HashMap<&str, &OwnedValue>) {
+    let emitted = connection.emit_signal(
+        None::<()>,
+        OBJECT_PATH,
+        "org.freedesktop.DBus.Properties",
+        "PropertiesChanged",
+        &(PLAYER_INTERFACE, changed, Vec::<&str>::new()),
+    );
+
+    if let Err(why) = emitted {
+        eprintln!("Failed to update the MPRIS proxy: {}", why);
+    }
+}
+
+/// The properties mirrored from the current player, as they are announced in
+/// `PropertiesChanged`.
+fn properties(manager: &PlayerManager) -> HashMap<&'static str, OwnedValue> {
+    let player = current(manager);
+    let player = player.as_ref();
+
+    let mut properties = HashMap::new();
+
+    properties.insert("PlaybackStatus", owned(playback_status(player)));
+    properties.insert("LoopStatus", owned(loop_status(player)));
+    properties.insert(
+        "Shuffle",
+        owned(player.and_then(|p| p.shuffle().ok()).unwrap_or(false)),
+    );
+    properties.insert(
+        "Volume",
+        owned(player.and_then(|p| p.volume().ok()).unwrap_or(0.0)),
+    );
+    properties.insert("Metadata", owned(metadata(player)));
+
+    for (property, capability) in CAPABILITIES {
+        properties.insert(property, owned(can(player, capability)));
+    }
+
+    properties
+}
+
+/// The capabilities mirrored from the current player, by property name.
+const CAPABILITIES: [(&str, Capability); 5] = [
+    ("CanGoNext", Capability::GoNext),
+    ("CanGoPrevious", Capability::GoPrevious),
+    ("CanPlay", Capability::Play),
+    ("CanPause", Capability::Pause),
+    ("CanSeek", Capability::Seek),
+];
+
+fn can(player: Option<&Player>, capability: Capability) -> bool {
+    player.is_some_and(|p| p.can(capability))
+}
+
+fn owned<'a>(value: impl Into<Value<'a>>) -> OwnedValue {
+    // Only fails for file descriptors, which are never mirrored.
+    value.into().try_into().unwrap()
+}
+
+/// The current player, if there is one. Picks a new one if it has gone away.
+fn current(manager: &PlayerManager) -> Option<Player<'_>> {
+    manager.current_or_init().ok()
+}
+
+fn playback_status(player: Option<&Player>) -> String {
+    player
+        .and_then(|p| p.status().ok())
+        .unwrap_or(PlaybackStatus::Stopped)
+        .to_string()
+}
+
+fn loop_status(player: Option<&Player>) -> String {
+    player
+        .and_then(|p| p.loop_status().ok())
+        .unwrap_or(LoopStatus::None)
+        .to_string()
+}
+
+/// Turns the metadata back into D-Bus values, with the types the MPRIS spec
+/// prescribes where it matters.
+fn metadata(player: Option<&Player>) -> HashMap<String, OwnedValue> {
+    let metadata = match player.and_then(|p| p.metadata().ok()) {
+        Some(m) => m,
+        None => return HashMap::new(),
+    };
+
+    metadata
+        .iter()
+        .map(|(key, value)| {
+            let value = match (key, value) {
+                ("mpris:trackid", MetadataValue::Text(s)) => match ObjectPath::try_from(s.as_str())
+                {
+                    Ok(path) => owned(path),
+                    Err(_) => owned(s.as_str()),
+                },
+                (
+                    "xesam:trackNumber" | "xesam:discNumber" | "xesam:useCount" | "xesam:audioBPM",
+                    MetadataValue::Integer(n),
+                ) => owned(i32::try_from(*n).unwrap_or_default()),
+                (_, MetadataValue::Text(s)) => owned(s.as_str()),
+                (_, MetadataValue::Integer(n)) => owned(*n),
+                (_, MetadataValue::Float(n)) => owned(*n),
+                (_, MetadataValue::Bool(b)) => owned(*b),
+                (_, MetadataValue::List(v)) => owned(v.clone()),
+            };
+
+            (key.to_string(), value)
+        })
+        .collect()
+}
+
+fn forward(manager: &PlayerManager, command: impl Fn(&Player) -> Result<()>) -> fdo::Result<()> {
+    let player =
+        current(manager).ok_or_else(|| fdo::Error::Failed(Error::NoPlayers.to_string()))?;
+
+    command(&player).map_err(|why| fdo::Error::Failed(why.to_string()))
+}
+
+struct Root {
+    manager: Arc<PlayerManager>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.raise())
+    }
+
+    fn quit(&self) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.quit())
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        String::from(PROXY_PLAYER)
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+struct Proxy {
+    manager: Arc<PlayerManager>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Proxy {
+    fn play(&self) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.play())
+    }
+
+    fn pause(&self) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.pause())
+    }
+
+    fn play_pause(&self) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.toggle())
+    }
+
+    fn stop(&self) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.stop())
+    }
+
+    fn next(&self) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.next())
+    }
+
+    fn previous(&self) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.previous())
+    }
+
+    async fn seek(
+        &self,
+        offset: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.seek(offset))?;
+
+        self.announce_position(&emitter).await
+    }
+
+    /// The track id is not checked, as the position always refers to the
+    /// current player's track.
+    async fn set_position(
+        &self,
+        _track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.set_position(position))?;
+
+        self.announce_position(&emitter).await
+    }
+
+    fn open_uri(&self, uri: String) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.open(&uri))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        playback_status(current(&self.manager).as_ref())
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        loop_status(current(&self.manager).as_ref())
+    }
+
+    #[zbus(property)]
+    fn set_loop_status(&self, status: String) -> fdo::Result<()> {
+        let status: LoopStatus = status.parse().map_err(fdo::Error::InvalidArgs)?;
+
+        forward(&self.manager, |p| p.set_loop_status(status))
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        current(&self.manager)
+            .and_then(|p| p.shuffle().ok())
+            .unwrap_or(false)
+    }
+
+    #[zbus(property)]
+    fn set_shuffle(&self, shuffle: bool) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.set_shuffle(shuffle))
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        current(&self.manager)
+            .and_then(|p| p.volume().ok())
+            .unwrap_or(0.0)
+    }
+
+    #[zbus(property)]
+    fn set_volume(&self, volume: f64) -> fdo::Result<()> {
+        forward(&self.manager, |p| p.set_volume(volume.max(0.0)))
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        metadata(current(&self.manager).as_ref())
+    }
+
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        current(&self.manager)
+            .and_then(|p| p.position().ok())
+            .unwrap_or(0)
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        can(current(&self.manager).as_ref(), Capability::GoNext)
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        can(current(&self.manager).as_ref(), Capability::GoPrevious)
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        can(current(&self.manager).as_ref(), Capability::Play)
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        can(current(&self.manager).as_ref(), Capability::Pause)
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        can(current(&self.manager).as_ref(), Capability::Seek)
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+impl Proxy {
+    async fn announce_position(&self, emitter: &SignalEmitter<'_>) -> fdo::Result<()> {
+        let position = current(&self.manager)
+            .and_then(|p| p.position().ok())
+            .unwrap_or(0);
+
+        Proxy::seeked(emitter, position).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+//! Talks to PulseAudio, or PipeWire through `pipewire-pulse`, by shelling out
+//! to `pactl`.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::process::{Command, Stdio};
+
+use crate::child::ChildGuard;
+use crate::error::{Error, Result};
+
+/// What PulseAudio considers 100% volume.
+const NORMAL_VOLUME: f64 = 65536.0;
+
+/// An audio output, such as speakers, headphones or a Bluetooth headset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sink {
+    pub index: u32,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// A stream playing to a sink, such as a browser's audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkInput {
+    pub index: u32,
+    /// The index of the sink it plays to.
+    pub sink: u32,
+    /// The average over its channels, where `1.0` is 100%.
+    pub volume: f64,
+    pub process_id: Option<u32>,
+    pub process_binary: Option<String>,
+    pub application_name: Option<String>,
+}
+
+/// Runs pactl with the given arguments and returns its stdout, or its stderr
+/// as the error if it failed.
+pub fn run<I, S>(args: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = pactl()
+        .args(args)
+        .output()
+        .map_err(|why| Error::Other(format!("Failed to run pactl: {}", why)))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(Error::Other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Starts pactl with its stdout piped, e.g. for `subscribe`. It is killed
+/// once the returned guard is dropped.
+pub fn spawn<I, S>(args: I) -> Result<ChildGuard>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    pactl()
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map(ChildGuard::new)
+        .map_err(|why| Error::Other(format!("Failed to run pactl: {}", why)))
+}
+
+/// pactl, with its output in English to be parsed.
+fn pactl() -> Command {
+    let mut command = Command::new("pactl");
+    command.env("LC_ALL", "C").stdin(Stdio::null());
+    command
+}
+
+pub fn sinks() -> Result<Vec<Sink>> {
+    let list = run(["list", "sinks"])?;
+
+    let mut sinks = Vec::new();
+    let mut index = None;
+
+    for line in list.lines().map(str::trim) {
+        if let Some(i) = line.strip_prefix("Sink #") {
+            index = i.parse().ok();
+        } else if let (Some(name), Some(index)) = (line.strip_prefix("Name: "), index) {
+            sinks.push(Sink {
+                index,
+                name: name.to_string(),
+                description: None,
+            });
+        } else if let (Some(description), Some(sink)) =
+            (line.strip_prefix("Description: "), sinks.last_mut())
+        {
+            sink.description = Some(description.to_string());
+        }
+    }
+
+    Ok(sinks)
+}
+
+pub fn sink_inputs() -> Result<Vec<SinkInput>> {
+    let list = run(["list", "sink-inputs"])?;
+
+    let mut inputs = Vec::new();
+    let mut input: Option<SinkInput> = None;
+
+    for line in list.lines().map(str::trim) {
+        if let Some(index) = line.strip_prefix("Sink Input #") {
+            inputs.extend(input.take());
+
+            input = index.parse().ok().map(|index| SinkInput {
+                index,
+                sink: 0,
+                volume: 1.0,
+                process_id: None,
+                process_binary: None,
+                application_name: None,
+            });
+
+            continue;
+        }
+
+        let input = match &mut input {
+            Some(i) => i,
+            None => continue,
+        };
+
+        if let Some(sink) = line.strip_prefix("Sink: ") {
+            input.sink = sink.parse().unwrap_or_default();
+        } else if let Some(volume) = line.strip_prefix("Volume: ") {
+            input.volume = parse_volume(volume).unwrap_or(input.volume);
+        } else if let Some((key, value)) = line.split_once(" = ") {
+            let value = value.trim_matches('"').to_string();
+
+            match key {
+                "application.process.id" => input.process_id = value.parse().ok(),
+                "application.process.binary" => input.process_binary = Some(value),
+                "application.name" => input.application_name = Some(value),
+                _ => (),
+            }
+        }
+    }
+
+    inputs.extend(input);
+
+    Ok(inputs)
+}
+
+/// Averages the raw values of a volume such as
+/// `front-left: 32768 /  50% / -18.06 dB,   front-right: 32768 /  50% / -18.06 dB`.
+fn parse_volume(volume: &str) -> Option<f64> {
+    let raw: Vec<f64> = volume
+        .split(',')
+        .filter_map(|channel| {
+            let (_, value) = channel.split_once(':')?;
+            value.split('/').next()?.trim().parse().ok()
+        })
+        .collect();
+
+    if raw.is_empty() {
+        return None;
+    }
+
+    Some(raw.iter().sum::<f64>() / raw.len() as f64 / NORMAL_VOLUME)
+}
+
+/// The streams of `player`: those of the process with `pid`, which owns the
+/// player on the bus, or of its children, since browsers play audio from a
+/// separate process. Without any, those of a program named like the player.
+pub fn sink_inputs_of(player: &str, pid: Option<u32>) -> Result<Vec<SinkInput>> {
+    let inputs = sink_inputs()?;
+
+    let (by_process, others): (Vec<_>, Vec<_>) = inputs.into_iter().partition(|input| {
+        matches!((input.process_id, pid), (Some(p), Some(owner)) if descends_from(p, owner))
+    });
+
+    if !by_process.is_empty() {
+        return Ok(by_process);
+    }
+
+    // Instances are named like `firefox.instance_1_234`.
+    let program = player.split('.').next().unwrap_or(player).to_lowercase();
+
+    Ok(others
+        .into_iter()
+        .filter(|input| {
+            [&input.process_binary, &input.application_name]
+                .iter()
+                .any(|name| name.as_ref().is_some_and(|n| n.to_lowercase() == program))
+        })
+        .collect())
+}
+
+/// Whether `pid` is `ancestor` or one of its descendants.
+fn descends_from(mut pid: u32, ancestor: u32) -> bool {
+    while pid > 1 {
+        if pid == ancestor {
+            return true;
+        }
+
+        pid = match parent(pid) {
+            Some(p) => p,
+            None => return false,
+        };
+    }
+
+    false
+}
+
+fn parent(pid: u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // The name in parentheses may contain spaces, so fields are counted from
+    // after it: state, then the parent's pid.
+    let (_, fields) = stat.rsplit_once(')')?;
+
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Sets the volume of a stream, where `1.0` is 100%.
+pub fn set_sink_input_volume(index: u32, volume: f64) -> Result<()> {
+    let raw = (volume * NORMAL_VOLUME).round() as u32;
+
+    run([
+        "set-sink-input-volume",
+        &index.to_string(),
+        &raw.to_string(),
+    ])
+    .map(|_| ())
+}
+
+/// Moves a stream to the sink with the given name or index.
+pub fn move_sink_input(index: u32, sink: &str) -> Result<()> {
+    run(["move-sink-input", &index.to_string(), sink]).map(|_| ())
+}
@@ -0,0 +1,114 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use multiplayerctl::config::default_config_path;
+use multiplayerctl::{Config, FileWatcher, PlayerManager, Result};
+
+use crate::{apply_overrides, wait_readable, Opt};
+
+/// The end of the pipe that SIGHUP is passed on through, as little else may
+/// be done in a signal handler.
+static HANGUPS: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_hangup(_: libc::c_int) {
+    let fd = HANGUPS.load(Ordering::Relaxed);
+
+    if fd != -1 {
+        unsafe { libc::write(fd, b"h".as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+/// Reloads the config whenever the daemon gets SIGHUP or the config file is
+/// written. The players, the current player and anyone following them are
+/// left alone; only what is read from the config changes.
+pub fn run(manager: Arc<PlayerManager>, opt: &Opt) -> Result<()> {
+    let hangups = listen_for_hangups()?;
+
+    // The directory may not exist, or not yet, in which case only SIGHUP
+    // works.
+    let watcher = opt
+        .config
+        .clone()
+        .or_else(default_config_path)
+        .and_then(|path| FileWatcher::new(&path).ok());
+
+    loop {
+        let (hangup, written) = match &watcher {
+            Some(watcher) => {
+                let [hangup, written] = wait_readable([hangups.as_raw_fd(), watcher.as_raw_fd()])?;
+
+                (hangup, written && watcher.read_change()?)
+            }
+            None => (wait_readable([hangups.as_raw_fd()])?[0], false),
+        };
+
+        if hangup {
+            drain(&hangups);
+        }
+
+        if hangup || written {
+            reload(&manager, opt);
+        }
+    }
+}
+
+fn reload(manager: &PlayerManager, opt: &Opt) {
+    match Config::load(opt.config.as_deref()) {
+        Ok(mut config) => {
+            apply_overrides(opt, &mut config);
+
+            for problem in config.problems() {
+                eprintln!("Config: {}", problem);
+            }
+
+            manager.set_config(config);
+
+            eprintln!("Reloaded the config.");
+        }
+        Err(why) => eprintln!(
+            "{}\nKeeping the config as it was.",
+            why.to_string().trim_end()
+        ),
+    }
+}
+
+/// Routes SIGHUP to the returned pipe, instead of having it end the daemon.
+fn listen_for_hangups() -> Result<OwnedFd> {
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    // Kept open for as long as the handler may run, i.e. for good.
+    HANGUPS.store(write.into_raw_fd(), Ordering::Relaxed);
+
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = on_hangup as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // Interrupted system calls elsewhere in the daemon are carried on.
+    action.sa_flags = libc::SA_RESTART;
+
+    if unsafe { libc::sigaction(libc::SIGHUP, &action, std::ptr::null_mut()) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(read)
+}
+
+/// Empties the pipe, so that several signals in a row cause one reload.
+fn drain(hangups: &OwnedFd) {
+    let mut buf = [0u8; 64];
+
+    while unsafe {
+        libc::read(
+            hangups.as_raw_fd(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+        )
+    } > 0
+    {}
+}
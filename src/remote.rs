@@ -0,0 +1,75 @@
+use std::io::{self, IsTerminal};
+use std::process::Command;
+
+use multiplayerctl::config::RemoteConfig;
+use multiplayerctl::{Error, Result};
+
+/// Runs the command line `args` with the multiplayerctl on `host` over SSH.
+/// Its output goes straight to ours, and it fails with its exit code.
+pub fn run(host: &str, config: &RemoteConfig, args: &[String]) -> Result<()> {
+    let mut command = Command::new("ssh");
+
+    // Only with a terminal here is one wanted there, for `tui` and prompts,
+    // as it mixes the error output into the output.
+    match io::stdin().is_terminal() && io::stdout().is_terminal() {
+        true => command.arg("-t"),
+        false => command.arg("-T"),
+    };
+
+    // SSH runs the command through the remote shell, which is left to
+    // expand `command`, e.g. a `~`.
+    command
+        .arg("--")
+        .arg(host)
+        .arg(&config.command)
+        // So that a `remote.host` in the config there does not send it on.
+        .arg("--host=")
+        .args(args.iter().map(|arg| quote(arg)));
+
+    let status = command
+        .status()
+        .map_err(|why| Error::Other(format!("Failed to run ssh: {}", why)))?;
+
+    match status.code() {
+        Some(0) => Ok(()),
+        // Both SSH and the remote command have already said what went wrong.
+        code => Err(Error::Remote {
+            code: code.unwrap_or(1),
+            message: String::new(),
+        }),
+    }
+}
+
+/// The command line without `--host`, to be run on the host.
+pub fn without_host(mut args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut rest = Vec::new();
+    let mut found = false;
+
+    while let Some(arg) = args.next() {
+        if found {
+            rest.push(arg);
+        } else if arg == "--host" {
+            args.next();
+            found = true;
+        } else if arg.starts_with("--host=") {
+            found = true;
+        } else {
+            rest.push(arg);
+        }
+    }
+
+    rest
+}
+
+/// Quotes `arg` for a POSIX shell, unless it is safe as it is.
+pub fn quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+
+    match safe {
+        true => arg.to_string(),
+        false => format!("'{}'", arg.replace('\'', "'\\''")),
+    }
+}
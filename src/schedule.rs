@@ -0,0 +1,329 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use multiplayerctl::{Error, PlayerManager, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    daemon, fade, format_duration, format_timestamp, is_oneshot, json_line, parse_duration,
+    session_file, Opt, ScheduleCommand,
+};
+
+/// How often the daemon looks for schedules that are due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Held while the schedules are read and rewritten, so that the daemon taking
+/// down those that are due and a command adding or cancelling one at the same
+/// time don't undo each other.
+static SCHEDULES: Mutex<()> = Mutex::new(());
+
+/// Raising the volume of the player a scheduled command acts on, e.g. to be
+/// woken up gently.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Ramp {
+    from: f64,
+    to: f64,
+    /// In seconds.
+    duration: f64,
+}
+
+/// A command waiting to be run by the daemon.
+#[derive(Debug, Serialize, Deserialize)]
+struct Schedule {
+    id: u32,
+    /// In seconds since the epoch.
+    at: i64,
+    command: Vec<String>,
+    ramp: Option<Ramp>,
+}
+
+/// Parses a time of day like `07:30` into the hour and minute.
+pub fn parse_time(value: &str) -> std::result::Result<(u32, u32), String> {
+    value
+        .split_once(':')
+        .and_then(|(hour, minute)| Some((hour.parse().ok()?, minute.parse().ok()?)))
+        .filter(|&(hour, minute)| hour < 24 && minute < 60)
+        .ok_or_else(|| format!("'{}' is not a time of day like 07:30", value))
+}
+
+/// Parses a volume ramp like `0.1..0.6/5m`.
+pub fn parse_ramp(value: &str) -> std::result::Result<Ramp, String> {
+    let ramp = value.split_once('/').and_then(|(volumes, duration)| {
+        let (from, to) = volumes.split_once("..")?;
+
+        Some(Ramp {
+            from: from.parse().ok()?,
+            to: to.parse().ok()?,
+            duration: parse_duration(duration).filter(|d| *d > 0.0)?,
+        })
+    });
+
+    ramp.filter(|r| [r.from, r.to].iter().all(|v| v.is_finite() && *v >= 0.0))
+        .ok_or_else(|| format!("'{}' is not a volume ramp like 0.1..0.6/5m", value))
+}
+
+/// Adds, lists or cancels schedules.
+pub fn command(
+    manager: &PlayerManager,
+    time: Option<(u32, u32)>,
+    ramp: Option<Ramp>,
+    command: Vec<String>,
+    action: Option<ScheduleCommand>,
+    json: bool,
+) -> Result<String> {
+    let (hour, minute) = match (time, action) {
+        (Some(time), _) => time,
+        (None, Some(ScheduleCommand::Cancel { id })) => return cancel(manager, id),
+        (None, _) => return Ok(list(manager, json)),
+    };
+
+    let (mut command, ramp) = take_ramp(command, ramp)?;
+
+    if command.is_empty() {
+        command.push(String::from("play"));
+    }
+
+    // `--player` given before `schedule` applies to the scheduled command.
+    if let Some(target) = manager.target() {
+        command.splice(0..0, [String::from("--player"), target.to_string()]);
+    }
+
+    let argv = std::iter::once(String::from("multiplayerctl")).chain(command.iter().cloned());
+
+    match Opt::try_parse_from(argv) {
+        Ok(opt) if is_oneshot(&opt.command) => (),
+        Ok(_) => {
+            return Err(Error::InvalidArgument(String::from(
+                "Only commands the daemon can run can be scheduled.",
+            )))
+        }
+        Err(why) => return Err(Error::InvalidArgument(why.to_string())),
+    }
+
+    let _guard = SCHEDULES.lock().unwrap();
+    let mut schedules = load(manager);
+
+    schedules.push(Schedule {
+        id: schedules.iter().map(|s| s.id).max().unwrap_or(0) + 1,
+        at: next_time_of_day(hour, minute),
+        command,
+        ramp,
+    });
+
+    store(manager, &schedules)?;
+
+    Ok(String::new())
+}
+
+/// Takes `--volume-ramp` out of the command, where it ends up when given
+/// after it.
+fn take_ramp(command: Vec<String>, ramp: Option<Ramp>) -> Result<(Vec<String>, Option<Ramp>)> {
+    let mut ramp = ramp;
+    let mut rest = Vec::new();
+    let mut args = command.into_iter();
+
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--volume-ramp") {
+            Some("") => args.next().unwrap_or_default(),
+            Some(value) if value.starts_with('=') => value[1..].to_string(),
+            _ => {
+                rest.push(arg);
+                continue;
+            }
+        };
+
+        ramp = Some(parse_ramp(&value).map_err(Error::InvalidArgument)?);
+    }
+
+    Ok((rest, ramp))
+}
+
+fn list(manager: &PlayerManager, json: bool) -> String {
+    let schedules = load(manager);
+
+    if json {
+        let entries: Vec<Value> = schedules
+            .iter()
+            .map(|s| {
+                json!({
+                    "id": s.id,
+                    "at": s.at,
+                    "command": s.command,
+                    "ramp": s.ramp,
+                })
+            })
+            .collect();
+
+        return json_line(Value::Array(entries));
+    }
+
+    if schedules.is_empty() {
+        return String::from("Nothing scheduled.\n");
+    }
+
+    let mut output = String::new();
+
+    for s in &schedules {
+        output.push_str(&format!(
+            "{:>3}  {}  {}",
+            s.id,
+            format_timestamp(s.at),
+            s.command.join(" ")
+        ));
+
+        if let Some(ramp) = s.ramp {
+            output.push_str(&format!(
+                " (volume {} to {} over {})",
+                ramp.from,
+                ramp.to,
+                format_duration((ramp.duration * 1_000_000.0) as i64)
+            ));
+        }
+
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Cancels the schedule with `id`, or all of them.
+fn cancel(manager: &PlayerManager, id: Option<u32>) -> Result<String> {
+    let _guard = SCHEDULES.lock().unwrap();
+    let mut schedules = load(manager);
+
+    match id {
+        Some(id) => {
+            let count = schedules.len();
+            schedules.retain(|s| s.id != id);
+
+            if schedules.len() == count {
+                return Err(Error::InvalidArgument(format!(
+                    "No schedule with id {}",
+                    id
+                )));
+            }
+        }
+        None => schedules.clear(),
+    }
+
+    store(manager, &schedules).map(|_| String::new())
+}
+
+/// Runs scheduled commands as they come due. Never returns.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    loop {
+        thread::sleep(CHECK_INTERVAL);
+
+        let guard = SCHEDULES.lock().unwrap();
+
+        let (due, waiting): (Vec<_>, Vec<_>) =
+            load(&manager).into_iter().partition(|s| s.at <= now());
+
+        if due.is_empty() {
+            continue;
+        }
+
+        // Taken down first, so that they run only once.
+        let stored = store(&manager, &waiting);
+
+        drop(guard);
+
+        if let Err(why) = stored {
+            eprintln!("{}", why);
+            continue;
+        }
+
+        for schedule in due {
+            let manager = Arc::clone(&manager);
+
+            // Ramps take a while, and should not hold up anything else due.
+            thread::spawn(move || {
+                if let Err(why) = go_off(&manager, schedule) {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+    }
+}
+
+fn go_off(manager: &PlayerManager, schedule: Schedule) -> Result<()> {
+    let ramp = match schedule.ramp {
+        Some(r) => r,
+        None => return daemon::handle(manager, schedule.command).map(|_| ()),
+    };
+
+    let duration = Duration::try_from_secs_f64(ramp.duration).map_err(|_| {
+        Error::InvalidArgument(format!(
+            "The volume ramp of schedule {} does not have a valid duration.",
+            schedule.id
+        ))
+    })?;
+
+    let argv = std::iter::once(String::from("multiplayerctl")).chain(schedule.command.clone());
+    let target = Opt::try_parse_from(argv).ok().and_then(|opt| opt.player);
+
+    let targeted = target.map(|p| manager.targeting(&p));
+    let targeted = targeted.as_ref().unwrap_or(manager);
+
+    // Quiet from the start if the player is already there.
+    if let Ok(player) = targeted.current() {
+        let _ = player.set_volume(ramp.from);
+    }
+
+    daemon::handle(manager, schedule.command)?;
+
+    fade::ramp(&targeted.current()?, ramp.from, ramp.to, duration)
+}
+
+/// The next time the clock shows `hour:minute`, in seconds since the epoch.
+fn next_time_of_day(hour: u32, minute: u32) -> i64 {
+    let now = now() as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+
+    unsafe { libc::localtime_r(&now, &mut tm) };
+
+    let mut at = now;
+
+    // Tomorrow if it has passed today. Set again after `mktime` normalizes
+    // the fields, in case a DST change shifted them.
+    for day in 0..2 {
+        tm.tm_mday += day;
+        tm.tm_hour = hour as i32;
+        tm.tm_min = minute as i32;
+        tm.tm_sec = 0;
+        tm.tm_isdst = -1;
+
+        at = unsafe { libc::mktime(&mut tm) };
+
+        if at > now {
+            break;
+        }
+    }
+
+    at
+}
+
+fn load(manager: &PlayerManager) -> Vec<Schedule> {
+    fs::read_to_string(session_file(manager, "schedules"))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn store(manager: &PlayerManager, schedules: &[Schedule]) -> Result<()> {
+    fs::write(
+        session_file(manager, "schedules"),
+        json!(schedules).to_string(),
+    )
+    .map_err(|why| Error::Other(format!("Failed to write the schedules: {}", why)))
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
@@ -0,0 +1,279 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use multiplayerctl::lastfm::Lastfm;
+use multiplayerctl::listenbrainz::ListenBrainz;
+use multiplayerctl::scrobbler::{Scrobbler, Track};
+use multiplayerctl::{Error, PlaybackStatus, PlayerManager, Result};
+
+use crate::follow;
+
+/// Last.fm ignores tracks this short.
+const MIN_LENGTH: f64 = 30.0;
+/// A track counts once half of it, or this much, has been played.
+const MAX_THRESHOLD: Duration = Duration::from_secs(4 * 60);
+/// How often scrobbles kept back while offline are retried.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+enum Submission {
+    NowPlaying(Track),
+    Scrobble(Track, i64),
+}
+
+/// Submits what the players included by `[scrobble]` play to the configured
+/// services, until watching the players fails.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let scrobblers = scrobblers(&manager)?;
+
+    if scrobblers.is_empty() {
+        return Ok(());
+    }
+
+    let (sender, receiver) = mpsc::channel();
+
+    // Submitted elsewhere, so that a slow network does not hold up watching.
+    thread::spawn(move || {
+        flush(&scrobblers);
+
+        loop {
+            let submission = match receiver.recv_timeout(FLUSH_INTERVAL) {
+                Ok(s) => s,
+                Err(RecvTimeoutError::Timeout) => {
+                    flush(&scrobblers);
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+
+            for scrobbler in &scrobblers {
+                let submitted = match &submission {
+                    Submission::NowPlaying(track) => scrobbler.now_playing(track),
+                    Submission::Scrobble(track, started_at) => {
+                        scrobbler.scrobble(track, *started_at)
+                    }
+                };
+
+                if let Err(why) = submitted {
+                    eprintln!("{}", why);
+                }
+            }
+        }
+    });
+
+    let mut playing: HashMap<String, Playback> = HashMap::new();
+    let next = Cell::new(None);
+
+    follow::watch(
+        manager,
+        |_| next.get(),
+        |manager| {
+            let players: Vec<String> = manager
+                .players()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|name| manager.config().scrobble.includes(name))
+                .collect();
+
+            playing.retain(|name, _| players.contains(name));
+
+            for name in players {
+                let player = manager.player(&name);
+                let metadata = player.metadata().unwrap_or_default();
+                let status = player.status().ok();
+
+                let key = format!(
+                    "{:?} {:?} {:?} {:?}",
+                    metadata.track_id(),
+                    metadata.title(),
+                    metadata.artists(),
+                    metadata.album()
+                );
+
+                if playing.get(&name).is_some_and(|p| p.key != key) {
+                    playing.remove(&name);
+                }
+
+                if let Some(playback) = playing.get_mut(&name) {
+                    playback.update(status, &sender);
+                    continue;
+                }
+
+                if status != Some(PlaybackStatus::Playing) {
+                    continue;
+                }
+
+                let track = match Track::from_metadata(&metadata) {
+                    Some(t) => t,
+                    None => continue,
+                };
+
+                let _ = sender.send(Submission::NowPlaying(track.clone()));
+
+                playing.insert(
+                    name,
+                    Playback {
+                        key,
+                        track,
+                        started_at: unix_time(),
+                        played: Duration::ZERO,
+                        playing_since: Some(Instant::now()),
+                        scrobbled: false,
+                    },
+                );
+            }
+
+            next.set(playing.values().filter_map(Playback::remaining).min());
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+/// The track a player is on, as it is being listened to.
+struct Playback {
+    key: String,
+    track: Track,
+    started_at: i64,
+    /// How long it played before it was last paused.
+    played: Duration,
+    playing_since: Option<Instant>,
+    scrobbled: bool,
+}
+
+impl Playback {
+    fn update(&mut self, status: Option<PlaybackStatus>, sender: &Sender<Submission>) {
+        match (status, self.playing_since) {
+            (Some(PlaybackStatus::Playing), None) => self.playing_since = Some(Instant::now()),
+            (Some(PlaybackStatus::Playing), Some(_)) => (),
+            (_, Some(since)) => {
+                self.played += since.elapsed();
+                self.playing_since = None;
+            }
+            (_, None) => (),
+        }
+
+        if self.remaining() == Some(Duration::ZERO) {
+            let _ = sender.send(Submission::Scrobble(self.track.clone(), self.started_at));
+            self.scrobbled = true;
+        }
+    }
+
+    /// How much longer the track has to play to be scrobbled, or `None` if
+    /// it is paused, too short, or already scrobbled.
+    fn remaining(&self) -> Option<Duration> {
+        if self.scrobbled || self.track.length.is_some_and(|l| l <= MIN_LENGTH) {
+            return None;
+        }
+
+        let threshold = match self.track.length {
+            Some(l) => Duration::from_secs_f64(l / 2.0).min(MAX_THRESHOLD),
+            None => MAX_THRESHOLD,
+        };
+
+        let played = self.played + self.playing_since.map_or(Duration::ZERO, |s| s.elapsed());
+
+        if played >= threshold {
+            return Some(Duration::ZERO);
+        }
+
+        self.playing_since.map(|_| threshold - played)
+    }
+}
+
+fn scrobblers(manager: &PlayerManager) -> Result<Vec<Box<dyn Scrobbler>>> {
+    let config = &manager.config().scrobble;
+    let mut scrobblers: Vec<Box<dyn Scrobbler>> = Vec::new();
+
+    if config.lastfm.is_some() {
+        match fs::read_to_string(session_path(manager)) {
+            Ok(session) => {
+                scrobblers.push(Box::new(lastfm(manager, Some(session.trim().to_string()))?))
+            }
+            Err(_) => {
+                eprintln!("Not scrobbling to Last.fm: run `multiplayerctl scrobble auth` first.")
+            }
+        }
+    }
+
+    if let Some(listenbrainz) = &config.listenbrainz {
+        scrobblers.push(Box::new(ListenBrainz::new(
+            &listenbrainz.url,
+            &listenbrainz.token,
+            manager.state_path().join("listenbrainz-queue"),
+        )));
+    }
+
+    Ok(scrobblers)
+}
+
+fn flush(scrobblers: &[Box<dyn Scrobbler>]) {
+    for scrobbler in scrobblers {
+        if let Err(why) = scrobbler.flush() {
+            eprintln!("{}", why);
+        }
+    }
+}
+
+/// Has the user allow scrobbling to their Last.fm account, and saves the
+/// session key for the daemon.
+pub fn auth(manager: &PlayerManager) -> Result<()> {
+    let lastfm = lastfm(manager, None)?;
+    let token = lastfm.token()?;
+
+    println!(
+        "Allow multiplayerctl to scrobble at\n\n    {}\n\nthen press Enter.",
+        lastfm.auth_url(&token)
+    );
+
+    io::stdin().read_line(&mut String::new())?;
+
+    let session = lastfm.session(&token)?;
+    let path = session_path(manager);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)?
+        .write_all(session.as_bytes())?;
+
+    println!("Scrobbling is set up; it starts with the next daemon.");
+
+    Ok(())
+}
+
+fn lastfm(manager: &PlayerManager, session: Option<String>) -> Result<Lastfm> {
+    let config = manager.config();
+
+    let config = config.scrobble.lastfm.as_ref().ok_or_else(|| {
+        Error::Config(String::from(
+            "Set api_key and api_secret in the [scrobble.lastfm] section of the config first.",
+        ))
+    })?;
+
+    Ok(Lastfm::new(&config.api_key, &config.api_secret, session))
+}
+
+fn session_path(manager: &PlayerManager) -> PathBuf {
+    manager.state_path().join("lastfm-session")
+}
+
+fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
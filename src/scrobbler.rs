@@ -0,0 +1,40 @@
+//! The services the daemon submits played tracks to.
+
+use crate::error::Result;
+use crate::player::Metadata;
+
+/// A track as scrobbling services want to hear about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    /// In seconds.
+    pub length: Option<f64>,
+}
+
+impl Track {
+    /// Returns `None` unless the metadata names both an artist and a title.
+    pub fn from_metadata(metadata: &Metadata) -> Option<Track> {
+        Some(Track {
+            artist: metadata.artist()?,
+            title: metadata.title()?.to_string(),
+            album: metadata.album().map(String::from),
+            length: metadata.length_secs(),
+        })
+    }
+}
+
+pub trait Scrobbler: Send {
+    /// Shows `track` as what the user is listening to right now.
+    fn now_playing(&self, track: &Track) -> Result<()>;
+
+    /// Adds `track`, started at the Unix timestamp `started_at`, to the
+    /// user's listening history.
+    fn scrobble(&self, track: &Track, started_at: i64) -> Result<()>;
+
+    /// Retries scrobbles kept back while the service was unreachable.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
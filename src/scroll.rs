@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Characters scrolled per second when `--scroll` does not say.
+const DEFAULT_SPEED: f64 = 2.0;
+
+/// A window of fixed width over text too long for it, moving along the text
+/// over time and starting over after a separator, as in a marquee.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scroll {
+    /// How many columns are shown.
+    pub width: usize,
+    /// How many characters the window moves per second.
+    pub speed: f64,
+}
+
+impl Scroll {
+    /// How long after `elapsed` the window moves on, for text that scrolls.
+    pub fn next_step(&self, elapsed: Duration) -> Duration {
+        let step = 1.0 / self.speed;
+        let into = elapsed.as_secs_f64() % step;
+
+        Duration::from_secs_f64(step - into)
+    }
+
+    /// Whether `text` is too wide to be shown whole, and therefore scrolls.
+    pub fn scrolls(&self, text: &str) -> bool {
+        text.width() > self.width
+    }
+
+    /// The part of `text` shown `elapsed` after it was first shown. Text that
+    /// fits is returned as it is. Characters are never cut in half: a wide one
+    /// that would only partly fit at the end is left out, and a space put
+    /// in its place.
+    pub fn window(&self, text: &str, separator: &str, elapsed: Duration) -> String {
+        if !self.scrolls(text) {
+            return text.to_string();
+        }
+
+        let cycle: Vec<&str> = text
+            .graphemes(true)
+            .chain(separator.graphemes(true))
+            .collect();
+
+        let start = (elapsed.as_secs_f64() * self.speed) as usize % cycle.len();
+
+        let mut shown = String::new();
+        let mut width = 0;
+
+        for grapheme in cycle.iter().cycle().skip(start) {
+            let grapheme_width = grapheme.width();
+
+            if width + grapheme_width > self.width {
+                break;
+            }
+
+            shown.push_str(grapheme);
+            width += grapheme_width;
+        }
+
+        shown.push_str(&" ".repeat(self.width - width));
+
+        shown
+    }
+}
+
+/// Parses `--scroll`'s `<width>[,speed]`.
+pub fn parse(value: &str) -> std::result::Result<Scroll, String> {
+    let (width, speed) = match value.split_once(',') {
+        Some((width, speed)) => (width, Some(speed)),
+        None => (value, None),
+    };
+
+    let width = match width.trim().parse::<usize>() {
+        Ok(width) if width > 0 => width,
+        _ => return Err(format!("'{}' is not a width in columns", width)),
+    };
+
+    let speed = match speed {
+        Some(speed) => match speed.trim().parse::<f64>() {
+            Ok(s) if s.is_finite() && s > 0.0 => s,
+            _ => {
+                return Err(format!(
+                    "'{}' is not a speed in characters per second",
+                    speed
+                ))
+            }
+        },
+        None => DEFAULT_SPEED,
+    };
+
+    Ok(Scroll { width, speed })
+}
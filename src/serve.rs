@@ -0,0 +1,383 @@
+use std::io::Cursor;
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use multiplayerctl::{art, Error, PlayerManager, Result};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::{daemon, follow};
+
+/// The remote control page, which holds no secrets and asks for the token
+/// itself.
+const PAGE: &[u8] = include_bytes!("web/index.html");
+
+/// Commands that only report something, answered to `GET /<command>`.
+const QUERIES: &[&str] = &[
+    "metadata", "status", "volume", "position", "player", "loop", "shuffle", "title", "artist",
+    "album", "length",
+];
+
+/// Commands that change something, run by `POST /<command>`.
+const ACTIONS: &[&str] = &[
+    "toggle", "play", "pause", "stop", "next", "previous", "switch", "volume", "position", "seek",
+    "loop", "shuffle", "mute", "open", "raise",
+];
+
+/// Serves the REST API and the WebSocket at `bind` until the server fails.
+/// With `token`, every request has to carry it as a bearer token (or, for
+/// browsers opening a WebSocket, as the `token` query parameter). Requests
+/// from pages of other origins are turned away, and so are, without a token,
+/// those for host names other than `localhost` and the bound one, as a page
+/// could point its own name at this machine.
+pub fn run(manager: PlayerManager, bind: &str, token: Option<String>) -> Result<()> {
+    let server = Server::http(bind)
+        .map_err(|why| Error::Other(format!("Failed to listen on {}: {}", bind, why)))?;
+
+    let manager = Arc::new(manager);
+    let clients = Clients::default();
+
+    let watched = Arc::clone(&manager);
+    let updates = clients.clone();
+
+    thread::spawn(move || {
+        let watching = follow::watch(
+            watched,
+            |_| None,
+            |manager| {
+                updates.broadcast(state(manager).to_string());
+
+                Ok(ControlFlow::Continue(()))
+            },
+        );
+
+        if let Err(why) = watching {
+            eprintln!("{}", why);
+        }
+    });
+
+    for request in server.incoming_requests() {
+        let (path, query) = split_url(request.url());
+
+        if !is_same_origin(&request) || (token.is_none() && !is_local_host(&request, bind)) {
+            let _ = request.respond(error_response(
+                403,
+                "Requests from other sites are not allowed.",
+            ));
+            continue;
+        }
+
+        if path == "/" && *request.method() == Method::Get {
+            let page = Response::from_data(PAGE)
+                .with_header(header_value("Content-Type", "text/html; charset=utf-8"));
+
+            let _ = request.respond(page);
+            continue;
+        }
+
+        if token
+            .as_ref()
+            .is_some_and(|t| !is_authorized(&request, &query, t))
+        {
+            let _ = request.respond(error_response(401, "Missing or wrong token."));
+            continue;
+        }
+
+        if path == "/ws" && header(&request, "Upgrade").is_some() {
+            let clients = clients.clone();
+
+            thread::spawn(move || push(request, &clients));
+            continue;
+        }
+
+        if path == "/art" && *request.method() == Method::Get {
+            let _ = request.respond(cover_art(&manager, &query));
+            continue;
+        }
+
+        let response = match arguments(request.method(), &path, &query) {
+            Some(args) => respond(daemon::handle(&manager, args)),
+            None => error_response(404, "No such endpoint."),
+        };
+
+        let _ = request.respond(response);
+    }
+
+    Err(Error::Other(String::from("Stopped serving.")))
+}
+
+/// The WebSocket clients, each waiting for the next state.
+#[derive(Clone, Default)]
+struct Clients {
+    senders: Arc<Mutex<Vec<Sender<String>>>>,
+    /// Sent to clients as soon as they connect.
+    last: Arc<Mutex<Option<String>>>,
+}
+
+impl Clients {
+    fn broadcast(&self, state: String) {
+        let mut last = self.last.lock().unwrap();
+
+        if last.as_ref() == Some(&state) {
+            return;
+        }
+
+        // Clients that went away have dropped their receiver.
+        self.senders
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.send(state.clone()).is_ok());
+
+        *last = Some(state);
+    }
+
+    fn subscribe(&self) -> Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+
+        if let Some(state) = self.last.lock().unwrap().clone() {
+            let _ = sender.send(state);
+        }
+
+        self.senders.lock().unwrap().push(sender);
+
+        receiver
+    }
+}
+
+/// Completes the WebSocket handshake and sends the state whenever it
+/// changes, until the client goes away.
+fn push(request: Request, clients: &Clients) {
+    let key = match header(&request, "Sec-WebSocket-Key") {
+        Some(k) => derive_accept_key(k.as_bytes()),
+        None => {
+            let _ = request.respond(error_response(400, "Not a WebSocket request."));
+            return;
+        }
+    };
+
+    let response = Response::empty(101)
+        .with_header(header_value("Upgrade", "websocket"))
+        .with_header(header_value("Sec-WebSocket-Accept", &key));
+
+    let stream = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    for state in clients.subscribe() {
+        if socket.send(Message::text(state)).is_err() {
+            return;
+        }
+    }
+}
+
+/// The current player and the players, as pushed over the WebSocket.
+fn state(manager: &PlayerManager) -> Value {
+    let players = manager.players().unwrap_or_default();
+
+    let player = match manager.current_or_init() {
+        Ok(p) => p,
+        Err(_) => return json!({ "player": null, "players": players }),
+    };
+
+    let metadata = player.metadata().unwrap_or_default();
+
+    json!({
+        "player": player.name(),
+        "player_name": manager.display_name(player.name()),
+        "status": player.status().ok().map(|s| s.to_string()),
+        "title": metadata.title(),
+        "artist": metadata.artist(),
+        "album": metadata.album(),
+        "art_url": metadata.art_url(),
+        "length": metadata.length_secs(),
+        "position": player.position().ok().map(|p| p as f64 / 1_000_000.0),
+        "volume": player.volume().ok(),
+        "players": players,
+    })
+}
+
+/// Turns a request into `multiplayerctl` arguments. The `player` query
+/// parameter targets a player (or for `switch`, picks the one to switch to),
+/// `value` is the command's argument, and `switch` also takes `index` or
+/// `direction` (`next`, `back` or `playing`).
+fn arguments(method: &Method, path: &str, query: &[(String, String)]) -> Option<Vec<String>> {
+    let command = path.trim_matches('/');
+    let param = |name: &str| {
+        query
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    };
+
+    let mut args = Vec::new();
+
+    if let Some(player) = param("player") {
+        args.extend([String::from("--player"), player]);
+    }
+
+    match (method, command) {
+        (Method::Get, "players") => args.extend([String::from("--json"), String::from("list")]),
+        (Method::Get, c) if QUERIES.contains(&c) => {
+            args.extend([String::from("--json"), c.to_string()])
+        }
+        (Method::Post, "switch") => {
+            args.push(String::from("switch"));
+
+            if let Some(index) = param("index") {
+                args.extend([String::from("--index"), index]);
+            } else if let Some(direction) = param("direction") {
+                args.push(format!("--{}", direction));
+            }
+        }
+        (Method::Post, c) if ACTIONS.contains(&c) => {
+            args.push(c.to_string());
+            args.extend(param("value"));
+        }
+        _ => return None,
+    }
+
+    Some(args)
+}
+
+/// The cover art of the current player, or of the `player` query parameter.
+fn cover_art(manager: &PlayerManager, query: &[(String, String)]) -> Response<Cursor<Vec<u8>>> {
+    let player = match query.iter().find(|(k, _)| k == "player") {
+        Some((_, name)) => Ok(manager.player(name)),
+        None => manager.current_or_init(),
+    };
+
+    let file = player.and_then(|p| {
+        let metadata = p.metadata()?;
+        art::fetch(
+            manager.cache_path(),
+            p.name(),
+            &metadata,
+            None,
+            manager.config().art.musicbrainz,
+        )
+    });
+
+    let bytes = match file.and_then(|f| std::fs::read(&f).map_err(Error::from)) {
+        Ok(b) => b,
+        Err(why) => return error_response(404, &why.to_string()),
+    };
+
+    // Caches written by older versions may hold whatever the art URL named.
+    let content_type = match art::content_type(&bytes) {
+        Some(t) => t,
+        None => return error_response(415, "The cover art is not an image."),
+    };
+
+    Response::from_data(bytes).with_header(header_value("Content-Type", content_type))
+}
+
+fn respond(output: Result<String>) -> Response<Cursor<Vec<u8>>> {
+    match output {
+        Ok(o) if o.is_empty() => Response::from_data(Vec::new()).with_status_code(204),
+        Ok(o) => {
+            Response::from_string(o).with_header(header_value("Content-Type", "application/json"))
+        }
+        Err(why) => {
+            let status = match why.exit_code() {
+                2 => 400,
+                4 | 5 | 7 => 404,
+                8 => 409,
+                _ => 500,
+            };
+
+            error_response(status, &why.to_string())
+        }
+    }
+}
+
+fn error_response(status: u16, message: &str) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(json!({ "error": message }).to_string())
+        .with_status_code(StatusCode(status))
+        .with_header(header_value("Content-Type", "application/json"))
+}
+
+fn is_authorized(request: &Request, query: &[(String, String)], token: &str) -> bool {
+    let bearer = header(request, "Authorization").and_then(|h| h.strip_prefix("Bearer "));
+    let param = query
+        .iter()
+        .find(|(k, _)| k == "token")
+        .map(|(_, v)| v.as_str());
+
+    bearer
+        .or(param)
+        .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+}
+
+/// Compares in a time that does not tell how much of `given` is right.
+fn constant_time_eq(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Whether the request comes from a page served here, or not from a page at
+/// all. Browsers send the `Origin` of cross-origin requests, including the
+/// simple `POST`s they make without asking first, and of WebSockets.
+fn is_same_origin(request: &Request) -> bool {
+    let origin = match header(request, "Origin") {
+        Some(origin) => origin,
+        None => return true,
+    };
+
+    let origin_host = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+
+    origin_host.is_some() && origin_host == header(request, "Host")
+}
+
+/// Whether the `Host` the request was sent to is an address, `localhost` or
+/// the host of `bind`, rather than a name a page may have pointed here.
+fn is_local_host(request: &Request, bind: &str) -> bool {
+    let host = match header(request, "Host") {
+        Some(host) => host_name(host),
+        None => return true,
+    };
+
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<std::net::IpAddr>().is_ok()
+        || host.eq_ignore_ascii_case(host_name(bind))
+}
+
+/// `host:port` without the port, and IPv6 addresses without their brackets.
+fn host_name(address: &str) -> &str {
+    match address.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => address.rsplit_once(':').map_or(address, |(host, _)| host),
+    }
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+fn header_value(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn split_url(url: &str) -> (String, Vec<(String, String)>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    (
+        path.to_string(),
+        form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect(),
+    )
+}
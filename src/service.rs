@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use multiplayerctl::{Error, PlayerManager, Result};
+use zbus::blocking::connection;
+use zbus::fdo;
+use zbus::interface;
+
+use crate::daemon::pause_others;
+
+const SERVICE_NAME: &str = "org.duckonaut.multiplayerctl1";
+const SERVICE_PATH: &str = "/org/duckonaut/multiplayerctl1";
+
+/// Registers `org.duckonaut.multiplayerctl1` on the session bus, letting other
+/// programs query and switch the current player without running the CLI.
+/// Blocks while announcing switches through `CurrentPlayerChanged`.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let connection = connection::Builder::session()
+        .and_then(|b| b.name(SERVICE_NAME))
+        .and_then(|b| {
+            b.serve_at(
+                SERVICE_PATH,
+                Service {
+                    manager: Arc::clone(&manager),
+                },
+            )
+        })
+        .and_then(|b| b.build())
+        .map_err(|why| Error::Other(format!("Failed to register {}: {}", SERVICE_NAME, why)))?;
+
+    let switches = manager.watch_current()?;
+    let mut last = manager.current_name().ok();
+
+    loop {
+        switches.wait()?;
+
+        let current = manager.current_name().ok();
+
+        if current == last {
+            continue;
+        }
+
+        if let Some(player) = &current {
+            let emitted = connection.emit_signal(
+                None::<()>,
+                SERVICE_PATH,
+                SERVICE_NAME,
+                "CurrentPlayerChanged",
+                &(player.as_str(),),
+            );
+
+            if let Err(why) = emitted {
+                eprintln!("Failed to announce the current player: {}", why);
+            }
+        }
+
+        last = current;
+    }
+}
+
+struct Service {
+    manager: Arc<PlayerManager>,
+}
+
+#[interface(name = "org.duckonaut.multiplayerctl1")]
+impl Service {
+    /// Returns the current player, picking one first if needed.
+    fn get_current_player(&self) -> fdo::Result<String> {
+        self.manager.init().map_err(failed)?;
+        self.manager.current_name().map_err(failed)
+    }
+
+    /// Makes `player` the current player.
+    fn switch(&self, player: String) -> fdo::Result<()> {
+        self.manager.switch_to(&player).map_err(failed)?;
+
+        // A player switched to while already playing does not announce anything.
+        if self.manager.config().daemon.exclusive {
+            pause_others(&self.manager);
+        }
+
+        Ok(())
+    }
+
+    /// Lists the players that can be switched to.
+    fn list_players(&self) -> fdo::Result<Vec<String>> {
+        self.manager.players().map_err(failed)
+    }
+
+    /// Sent whenever the current player changes, by any means.
+    #[zbus(signal)]
+    async fn current_player_changed(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        player: &str,
+    ) -> zbus::Result<()>;
+}
+
+fn failed(why: Error) -> fdo::Error {
+    fdo::Error::Failed(why.to_string())
+}
@@ -0,0 +1,77 @@
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use multiplayerctl::{ChildGuard, Result};
+
+/// How long to wait for stdout to take the rest of the output, as whatever
+/// reads it may have stopped.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Has SIGINT and SIGTERM end the command cleanly: the playerctl processes
+/// it started are killed and reaped, the flags of stdin, stdout and stderr
+/// are put back as they were and the output is flushed. It then dies of the
+/// signal, so that shells report 130 or 143 and systemd a clean stop.
+///
+/// Has to be called before any other thread is started, as only those
+/// started afterwards leave the signals to the thread waiting for them.
+pub fn install() -> Result<()> {
+    // Children share the open files, and may leave them non-blocking when
+    // killed, which would break the shell we were run from.
+    let flags = [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO]
+        .map(|fd| (fd, unsafe { libc::fcntl(fd, libc::F_GETFL) }));
+
+    let signals = unsafe {
+        let mut signals: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        libc::sigaddset(&mut signals, libc::SIGINT);
+        libc::sigaddset(&mut signals, libc::SIGTERM);
+        signals
+    };
+
+    let blocked = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut()) };
+
+    if blocked != 0 {
+        return Err(io::Error::from_raw_os_error(blocked).into());
+    }
+
+    thread::spawn(move || {
+        let mut signal = 0;
+
+        if unsafe { libc::sigwait(&signals, &mut signal) } != 0 {
+            return;
+        }
+
+        ChildGuard::kill_all();
+
+        for (fd, flags) in flags {
+            if flags != -1 {
+                unsafe { libc::fcntl(fd, libc::F_SETFL, flags) };
+            }
+        }
+
+        flush();
+
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::pthread_sigmask(libc::SIG_UNBLOCK, &signals, std::ptr::null_mut());
+            libc::raise(signal);
+        }
+    });
+
+    Ok(())
+}
+
+/// Flushes stdout, unless it cannot take any more, in which case what is
+/// left is lost rather than holding up the exit.
+fn flush() {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let _ = io::stdout().flush();
+        let _ = sender.send(());
+    });
+
+    let _ = receiver.recv_timeout(FLUSH_TIMEOUT);
+}
@@ -0,0 +1,190 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use multiplayerctl::{Error, PlaybackStatus, PlayerManager, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{fade, format_duration, json_line, session_file, SleepCommand};
+
+/// How often the daemon looks at the timer.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A sleep timer, kept in the state directory so that it is there for the
+/// daemon to act on, even across restarts.
+#[derive(Debug, Serialize, Deserialize)]
+struct Timer {
+    /// When the players are paused, in seconds since the epoch.
+    deadline: f64,
+    /// How long before the deadline to start fading out, in seconds.
+    fade: Option<f64>,
+    quit: bool,
+}
+
+/// Sets, shows or cancels the sleep timer.
+pub fn command(
+    manager: &PlayerManager,
+    duration: Option<Duration>,
+    fade: Option<Duration>,
+    quit: bool,
+    command: Option<SleepCommand>,
+    json: bool,
+) -> Result<String> {
+    match (duration, command) {
+        (Some(duration), _) => {
+            if fade.is_some_and(|f| f > duration) {
+                return Err(Error::InvalidArgument(String::from(
+                    "The fade cannot be longer than the timer.",
+                )));
+            }
+
+            let timer = Timer {
+                deadline: now() + duration.as_secs_f64(),
+                fade: fade.map(|f| f.as_secs_f64()),
+                quit,
+            };
+
+            store(manager, Some(&timer))?;
+
+            Ok(String::new())
+        }
+        (None, Some(SleepCommand::Cancel)) => store(manager, None).map(|_| String::new()),
+        (None, _) => Ok(status(manager, json)),
+    }
+}
+
+fn status(manager: &PlayerManager, json: bool) -> String {
+    let timer = load(manager);
+    let remaining = timer.as_ref().map(|t| (t.deadline - now()).max(0.0));
+
+    if json {
+        return json_line(json!({
+            "remaining": remaining,
+            "fade": timer.as_ref().and_then(|t| t.fade),
+            "quit": timer.as_ref().map(|t| t.quit),
+        }));
+    }
+
+    let (timer, remaining) = match (timer, remaining) {
+        (Some(t), Some(r)) => (t, r),
+        _ => return String::from("No sleep timer set.\n"),
+    };
+
+    let action = if timer.quit { "Quitting" } else { "Pausing" };
+    // Rounded up, so that it does not show 0:00 while there is time left.
+    let micros = |seconds: f64| (seconds.ceil() * 1_000_000.0) as i64;
+
+    match timer.fade {
+        Some(fade) => format!(
+            "{} players in {}, fading out over the last {}.\n",
+            action,
+            format_duration(micros(remaining)),
+            format_duration(micros(fade))
+        ),
+        None => format!(
+            "{} players in {}.\n",
+            action,
+            format_duration(micros(remaining))
+        ),
+    }
+}
+
+/// Pauses or quits every player that is playing once the sleep timer goes
+/// off. Never returns.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    loop {
+        thread::sleep(CHECK_INTERVAL);
+
+        let timer = match load(&manager) {
+            Some(t) => t,
+            None => continue,
+        };
+
+        let remaining = timer.deadline - now();
+
+        if remaining > timer.fade.unwrap_or(0.0) {
+            continue;
+        }
+
+        // Taken down first, so that it goes off only once.
+        if let Err(why) = store(&manager, None) {
+            eprintln!("{}", why);
+            continue;
+        }
+
+        go_off(
+            &manager,
+            &timer,
+            Duration::from_secs_f64(remaining.max(0.0)),
+        );
+    }
+}
+
+/// Fades out, pauses and possibly quits the players that are playing, all
+/// at once.
+fn go_off(manager: &PlayerManager, timer: &Timer, fade: Duration) {
+    let players: Vec<String> = manager
+        .players()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| matches!(manager.player(name).status(), Ok(PlaybackStatus::Playing)))
+        .collect();
+
+    thread::scope(|scope| {
+        for name in &players {
+            scope.spawn(move || {
+                let player = manager.player(name);
+
+                let result = match fade.is_zero() {
+                    true => player.pause(),
+                    false => fade::pause(&player, fade),
+                };
+
+                let result = match timer.quit {
+                    true => result.and_then(|_| player.quit()),
+                    false => result,
+                };
+
+                if let Err(why) = result {
+                    eprintln!("{}", why);
+                }
+            });
+        }
+    });
+}
+
+fn timer_file(manager: &PlayerManager) -> PathBuf {
+    session_file(manager, "sleep")
+}
+
+fn load(manager: &PlayerManager) -> Option<Timer> {
+    let contents = fs::read_to_string(timer_file(manager)).ok()?;
+
+    serde_json::from_str(&contents).ok()
+}
+
+/// Replaces the timer, or removes it with `None`.
+fn store(manager: &PlayerManager, timer: Option<&Timer>) -> Result<()> {
+    let path = timer_file(manager);
+
+    let stored = match timer {
+        Some(t) => fs::write(&path, json!(t).to_string()),
+        None => match fs::remove_file(&path) {
+            Err(why) if why.kind() == ErrorKind::NotFound => Ok(()),
+            result => result,
+        },
+    };
+
+    stored.map_err(|why| Error::Other(format!("Failed to write the sleep timer: {}", why)))
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
@@ -0,0 +1,525 @@
+//! A [`PlayerBackend`] for [Snapcast](https://github.com/badaix/snapcast)
+//! groups, spoken to over the server's JSON-RPC control port, so that the
+//! volume keys work for a room as a whole.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::backend::PlayerBackend;
+use crate::config::SnapcastConfig;
+use crate::error::{Error, Result};
+use crate::player::{Capability, Change, Metadata, MetadataValue, PlaybackStatus};
+
+/// How long to wait for the server, so that a missing one does not hold up
+/// listing the other players.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait before connecting again when the server went away.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A Snapcast server, whose groups are listed as players named
+/// `<name>.<group>`.
+pub struct Snapcast {
+    config: SnapcastConfig,
+}
+
+/// A group of clients playing the same stream, as listed by the server.
+struct Group {
+    id: String,
+    /// The player it is listed as.
+    player: String,
+    muted: bool,
+    /// The stream it plays, from `Server.GetStatus`.
+    stream: Value,
+    clients: Vec<Client>,
+}
+
+struct Client {
+    id: String,
+    /// In percent.
+    volume: f64,
+    muted: bool,
+}
+
+/// A connection to the control port, over which requests are sent one at a
+/// time. The server also sends notifications on it whenever anything
+/// changes.
+struct Connection {
+    name: String,
+    reader: BufReader<TcpStream>,
+    next_id: u64,
+}
+
+impl Connection {
+    /// Sends a request and waits for its result, skipping the notifications
+    /// that come in meanwhile.
+    fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+
+        let request = json!({
+            "id": self.next_id,
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        });
+
+        writeln!(self.reader.get_mut(), "{}", request)
+            .map_err(|why| Error::player_command(&self.name, why))?;
+
+        loop {
+            let message = self.receive()?;
+
+            if message["id"].as_u64() != Some(self.next_id) {
+                continue;
+            }
+
+            if let Some(error) = message.get("error") {
+                let why = error["message"].as_str().unwrap_or("Unknown error");
+
+                return Err(Error::player_command(&self.name, why));
+            }
+
+            return Ok(message["result"].clone());
+        }
+    }
+
+    /// Reads the next message from the server.
+    fn receive(&mut self) -> Result<Value> {
+        let mut line = String::new();
+
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err(Error::player_command(
+                &self.name,
+                "Snapcast closed the connection",
+            )),
+            Ok(_) => serde_json::from_str(&line).map_err(|why| {
+                Error::player_command(&self.name, format!("Invalid message: {}", why))
+            }),
+            Err(why) => Err(Error::player_command(&self.name, why)),
+        }
+    }
+}
+
+impl Snapcast {
+    pub fn new(config: SnapcastConfig) -> Self {
+        Self { config }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let address = format!("{}:{}", self.config.host, self.config.port);
+
+        let unreachable = |why: std::io::Error| {
+            Error::UnknownPlayer(format!("{} ({}: {})", self.config.name, address, why))
+        };
+
+        let socket_address = (self.config.host.as_str(), self.config.port)
+            .to_socket_addrs()
+            .map_err(unreachable)?
+            .next()
+            .ok_or_else(|| {
+                unreachable(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "no address",
+                ))
+            })?;
+
+        let stream =
+            TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT).map_err(unreachable)?;
+
+        stream
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(unreachable)?;
+
+        Ok(Connection {
+            name: self.config.name.clone(),
+            reader: BufReader::new(stream),
+            next_id: 0,
+        })
+    }
+
+    /// All groups on the server.
+    fn groups(&self, connection: &mut Connection) -> Result<Vec<Group>> {
+        let status = connection.call("Server.GetStatus", json!({}))?;
+        let server = &status["server"];
+
+        let streams = server["streams"].as_array().cloned().unwrap_or_default();
+        let mut groups = Vec::new();
+
+        for group in server["groups"].as_array().into_iter().flatten() {
+            let all_clients = group["clients"].as_array().cloned().unwrap_or_default();
+
+            // Clients that are not connected play nothing and keep their
+            // volume out of the group's.
+            let connected: Vec<&Value> = all_clients
+                .iter()
+                .filter(|c| c["connected"].as_bool().unwrap_or(true))
+                .collect();
+
+            let clients = connected
+                .iter()
+                .map(|c| Client {
+                    id: c["id"].as_str().unwrap_or_default().to_string(),
+                    volume: c["config"]["volume"]["percent"].as_f64().unwrap_or(100.0),
+                    muted: c["config"]["volume"]["muted"].as_bool().unwrap_or(false),
+                })
+                .collect();
+
+            let id = group["id"].as_str().unwrap_or_default().to_string();
+
+            let stream = streams
+                .iter()
+                .find(|s| s["id"] == group["stream_id"])
+                .cloned()
+                .unwrap_or(Value::Null);
+
+            groups.push(Group {
+                player: format!("{}.{}", self.config.name, group_name(group, &id)),
+                id,
+                muted: group["muted"].as_bool().unwrap_or(false),
+                stream,
+                clients,
+            });
+        }
+
+        Ok(groups)
+    }
+
+    /// The group listed as `player`, and a connection to act on it.
+    fn group(&self, player: &str) -> Result<(Group, Connection)> {
+        let mut connection = self.connect()?;
+
+        let group = self
+            .groups(&mut connection)?
+            .into_iter()
+            .find(|g| g.player == player)
+            .ok_or_else(|| Error::UnknownPlayer(player.to_string()))?;
+
+        Ok((group, connection))
+    }
+
+    /// Waits for notifications on `connection` until it fails, reporting
+    /// each as a change to every group, and groups coming and going.
+    fn listen(
+        &self,
+        connection: &mut Connection,
+        players: &mut Vec<String>,
+        f: &mut dyn FnMut(&str, Change),
+    ) -> Result<()> {
+        connection
+            .reader
+            .get_ref()
+            .set_read_timeout(None)
+            .map_err(|why| Error::player_command(&self.config.name, why))?;
+
+        loop {
+            let message = connection.receive()?;
+
+            if message.get("method").is_none() {
+                continue;
+            }
+
+            let current = self.list_players().unwrap_or_default();
+
+            for player in players.iter().filter(|p| !current.contains(p)) {
+                f(player, Change::Vanished);
+            }
+
+            for player in &current {
+                match players.contains(player) {
+                    true => f(player, Change::Properties),
+                    false => f(player, Change::Appeared),
+                }
+            }
+
+            *players = current;
+        }
+    }
+}
+
+impl PlayerBackend for Snapcast {
+    fn owns(&self, player: &str) -> bool {
+        player
+            .strip_prefix(&self.config.name)
+            .is_some_and(|rest| rest.starts_with('.'))
+    }
+
+    fn list_players(&self) -> Result<Vec<String>> {
+        let mut connection = self.connect()?;
+
+        Ok(self
+            .groups(&mut connection)?
+            .into_iter()
+            .map(|g| g.player)
+            .collect())
+    }
+
+    fn playback_status(&self, player: &str) -> Result<PlaybackStatus> {
+        let (group, _) = self.group(player)?;
+
+        // Newer servers pass on the status of the player feeding the
+        // stream; otherwise all that is known is whether audio comes in.
+        let status = match group.stream["properties"]["playbackStatus"].as_str() {
+            Some(status) => status,
+            None => group.stream["status"].as_str().unwrap_or_default(),
+        };
+
+        Ok(match status {
+            "playing" => PlaybackStatus::Playing,
+            "paused" => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Stopped,
+        })
+    }
+
+    fn metadata(&self, player: &str) -> Result<Metadata> {
+        let (group, _) = self.group(player)?;
+        let metadata = &group.stream["properties"]["metadata"];
+
+        let mut values = BTreeMap::new();
+
+        let text = |key: &str| {
+            metadata[key]
+                .as_str()
+                .filter(|s| !s.is_empty())
+                .map(|s| MetadataValue::Text(s.to_string()))
+        };
+
+        for (key, field) in [
+            ("title", "xesam:title"),
+            ("album", "xesam:album"),
+            ("artUrl", "mpris:artUrl"),
+            ("url", "xesam:url"),
+        ] {
+            if let Some(value) = text(key) {
+                values.insert(String::from(field), value);
+            }
+        }
+
+        let artists: Vec<String> = match &metadata["artist"] {
+            Value::Array(artists) => artists
+                .iter()
+                .filter_map(|a| a.as_str().map(String::from))
+                .collect(),
+            Value::String(artist) => vec![artist.clone()],
+            _ => Vec::new(),
+        };
+
+        if !artists.is_empty() {
+            values.insert(String::from("xesam:artist"), MetadataValue::List(artists));
+        }
+
+        if let Some(seconds) = metadata["duration"].as_f64() {
+            values.insert(
+                String::from("mpris:length"),
+                MetadataValue::Integer((seconds * 1_000_000.0) as i64),
+            );
+        }
+
+        // Without a title from the source, the stream's name is the best
+        // description of what is playing.
+        if !values.contains_key("xesam:title") {
+            if let Some(stream) = group.stream["id"].as_str() {
+                values.insert(
+                    String::from("xesam:title"),
+                    MetadataValue::Text(stream.to_string()),
+                );
+            }
+        }
+
+        Ok(Metadata::new(values))
+    }
+
+    /// Passed on to the player feeding the group's stream, for streams that
+    /// support it.
+    fn player_command(&self, player: &str, command: &str) -> Result<()> {
+        let command = match command {
+            "play" => "play",
+            "pause" => "pause",
+            "play-pause" => "playPause",
+            "next" => "next",
+            "previous" => "previous",
+            "stop" => "stop",
+            other => {
+                return Err(Error::InvalidArgument(format!(
+                    "Unknown player command: {}",
+                    other
+                )))
+            }
+        };
+
+        let (group, mut connection) = self.group(player)?;
+
+        connection
+            .call(
+                "Stream.Control",
+                json!({ "id": group.stream["id"], "command": command }),
+            )
+            .map(|_| ())
+    }
+
+    fn can(&self, player: &str, capability: Capability) -> bool {
+        // The volume can always be changed, whatever the stream allows.
+        if capability == Capability::Control {
+            return true;
+        }
+
+        let property = match capability {
+            Capability::Play => "canPlay",
+            Capability::Pause => "canPause",
+            Capability::GoNext => "canGoNext",
+            Capability::GoPrevious => "canGoPrevious",
+            _ => "canSeek",
+        };
+
+        self.group(player).is_ok_and(|(group, _)| {
+            group.stream["properties"][property]
+                .as_bool()
+                .unwrap_or(false)
+        })
+    }
+
+    /// The average volume of the clients in the group.
+    fn volume(&self, player: &str) -> Result<f64> {
+        let (group, _) = self.group(player)?;
+
+        if group.clients.is_empty() {
+            return Err(Error::Unsupported {
+                player: player.to_string(),
+                action: String::from("report its volume without clients"),
+            });
+        }
+
+        let total: f64 = group.clients.iter().map(|c| c.volume).sum();
+
+        Ok(total / group.clients.len() as f64 / 100.0)
+    }
+
+    /// Moves the volume of every client in the group, keeping them in
+    /// proportion the way Snapweb does: louder clients give way less when
+    /// lowering, quieter ones catch up more when raising.
+    fn set_volume(&self, player: &str, volume: f64) -> Result<()> {
+        let current = self.volume(player)? * 100.0;
+        let target = (volume * 100.0).clamp(0.0, 100.0);
+
+        let (group, mut connection) = self.group(player)?;
+
+        for client in &group.clients {
+            let percent = match target >= current {
+                true if current >= 100.0 => target,
+                true => {
+                    client.volume + (100.0 - client.volume) * (target - current) / (100.0 - current)
+                }
+                false if current <= 0.0 => target,
+                false => client.volume - client.volume * (current - target) / current,
+            };
+
+            connection.call(
+                "Client.SetVolume",
+                json!({
+                    "id": client.id,
+                    "volume": { "percent": percent.round().clamp(0.0, 100.0), "muted": client.muted },
+                }),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn muted(&self, player: &str) -> Result<bool> {
+        self.group(player).map(|(group, _)| group.muted)
+    }
+
+    fn set_muted(&self, player: &str, muted: bool) -> Result<()> {
+        let (group, mut connection) = self.group(player)?;
+
+        connection
+            .call("Group.SetMute", json!({ "id": group.id, "mute": muted }))
+            .map(|_| ())
+    }
+
+    /// Listens to the server's notifications on a connection of its own,
+    /// connecting again while the server is away. Never returns.
+    fn watch_changes(&self, f: &mut dyn FnMut(&str, Change)) -> Result<()> {
+        // The groups last seen; they only count as appearing once the
+        // server has been seen, so that those already there are not
+        // announced.
+        let mut players: Option<Vec<String>> = None;
+
+        loop {
+            let listed = self.connect().and_then(|mut connection| {
+                let current: Vec<String> = self
+                    .groups(&mut connection)?
+                    .into_iter()
+                    .map(|g| g.player)
+                    .collect();
+
+                Ok((connection, current))
+            });
+
+            match listed {
+                Ok((mut connection, current)) => {
+                    let mut known = players.take().unwrap_or_else(|| current.clone());
+
+                    for player in current.iter().filter(|p| !known.contains(p)) {
+                        f(player, Change::Appeared);
+                    }
+
+                    known = current;
+
+                    let _ = self.listen(&mut connection, &mut known, f);
+
+                    players = Some(known);
+
+                    // Not straight away, in case the server keeps hanging up.
+                    thread::sleep(CONNECT_TIMEOUT);
+                }
+                Err(_) => {
+                    for player in players.iter().flatten() {
+                        f(player, Change::Vanished);
+                    }
+
+                    players = players.map(|_| Vec::new());
+
+                    thread::sleep(RECONNECT_INTERVAL);
+                }
+            }
+        }
+    }
+}
+
+/// What a group is called in its player name: its own name, or else the
+/// names of its clients, with spaces replaced so that the name is easy to
+/// type.
+fn group_name(group: &Value, id: &str) -> String {
+    let client_name = |client: &Value| {
+        let configured = client["config"]["name"].as_str().filter(|n| !n.is_empty());
+
+        configured
+            .or_else(|| client["host"]["name"].as_str())
+            .map(String::from)
+    };
+
+    let name = match group["name"].as_str().filter(|n| !n.is_empty()) {
+        Some(name) => name.to_string(),
+        None => group["clients"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(client_name)
+            .collect::<Vec<_>>()
+            .join("+"),
+    };
+
+    let name = if name.is_empty() {
+        id.to_string()
+    } else {
+        name
+    };
+
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use multiplayerctl::{art, Error, PlaybackStatus, PlayerManager, Result};
+
+use crate::template::render_fields;
+use crate::{follow, player_fields};
+
+/// Keeps the `[stream]` files up to date with the current player until
+/// watching the players fails.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let config = manager.config().stream.clone();
+
+    // The last text written and art copied, so that unrelated changes do not
+    // make OBS reload the files.
+    let mut written: Option<String> = None;
+    let mut copied: Option<Option<PathBuf>> = None;
+
+    // `{position}` only changes by itself.
+    let tick = config
+        .format
+        .contains("{position}")
+        .then_some(Duration::from_secs(1));
+
+    follow::watch(
+        manager,
+        |_| tick,
+        |manager| {
+            let player = manager.current_or_init().ok().filter(|p| match p.status() {
+                Ok(PlaybackStatus::Playing) => true,
+                Ok(PlaybackStatus::Paused) => config.paused,
+                _ => false,
+            });
+
+            if let Some(path) = &config.text {
+                let text = match &player {
+                    Some(p) => render_fields(&config.format, &player_fields(manager, p)),
+                    None => String::new(),
+                };
+
+                if written.as_ref() != Some(&text) {
+                    match replace(path, text.as_bytes()) {
+                        Ok(()) => written = Some(text),
+                        Err(why) => eprintln!("{}", why),
+                    }
+                }
+            }
+
+            if let Some(path) = &config.art {
+                let art = player.as_ref().and_then(|p| {
+                    let metadata = p.metadata().ok()?;
+                    art::fetch(
+                        manager.cache_path(),
+                        p.name(),
+                        &metadata,
+                        None,
+                        manager.config().art.musicbrainz,
+                    )
+                    .ok()
+                });
+
+                if copied.as_ref() != Some(&art) {
+                    match copy_art(path, art.as_deref()) {
+                        Ok(()) => copied = Some(art),
+                        Err(why) => eprintln!("{}", why),
+                    }
+                }
+            }
+
+            Ok(ControlFlow::Continue(()))
+        },
+    )
+}
+
+/// Puts the art at `from` in place at `path`, or removes it if there is none.
+fn copy_art(path: &Path, from: Option<&Path>) -> Result<()> {
+    match from {
+        Some(from) => replace(path, &fs::read(from)?),
+        None => match fs::remove_file(path) {
+            Err(why) if why.kind() != ErrorKind::NotFound => Err(Error::Other(format!(
+                "Failed to remove {}: {}",
+                path.display(),
+                why
+            ))),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Writes `path` through a temporary file, so that readers never see it
+/// half-written.
+fn replace(path: &Path, bytes: &[u8]) -> Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.part", name));
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(&temporary, bytes)
+        .and_then(|_| fs::rename(&temporary, path))
+        .map_err(|why| Error::Other(format!("Failed to write {}: {}", path.display(), why)))
+}
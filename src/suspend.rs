@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use multiplayerctl::{Error, PlayerManager, Result};
+use zbus::blocking::{Connection, MessageIterator, Proxy};
+use zbus::message::Type;
+use zbus::zvariant::OwnedFd;
+use zbus::MatchRule;
+
+use crate::daemon::{pause_playing, resume};
+
+const LOGIND: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+const LOGIND_MANAGER: &str = "org.freedesktop.login1.Manager";
+
+/// Holds off suspending until the players given by `suspend.pause` are
+/// paused, and with `suspend.resume` plays them again after waking up.
+/// Never returns unless logind goes away.
+pub fn run(manager: Arc<PlayerManager>) -> Result<()> {
+    let config = &manager.config().suspend;
+    let logind_error =
+        |why: zbus::Error| Error::Other(format!("Failed to talk to logind: {}", why));
+
+    let connection = Connection::system().map_err(logind_error)?;
+    let logind =
+        Proxy::new(&connection, LOGIND, LOGIND_PATH, LOGIND_MANAGER).map_err(logind_error)?;
+
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(LOGIND)
+        .and_then(|r| r.interface(LOGIND_MANAGER))
+        .and_then(|r| r.member("PrepareForSleep"))
+        .map_err(logind_error)?
+        .build();
+
+    // Subscribed to before inhibiting, so that no suspend slips through.
+    let messages =
+        MessageIterator::for_match_rule(rule, &connection, None).map_err(logind_error)?;
+
+    // Suspending waits (up to logind's `InhibitDelayMaxSec`) for this to be
+    // closed.
+    let mut inhibitor = Some(inhibit(&logind).map_err(logind_error)?);
+    let mut paused = Vec::new();
+
+    for message in messages {
+        let sleeping = match message.map(|m| m.body().deserialize::<bool>()) {
+            Ok(Ok(s)) => s,
+            _ => continue,
+        };
+
+        if sleeping {
+            paused = pause_playing(&manager, config.pause);
+            inhibitor = None;
+            continue;
+        }
+
+        if config.resume {
+            resume(&manager, &paused);
+        }
+
+        paused.clear();
+
+        if inhibitor.is_none() {
+            match inhibit(&logind) {
+                Ok(fd) => inhibitor = Some(fd),
+                Err(why) => eprintln!("Failed to delay suspending: {}", why),
+            }
+        }
+    }
+
+    Err(Error::Other(String::from("Stopped watching for suspend.")))
+}
+
+fn inhibit(logind: &Proxy) -> zbus::Result<OwnedFd> {
+    logind.call(
+        "Inhibit",
+        &(
+            "sleep",
+            "multiplayerctl",
+            "Pausing players before suspending",
+            "delay",
+        ),
+    )
+}
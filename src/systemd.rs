@@ -0,0 +1,301 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use multiplayerctl::{ipc, Error, Result};
+use serde_json::json;
+
+use crate::{json_line, Opt, ServiceCommand};
+
+const DAEMON_UNIT: &str = "multiplayerctl.service";
+const SOCKET_UNIT: &str = "multiplayerctl.socket";
+const NOTIFY_UNIT: &str = "multiplayerctl-notify.service";
+
+pub fn run(opt: &Opt, command: &ServiceCommand) -> Result<()> {
+    match command {
+        ServiceCommand::Install { notify } => install(opt, *notify),
+        ServiceCommand::Uninstall => uninstall(),
+        ServiceCommand::Start => systemctl(&["start", SOCKET_UNIT, DAEMON_UNIT]),
+        // With the socket, or the next command would start the daemon again.
+        ServiceCommand::Stop => systemctl(&["stop", SOCKET_UNIT, DAEMON_UNIT]),
+        ServiceCommand::Status => status(opt),
+    }
+}
+
+/// Writes the units, enables them and starts the daemon. The daemon is
+/// started at login, and by systemd as soon as a command is sent to its
+/// socket, should it not be running by then.
+fn install(opt: &Opt, notify: bool) -> Result<()> {
+    let dir = unit_directory()?;
+
+    let socket = ipc::socket_path().ok_or_else(|| {
+        Error::Other(String::from(
+            "The daemon has no socket, as XDG_RUNTIME_DIR is not set.",
+        ))
+    })?;
+
+    let executable = env::current_exe()
+        .map_err(|why| Error::Other(format!("Cannot tell where multiplayerctl is: {}", why)))?;
+
+    // The daemon reads the same config as the commands that install it.
+    let mut command = quote(&executable.to_string_lossy());
+
+    if let Some(config) = &opt.config {
+        let config = fs::canonicalize(config).unwrap_or_else(|_| config.clone());
+
+        command.push_str(&format!(" --config {}", quote(&config.to_string_lossy())));
+    }
+
+    fs::create_dir_all(&dir)
+        .map_err(|why| Error::Other(format!("Failed to create {}: {}", dir.display(), why)))?;
+
+    write_unit(&dir, SOCKET_UNIT, &socket_unit(&socket))?;
+    write_unit(&dir, DAEMON_UNIT, &daemon_unit(&command))?;
+
+    let mut units = vec![SOCKET_UNIT, DAEMON_UNIT];
+
+    if notify {
+        write_unit(&dir, NOTIFY_UNIT, &notify_unit(&command))?;
+        units.push(NOTIFY_UNIT);
+    }
+
+    systemctl(&["daemon-reload"])?;
+
+    // The socket must be listening before the daemon starts, or the daemon
+    // binds it on its own.
+    let _ = systemctl(&["stop", DAEMON_UNIT]);
+
+    let mut enable = vec!["enable", "--now"];
+    enable.extend(&units);
+    systemctl(&enable)?;
+
+    println!(
+        "Installed {} in {}. The daemon now starts at login.",
+        units.join(", "),
+        dir.display()
+    );
+
+    Ok(())
+}
+
+fn uninstall() -> Result<()> {
+    let dir = unit_directory()?;
+
+    let installed: Vec<&str> = [SOCKET_UNIT, DAEMON_UNIT, NOTIFY_UNIT]
+        .into_iter()
+        .filter(|unit| dir.join(unit).exists())
+        .collect();
+
+    if installed.is_empty() {
+        println!("Nothing is installed in {}.", dir.display());
+
+        return Ok(());
+    }
+
+    let mut disable = vec!["disable", "--now"];
+    disable.extend(&installed);
+    systemctl(&disable)?;
+
+    for unit in &installed {
+        let path = dir.join(unit);
+
+        fs::remove_file(&path)
+            .map_err(|why| Error::Other(format!("Failed to remove {}: {}", path.display(), why)))?;
+    }
+
+    systemctl(&["daemon-reload"])?;
+
+    println!("Removed {}.", installed.join(", "));
+
+    Ok(())
+}
+
+/// Reports whether the units are installed, enabled and running, and whether
+/// the daemon answers. Fails if it does not.
+fn status(opt: &Opt) -> Result<()> {
+    let dir = unit_directory()?;
+
+    let units: Vec<(&str, bool, String, String)> = [SOCKET_UNIT, DAEMON_UNIT, NOTIFY_UNIT]
+        .into_iter()
+        .map(|unit| {
+            let installed = dir.join(unit).exists();
+
+            (
+                unit,
+                installed,
+                systemctl_query("is-enabled", unit),
+                systemctl_query("is-active", unit),
+            )
+        })
+        .collect();
+
+    // Errors the daemon reports are answers all the same.
+    let answering = matches!(
+        ipc::send(&[String::from("player")]),
+        Ok(Ok(_) | Err(Error::Remote { .. }))
+    );
+
+    match opt.json {
+        true => print!(
+            "{}",
+            json_line(json!({
+                "units": units
+                    .iter()
+                    .map(|(unit, installed, enabled, active)| json!({
+                        "unit": unit,
+                        "installed": installed,
+                        "enabled": enabled,
+                        "active": active,
+                    }))
+                    .collect::<Vec<_>>(),
+                "socket": ipc::socket_path(),
+                "answering": answering,
+            }))
+        ),
+        false => {
+            for (unit, installed, enabled, active) in &units {
+                match installed {
+                    true => println!("{}: {}, {}", unit, enabled, active),
+                    false => println!("{}: not installed", unit),
+                }
+            }
+
+            match ipc::socket_path() {
+                Some(socket) if answering => {
+                    println!("The daemon answers on {}.", socket.display())
+                }
+                Some(socket) => println!("The daemon does not answer on {}.", socket.display()),
+                None => println!("The daemon has no socket, as XDG_RUNTIME_DIR is not set."),
+            }
+        }
+    }
+
+    match answering {
+        true => Ok(()),
+        false => Err(Error::Other(String::from(
+            "The daemon is not running; see `journalctl --user -u multiplayerctl` for why.",
+        ))),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user`, where the units of the user go.
+fn unit_directory() -> Result<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        Some(v) => PathBuf::from(v),
+        None => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => return Err(Error::Other(String::from("No $HOME defined!"))),
+        },
+    };
+
+    Ok(base.join("systemd").join("user"))
+}
+
+fn write_unit(dir: &Path, name: &str, contents: &str) -> Result<()> {
+    let path = dir.join(name);
+
+    fs::write(&path, contents)
+        .map_err(|why| Error::Other(format!("Failed to write {}: {}", path.display(), why)))
+}
+
+fn socket_unit(socket: &Path) -> String {
+    format!(
+        "[Unit]
+Description=multiplayerctl daemon socket
+
+[Socket]
+ListenStream={}
+SocketMode=0600
+DirectoryMode=0700
+
+[Install]
+WantedBy=sockets.target
+",
+        socket.display().to_string().replace('%', "%%")
+    )
+}
+
+fn daemon_unit(command: &str) -> String {
+    format!(
+        "[Unit]
+Description=multiplayerctl daemon
+Documentation=https://github.com/Duckonaut/multiplayerctl
+Requires={socket}
+After={socket}
+
+[Service]
+ExecStart={command} daemon
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+",
+        socket = SOCKET_UNIT,
+        command = command
+    )
+}
+
+fn notify_unit(command: &str) -> String {
+    format!(
+        "[Unit]
+Description=multiplayerctl track notifications
+Documentation=https://github.com/Duckonaut/multiplayerctl
+
+[Service]
+ExecStart={} notify
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+",
+        command
+    )
+}
+
+/// Quotes `arg` for a unit's command line, which has its own rules: double
+/// quotes, backslash escapes and `%` specifiers.
+fn quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+
+    match safe {
+        true => arg,
+        false => format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+/// Runs `systemctl --user` with `args`, failing with what it printed.
+fn systemctl(args: &[&str]) -> Result<()> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .map_err(|why| Error::Other(format!("Failed to run systemctl: {}", why)))?;
+
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(Error::Other(format!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+/// Asks systemctl about `unit` with a command like `is-active`, which
+/// answers with a word even when the answer is no.
+fn systemctl_query(query: &str, unit: &str) -> String {
+    Command::new("systemctl")
+        .args(["--user", query, unit])
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|answer| !answer.is_empty())
+        .unwrap_or_else(|| String::from("unknown"))
+}
@@ -0,0 +1,293 @@
+use std::cell::Cell;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+thread_local! {
+    /// Whether [`render_styled`] colors what it renders, as `--color` says.
+    /// Per thread, as the daemon runs commands for clients that differ.
+    static COLOR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Has [`render_styled`] on this thread color its output, or not.
+pub fn set_color(enabled: bool) {
+    COLOR.set(enabled);
+}
+
+/// Replaces `{field}` placeholders in `format` and expands `\t` and `\n`.
+/// Unknown placeholders are left as they are.
+///
+/// A placeholder may give a width, as in `{title:30}`: longer values are cut
+/// short with an ellipsis and shorter ones padded with spaces, on the right,
+/// or on the left with `{title:>30}` and on both sides with `{title:^30}`.
+/// Widths are in terminal columns, so that wide characters such as CJK ones
+/// count twice and are never cut in half.
+///
+/// `upper(…)`, `lower(…)` and `trim(…)` change a value, as in
+/// `{upper(title)}`, and may be nested. `{?artist}…{/}` shows what is in
+/// between only if the field has a value, i.e. is neither empty nor `-`.
+///
+/// Style tags such as `{bold}` or `{color:green}` are left out; see
+/// [`render_styled`].
+pub fn render_fields(format: &str, fields: &[(&str, String)]) -> String {
+    render(format, fields, str::to_string, false)
+}
+
+/// Like [`render_fields`], but for a terminal: `{bold}`, `{dim}`,
+/// `{italic}`, `{underline}`, `{color:<name>}` and `{reset}` become ANSI
+/// escape codes if colors are on, and everything is reset at the end.
+pub fn render_styled(format: &str, fields: &[(&str, String)]) -> String {
+    let styled = COLOR.get();
+    let mut rendered = render(format, fields, str::to_string, styled);
+
+    if styled && rendered.contains('\x1b') {
+        rendered.push_str(RESET);
+    }
+
+    rendered
+}
+
+/// Like [`render_fields`], but escapes the values for Pango markup, once
+/// they have been fitted to their width.
+pub fn render_markup(format: &str, fields: &[(&str, String)]) -> String {
+    render(format, fields, escape_markup, false)
+}
+
+/// Like [`render_fields`], but keeps the values from being taken for
+/// polybar's formatting tags, which start with `%{`.
+pub fn render_polybar(format: &str, fields: &[(&str, String)]) -> String {
+    render(format, fields, |value| value.replace("%{", "%%{"), false)
+}
+
+/// Whether `format` is written for playerctl, whose placeholders are in
+/// double braces like `{{ title }}`, and has to be passed on to it.
+pub fn is_playerctl(format: &str) -> bool {
+    format.contains("{{")
+}
+
+/// How wide `{progress_bar}` is without a width.
+const PROGRESS_BAR_WIDTH: usize = 10;
+
+const RESET: &str = "\x1b[0m";
+
+/// The names `{color:<name>}` accepts, in the order of their ANSI codes.
+const COLORS: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// How the text is rendered, the same throughout a template.
+struct Renderer<'a, E> {
+    fields: &'a [(&'a str, String)],
+    escape: E,
+    /// Whether style tags become escape codes, rather than nothing.
+    styled: bool,
+}
+
+fn render<E>(format: &str, fields: &[(&str, String)], escape: E, styled: bool) -> String
+where
+    E: Fn(&str) -> String,
+{
+    let format = format.replace("\\t", "\t").replace("\\n", "\n");
+
+    let renderer = Renderer {
+        fields,
+        escape,
+        styled,
+    };
+
+    let mut rendered = String::new();
+    let mut rest = format.as_str();
+
+    renderer.render_block(&mut rest, &mut rendered, false);
+
+    rendered
+}
+
+impl<E> Renderer<'_, E>
+where
+    E: Fn(&str) -> String,
+{
+    /// Renders `rest` into `rendered` up to the end of the template or, if
+    /// `nested`, up to the `{/}` that closes the block, and consumes it.
+    fn render_block(&self, rest: &mut &str, rendered: &mut String, nested: bool) {
+        while let Some(open) = rest.find('{') {
+            rendered.push_str(&rest[..open]);
+            *rest = &rest[open..];
+
+            let close = match rest.find('}') {
+                Some(close) => close,
+                None => break,
+            };
+
+            let placeholder = &rest[1..close];
+
+            if placeholder == "/" && nested {
+                *rest = &rest[close + 1..];
+
+                return;
+            }
+
+            if let Some(condition) = placeholder.strip_prefix('?') {
+                if let Some(value) = render_field(condition, self.fields) {
+                    *rest = &rest[close + 1..];
+
+                    // Rendered either way, to get past it.
+                    let mut block = String::new();
+                    self.render_block(rest, &mut block, true);
+
+                    if has_value(&value) {
+                        rendered.push_str(&block);
+                    }
+
+                    continue;
+                }
+            } else if let Some(code) = style(placeholder) {
+                if self.styled {
+                    rendered.push_str(&code);
+                }
+
+                *rest = &rest[close + 1..];
+
+                continue;
+            } else if let Some(value) = render_field(placeholder, self.fields) {
+                rendered.push_str(&(self.escape)(&value));
+                *rest = &rest[close + 1..];
+
+                continue;
+            }
+
+            rendered.push('{');
+            *rest = &rest[1..];
+        }
+
+        rendered.push_str(rest);
+        *rest = "";
+    }
+}
+
+/// The escape code for a style tag such as `bold` or `color:green`.
+fn style(tag: &str) -> Option<String> {
+    let code = match tag {
+        "reset" => 0,
+        "bold" => 1,
+        "dim" => 2,
+        "italic" => 3,
+        "underline" => 4,
+        "color:default" => 39,
+        _ => {
+            30 + COLORS
+                .iter()
+                .position(|c| Some(*c) == tag.strip_prefix("color:"))?
+        }
+    };
+
+    Some(format!("\x1b[{}m", code))
+}
+
+/// The value of a placeholder such as `title`, `upper(title)` or
+/// `title:>30`, unless it names no field or has a width that cannot be read.
+fn render_field(placeholder: &str, fields: &[(&str, String)]) -> Option<String> {
+    match placeholder.split_once(':') {
+        Some(("progress_bar", width)) => progress_bar(fields, width.parse().ok()?),
+        Some((expression, width)) => fit(&evaluate(expression, fields)?, width),
+        None if placeholder == "progress_bar" => progress_bar(fields, PROGRESS_BAR_WIDTH),
+        None => evaluate(placeholder, fields),
+    }
+}
+
+/// `width` characters, as many of them filled as the `progress` field, in
+/// percent, says. Empty while it is unknown.
+fn progress_bar(fields: &[(&str, String)], width: usize) -> Option<String> {
+    let progress: f64 = evaluate("progress", fields)?.parse().unwrap_or(0.0);
+    let filled = ((progress / 100.0 * width as f64).round() as usize).min(width);
+
+    Some(format!(
+        "{}{}",
+        "▰".repeat(filled),
+        "▱".repeat(width - filled)
+    ))
+}
+
+fn evaluate(expression: &str, fields: &[(&str, String)]) -> Option<String> {
+    let expression = expression.trim();
+
+    if let Some((_, value)) = fields.iter().find(|(name, _)| *name == expression) {
+        return Some(value.clone());
+    }
+
+    let (function, argument) = expression.strip_suffix(')')?.split_once('(')?;
+    let argument = evaluate(argument, fields)?;
+
+    match function.trim() {
+        "upper" => Some(argument.to_uppercase()),
+        "lower" => Some(argument.to_lowercase()),
+        "trim" => Some(argument.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Whether a field is known, as unknown ones are empty or `-`.
+fn has_value(value: &str) -> bool {
+    !value.trim().is_empty() && value != "-"
+}
+
+/// Cuts `value` short or pads it to the width given by `spec`, e.g. `30`,
+/// `<30`, `>30` or `^30`.
+fn fit(value: &str, spec: &str) -> Option<String> {
+    let (align, width) = match spec.chars().next()? {
+        align @ ('<' | '>' | '^') => (align, &spec[1..]),
+        _ => ('<', spec),
+    };
+
+    let width: usize = width.parse().ok()?;
+
+    let mut fitted = String::new();
+    let mut used = 0;
+
+    match value.width() > width {
+        true => {
+            // Room is kept for the ellipsis, unless there is none at all.
+            let room = width.saturating_sub(1);
+
+            for grapheme in value.graphemes(true) {
+                if used + grapheme.width() > room {
+                    break;
+                }
+
+                fitted.push_str(grapheme);
+                used += grapheme.width();
+            }
+
+            if width > 0 {
+                fitted.push('…');
+                used += 1;
+            }
+        }
+        false => {
+            fitted.push_str(value);
+            used = value.width();
+        }
+    }
+
+    let padding = width - used;
+
+    Some(match align {
+        '>' => format!("{}{}", " ".repeat(padding), fitted),
+        '^' => format!(
+            "{}{}{}",
+            " ".repeat(padding / 2),
+            fitted,
+            " ".repeat(padding - padding / 2)
+        ),
+        _ => format!("{}{}", fitted, " ".repeat(padding)),
+    })
+}
+
+/// Escapes field values for Pango markup, as used by Waybar and in
+/// notification bodies, so that titles like "Tom & Jerry" do not break it.
+fn escape_markup(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,264 @@
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use ksni::blocking::TrayMethods;
+use ksni::menu::{RadioGroup, RadioItem, StandardItem};
+use ksni::{MenuItem, Orientation, ToolTip};
+use multiplayerctl::{Error, PlaybackStatus, PlayerManager, Result};
+
+use crate::{daemon, follow};
+
+/// Shows a StatusNotifierItem for the current player until watching the
+/// players fails, running commands through the daemon if `forward` is set
+/// and one is running.
+pub fn run(manager: PlayerManager, forward: bool) -> Result<()> {
+    let manager = Arc::new(manager);
+
+    let tray = Tray {
+        manager: Arc::clone(&manager),
+        forward,
+        state: State::default(),
+    };
+
+    let handle = tray.spawn().map_err(|why| {
+        Error::Other(format!(
+            "Failed to show the tray icon (is a StatusNotifierItem host running?): {}",
+            why
+        ))
+    })?;
+
+    follow::watch(
+        manager,
+        |_| None,
+        |manager| {
+            let state = State::take(manager);
+
+            match handle.update(|tray| tray.state = state) {
+                Some(()) => Ok(ControlFlow::Continue(())),
+                None => Ok(ControlFlow::Break(())),
+            }
+        },
+    )
+}
+
+struct Tray {
+    manager: Arc<PlayerManager>,
+    forward: bool,
+    state: State,
+}
+
+/// What the icon, tooltip and menu show.
+#[derive(Default)]
+struct State {
+    /// Each player with its display name.
+    players: Vec<(String, String)>,
+    current: Option<String>,
+    status: Option<PlaybackStatus>,
+    track: Option<String>,
+}
+
+impl State {
+    fn take(manager: &PlayerManager) -> State {
+        let players = manager
+            .players()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| (name.clone(), manager.display_name(&name)))
+            .collect();
+
+        let player = match manager.current_or_init() {
+            Ok(p) => p,
+            Err(_) => {
+                return State {
+                    players,
+                    ..State::default()
+                }
+            }
+        };
+
+        let metadata = player.metadata().unwrap_or_default();
+
+        let track = match (metadata.artist(), metadata.title()) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+            (None, Some(title)) => Some(title.to_string()),
+            _ => None,
+        };
+
+        State {
+            players,
+            current: Some(player.name().to_string()),
+            status: player.status().ok(),
+            track,
+        }
+    }
+
+    fn current_display_name(&self) -> Option<&str> {
+        self.players
+            .iter()
+            .find(|(name, _)| Some(name) == self.current.as_ref())
+            .map(|(_, display_name)| display_name.as_str())
+    }
+}
+
+impl Tray {
+    /// Runs a command, reporting failures as there is nowhere to show them.
+    fn run(&self, args: &[&str]) {
+        let args = args.iter().map(|a| a.to_string()).collect();
+
+        if let Err(why) = daemon::command(&self.manager, self.forward, args) {
+            eprintln!("{}", why);
+        }
+    }
+
+    fn switch(&self, player: &str) {
+        self.run(&["--player", player, "switch"]);
+    }
+}
+
+impl ksni::Tray for Tray {
+    fn id(&self) -> String {
+        String::from("multiplayerctl")
+    }
+
+    fn title(&self) -> String {
+        String::from("multiplayerctl")
+    }
+
+    fn icon_name(&self) -> String {
+        String::from(match self.state.status {
+            Some(PlaybackStatus::Playing) => "media-playback-start",
+            Some(PlaybackStatus::Paused) => "media-playback-pause",
+            _ => "media-playback-stop",
+        })
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let description = match (self.state.current_display_name(), self.state.status) {
+            (Some(player), Some(status)) => format!("{}: {}", player, status),
+            (Some(player), None) => player.to_string(),
+            _ => String::from("No players"),
+        };
+
+        ToolTip {
+            title: self
+                .state
+                .track
+                .clone()
+                .unwrap_or_else(|| String::from("multiplayerctl")),
+            description,
+            ..ToolTip::default()
+        }
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.run(&["toggle"]);
+    }
+
+    fn secondary_activate(&mut self, _x: i32, _y: i32) {
+        self.run(&["next"]);
+    }
+
+    /// Scrolling over the icon switches between the players.
+    fn scroll(&mut self, delta: i32, orientation: Orientation) {
+        if orientation != Orientation::Vertical || delta == 0 {
+            return;
+        }
+
+        match delta > 0 {
+            true => self.run(&["switch"]),
+            false => self.run(&["switch", "--back"]),
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let playing = self.state.status == Some(PlaybackStatus::Playing);
+        let has_player = self.state.current.is_some();
+
+        let mut menu = vec![
+            StandardItem {
+                label: self
+                    .state
+                    .track
+                    .clone()
+                    .unwrap_or_else(|| String::from("Nothing playing")),
+                enabled: false,
+                ..StandardItem::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: String::from(if playing { "Pause" } else { "Play" }),
+                icon_name: String::from(match playing {
+                    true => "media-playback-pause",
+                    false => "media-playback-start",
+                }),
+                enabled: has_player,
+                activate: Box::new(|tray: &mut Self| tray.run(&["toggle"])),
+                ..StandardItem::default()
+            }
+            .into(),
+            StandardItem {
+                label: String::from("Next"),
+                icon_name: String::from("media-skip-forward"),
+                enabled: has_player,
+                activate: Box::new(|tray: &mut Self| tray.run(&["next"])),
+                ..StandardItem::default()
+            }
+            .into(),
+            StandardItem {
+                label: String::from("Previous"),
+                icon_name: String::from("media-skip-backward"),
+                enabled: has_player,
+                activate: Box::new(|tray: &mut Self| tray.run(&["previous"])),
+                ..StandardItem::default()
+            }
+            .into(),
+        ];
+
+        if !self.state.players.is_empty() {
+            let selected = self
+                .state
+                .players
+                .iter()
+                .position(|(name, _)| Some(name) == self.state.current.as_ref());
+
+            menu.push(MenuItem::Separator);
+            menu.push(
+                RadioGroup {
+                    // Nothing is selected while there is no current player.
+                    selected: selected.unwrap_or(usize::MAX),
+                    select: Box::new(|tray: &mut Self, index| {
+                        let player = tray.state.players.get(index).map(|(n, _)| n.clone());
+
+                        if let Some(player) = player {
+                            tray.switch(&player);
+                        }
+                    }),
+                    options: self
+                        .state
+                        .players
+                        .iter()
+                        .map(|(_, display_name)| RadioItem {
+                            label: display_name.clone(),
+                            ..RadioItem::default()
+                        })
+                        .collect(),
+                }
+                .into(),
+            );
+        }
+
+        menu.push(MenuItem::Separator);
+        menu.push(
+            StandardItem {
+                label: String::from("Quit"),
+                icon_name: String::from("application-exit"),
+                activate: Box::new(|_| std::process::exit(0)),
+                ..StandardItem::default()
+            }
+            .into(),
+        );
+
+        menu
+    }
+}
@@ -0,0 +1,296 @@
+use std::time::Duration;
+
+use multiplayerctl::{PlaybackStatus, PlayerManager, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::Value;
+
+use crate::{daemon, format_duration};
+
+/// How often the players are looked at again, which also moves the progress
+/// bar along.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+const SEEK_STEP: &str = "5s";
+const VOLUME_STEP: &str = "0.05";
+
+const HELP: &str = "↑↓ switch  space play/pause  n/p next/previous  ←→ seek  +/- volume  q quit";
+
+/// Shows the players and the current track until the user quits, running
+/// commands through the daemon if `forward` is set and one is running.
+pub fn run(manager: PlayerManager, forward: bool) -> Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = show(&mut terminal, &manager, forward);
+
+    ratatui::restore();
+
+    result
+}
+
+fn show(terminal: &mut DefaultTerminal, manager: &PlayerManager, forward: bool) -> Result<()> {
+    // The outcome of the last key press, if it failed.
+    let mut message: Option<String> = None;
+
+    loop {
+        let snapshot = Snapshot::take(manager, forward);
+
+        terminal.draw(|frame| draw(frame, &snapshot, message.as_deref()))?;
+
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+
+        let key = match event::read()? {
+            Event::Key(k) if k.kind == KeyEventKind::Press => k,
+            _ => continue,
+        };
+
+        let args = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Up | KeyCode::Char('k') => snapshot.neighbour(-1).map(switch),
+            KeyCode::Down | KeyCode::Char('j') => snapshot.neighbour(1).map(switch),
+            KeyCode::Char(' ') => Some(vec![String::from("toggle")]),
+            KeyCode::Char('n') => Some(vec![String::from("next")]),
+            KeyCode::Char('p') => Some(vec![String::from("previous")]),
+            KeyCode::Left | KeyCode::Char('h') => Some(adjust("seek", '-', SEEK_STEP)),
+            KeyCode::Right | KeyCode::Char('l') => Some(adjust("seek", '+', SEEK_STEP)),
+            KeyCode::Char('+') | KeyCode::Char('=') => Some(adjust("volume", '+', VOLUME_STEP)),
+            KeyCode::Char('-') => Some(adjust("volume", '-', VOLUME_STEP)),
+            _ => None,
+        };
+
+        if let Some(args) = args {
+            message = daemon::command(manager, forward, args)
+                .err()
+                .map(|why| why.to_string());
+        }
+    }
+}
+
+fn switch(player: &str) -> Vec<String> {
+    vec![
+        String::from("--player"),
+        player.to_string(),
+        String::from("switch"),
+    ]
+}
+
+fn adjust(command: &str, sign: char, step: &str) -> Vec<String> {
+    vec![command.to_string(), format!("{}{}", sign, step)]
+}
+
+/// What is shown, looked up anew on every refresh.
+#[derive(Default)]
+struct Snapshot {
+    players: Vec<PlayerRow>,
+    current: Option<String>,
+    status: Option<PlaybackStatus>,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    /// In microseconds, like the position.
+    length: Option<i64>,
+    position: Option<i64>,
+    volume: Option<f64>,
+}
+
+struct PlayerRow {
+    name: String,
+    display_name: String,
+    status: Option<PlaybackStatus>,
+    track: String,
+}
+
+impl Snapshot {
+    fn take(manager: &PlayerManager, forward: bool) -> Snapshot {
+        let players = manager
+            .players()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| {
+                let player = manager.player(&name);
+                let metadata = player.metadata().unwrap_or_default();
+
+                let track = match (metadata.artist(), metadata.title()) {
+                    (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+                    (None, Some(title)) => title.to_string(),
+                    _ => String::new(),
+                };
+
+                PlayerRow {
+                    display_name: manager.display_name(&name),
+                    status: player.status().ok(),
+                    track,
+                    name,
+                }
+            })
+            .collect();
+
+        // The daemon may have a current player of its own.
+        let current = daemon::command(
+            manager,
+            forward,
+            vec![String::from("--json"), String::from("player")],
+        )
+        .ok()
+        .and_then(|output| serde_json::from_str::<Value>(&output).ok())
+        .and_then(|value| value["player"].as_str().map(String::from));
+
+        let mut snapshot = Snapshot {
+            players,
+            ..Snapshot::default()
+        };
+
+        let name = match current {
+            Some(n) => n,
+            None => return snapshot,
+        };
+
+        let player = manager.player(&name);
+        let metadata = player.metadata().unwrap_or_default();
+
+        snapshot.status = player.status().ok();
+        snapshot.title = metadata.title().map(String::from);
+        snapshot.artist = metadata.artist();
+        snapshot.album = metadata.album().map(String::from);
+        snapshot.length = metadata.length();
+        snapshot.position = player.position().ok();
+        snapshot.volume = player.volume().ok();
+        snapshot.current = Some(name);
+
+        snapshot
+    }
+
+    /// The player `offset` rows away from the current one.
+    fn neighbour(&self, offset: isize) -> Option<&str> {
+        let index = self
+            .players
+            .iter()
+            .position(|p| Some(&p.name) == self.current.as_ref())?;
+
+        self.players
+            .get(index.checked_add_signed(offset)?)
+            .map(|p| p.name.as_str())
+    }
+}
+
+fn draw(frame: &mut Frame, snapshot: &Snapshot, message: Option<&str>) {
+    let [players, now_playing, footer] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(7),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    draw_players(frame, players, snapshot);
+    draw_now_playing(frame, now_playing, snapshot);
+
+    let footer_text = match message {
+        Some(m) => Paragraph::new(m).fg(Color::Red),
+        None => Paragraph::new(HELP).dim(),
+    };
+
+    frame.render_widget(footer_text, footer);
+}
+
+fn draw_players(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let items: Vec<ListItem> = snapshot
+        .players
+        .iter()
+        .map(|p| {
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} ", status_icon(p.status))),
+                Span::raw(p.display_name.clone()).bold(),
+                Span::raw(format!("  {}", p.track)),
+            ]))
+        })
+        .collect();
+
+    let selected = snapshot
+        .players
+        .iter()
+        .position(|p| Some(&p.name) == snapshot.current.as_ref());
+
+    let list = List::new(items)
+        .block(Block::bordered().title(" Players "))
+        .highlight_style(Style::new().reversed());
+
+    frame.render_stateful_widget(
+        list,
+        area,
+        &mut ListState::default().with_selected(selected),
+    );
+}
+
+fn draw_now_playing(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let block = Block::bordered().title(" Now playing ");
+    let inner = block.inner(area);
+
+    frame.render_widget(block, area);
+
+    let [text, progress, volume] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(inner);
+
+    if snapshot.current.is_none() {
+        frame.render_widget(Paragraph::new("No players").dim(), text);
+        return;
+    }
+
+    let lines = vec![
+        Line::from(format!(
+            "{} {}",
+            status_icon(snapshot.status),
+            snapshot.title.as_deref().unwrap_or("Unknown track")
+        ))
+        .bold(),
+        Line::from(snapshot.artist.clone().unwrap_or_default()),
+        Line::from(snapshot.album.clone().unwrap_or_default()).dim(),
+    ];
+
+    frame.render_widget(Paragraph::new(lines), text);
+
+    let position = snapshot.position.unwrap_or(0);
+
+    let (ratio, label) = match snapshot.length.filter(|l| *l > 0) {
+        Some(length) => (
+            (position as f64 / length as f64).clamp(0.0, 1.0),
+            format!(
+                "{} / {}",
+                format_duration(position),
+                format_duration(length)
+            ),
+        ),
+        None => (0.0, format_duration(position)),
+    };
+
+    frame.render_widget(
+        Gauge::default()
+            .ratio(ratio)
+            .label(label)
+            .gauge_style(Style::new().fg(Color::Blue)),
+        progress,
+    );
+
+    let volume_text = match snapshot.volume {
+        Some(v) => format!("Volume {:.0}%", v * 100.0),
+        None => String::from("Volume -"),
+    };
+
+    frame.render_widget(Paragraph::new(volume_text).dim(), volume);
+}
+
+fn status_icon(status: Option<PlaybackStatus>) -> &'static str {
+    match status {
+        Some(PlaybackStatus::Playing) => "▶",
+        Some(PlaybackStatus::Paused) => "⏸",
+        _ => "⏹",
+    }
+}
@@ -0,0 +1,68 @@
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use multiplayerctl::{Error, PlaybackStatus, PlayerManager, Result};
+
+use crate::follow;
+
+/// Blocks until the current player (or the one given with `--player`) exists,
+/// has `status` and reports every `metadata` field with the expected value.
+/// Fails with [`Error::Timeout`] if that does not happen within `timeout`.
+pub fn run(
+    manager: PlayerManager,
+    status: Option<PlaybackStatus>,
+    metadata: Vec<(String, String)>,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+
+    follow::watch(
+        Arc::new(manager),
+        |_| deadline.map(|d| d.saturating_duration_since(Instant::now())),
+        |manager| {
+            if is_met(manager, status, &metadata) {
+                return Ok(ControlFlow::Break(()));
+            }
+
+            match (deadline, timeout) {
+                (Some(d), Some(t)) if Instant::now() >= d => Err(Error::Timeout(format!(
+                    "The condition was not met within {:?}.",
+                    t
+                ))),
+                _ => Ok(ControlFlow::Continue(())),
+            }
+        },
+    )
+}
+
+fn is_met(
+    manager: &PlayerManager,
+    status: Option<PlaybackStatus>,
+    metadata: &[(String, String)],
+) -> bool {
+    // Fails while there are no players, or the targeted one is not running.
+    let player = match manager.current_or_init() {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+
+    if status.is_some_and(|s| player.status() != Ok(s)) {
+        return false;
+    }
+
+    if metadata.is_empty() {
+        return true;
+    }
+
+    let values = match player.metadata() {
+        Ok(m) => m,
+        Err(_) => return false,
+    };
+
+    metadata.iter().all(|(key, expected)| {
+        values
+            .get(key)
+            .is_some_and(|value| value.to_string() == *expected)
+    })
+}
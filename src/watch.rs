@@ -0,0 +1,129 @@
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+const HEADER_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+
+/// Waits for a file to be written, no matter which process wrote it or
+/// whether it was replaced. Backed by inotify on the file's directory, which
+/// has to exist.
+pub struct FileWatcher {
+    inotify: OwnedFd,
+    path: PathBuf,
+    file_name: OsString,
+}
+
+impl FileWatcher {
+    pub fn new(file_path: &Path) -> Result<Self> {
+        let (dir, file_name) = match (file_path.parent(), file_path.file_name()) {
+            (Some(d), Some(f)) => (d, f.to_os_string()),
+            _ => {
+                return Err(Error::Other(format!(
+                    "Cannot watch {}",
+                    file_path.display()
+                )))
+            }
+        };
+
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+
+        if fd == -1 {
+            return Err(watch_error(file_path, io::Error::last_os_error()));
+        }
+
+        let inotify = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let dir = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|why| Error::Other(format!("Invalid directory {}: {}", dir.display(), why)))?;
+
+        // Writes show up as IN_CLOSE_WRITE, atomic replacements as IN_MOVED_TO.
+        let watch = unsafe {
+            libc::inotify_add_watch(
+                inotify.as_raw_fd(),
+                dir.as_ptr(),
+                libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO,
+            )
+        };
+
+        if watch == -1 {
+            return Err(watch_error(file_path, io::Error::last_os_error()));
+        }
+
+        Ok(Self {
+            inotify,
+            path: file_path.to_path_buf(),
+            file_name,
+        })
+    }
+
+    /// Blocks until the file has been written.
+    pub fn wait(&self) -> Result<()> {
+        while !self.read_change()? {}
+
+        Ok(())
+    }
+
+    /// Reads the pending events and returns whether the file was among them.
+    /// Blocks if there are none, so when polling the fd, only call this once
+    /// it is readable.
+    pub fn read_change(&self) -> Result<bool> {
+        let mut buf = [0u8; 4096];
+
+        loop {
+            let n = unsafe {
+                libc::read(
+                    self.inotify.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+
+            if n == -1 {
+                let why = io::Error::last_os_error();
+
+                if why.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                return Err(watch_error(&self.path, why));
+            }
+
+            return Ok(self.contains_file_event(&buf[..n as usize]));
+        }
+    }
+
+    /// Checks whether a buffer of `inotify_event`s mentions the watched file.
+    fn contains_file_event(&self, mut events: &[u8]) -> bool {
+        while events.len() >= HEADER_SIZE {
+            let event =
+                unsafe { std::ptr::read_unaligned(events.as_ptr() as *const libc::inotify_event) };
+
+            let end = (HEADER_SIZE + event.len as usize).min(events.len());
+
+            // The name is padded with NUL bytes.
+            let name = events[HEADER_SIZE..end].split(|b| *b == 0).next();
+
+            if name.map(OsStr::from_bytes) == Some(self.file_name.as_os_str()) {
+                return true;
+            }
+
+            events = &events[end..];
+        }
+
+        false
+    }
+}
+
+impl AsRawFd for FileWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify.as_raw_fd()
+    }
+}
+
+fn watch_error(path: &Path, why: io::Error) -> Error {
+    Error::Other(format!("Failed to watch {}: {}", path.display(), why))
+}
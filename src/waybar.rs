@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use multiplayerctl::{PlayerManager, Result};
+use serde_json::json;
+
+use crate::template::render_markup;
+use crate::{follow, player_fields, playing, shows_position};
+
+/// Prints a line of Waybar custom module JSON whenever the current player or
+/// anything shown about it changes.
+pub fn run(manager: PlayerManager, options: follow::Options) -> Result<()> {
+    let config = &manager.config().waybar;
+
+    // The position changes without the player announcing it.
+    let tick = (shows_position(&config.text) || shows_position(&config.tooltip))
+        .then_some(Duration::from_secs(1));
+
+    follow::run(
+        manager,
+        options,
+        |manager| tick.filter(|_| playing(manager)),
+        render,
+    )
+}
+
+fn render(manager: &PlayerManager) -> String {
+    let player = match manager.current_or_init() {
+        Ok(p) => p,
+        Err(_) => {
+            return json!({ "text": "", "tooltip": "", "class": "none", "alt": "" }).to_string()
+        }
+    };
+
+    let fields = player_fields(manager, &player);
+
+    let class = player
+        .status()
+        .map_or(String::from("stopped"), |s| s.to_string().to_lowercase());
+
+    json!({
+        "text": render_markup(&manager.config().waybar.text, &fields),
+        "tooltip": render_markup(&manager.config().waybar.tooltip, &fields),
+        "class": class,
+        "alt": player.name(),
+    })
+    .to_string()
+}